@@ -0,0 +1,76 @@
+use std::time::Instant;
+
+use crawler::modules::storage::disk::DiskFrontierConfig;
+use crawler::modules::storage::frontier::{Frontier, FrontierConfig, FrontierPoll};
+use crawler::task::Task;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const URLS: usize = 20_000;
+
+fn urls() -> Vec<String> {
+    (0..URLS)
+        .map(|i| format!("https://host{}.example.com/page/{}", i % 64, i))
+        .collect()
+}
+
+fn config(disk: bool) -> FrontierConfig {
+    let disk = disk.then(|| {
+        let path = std::env::temp_dir()
+            .join(format!("thamur-bench-{}", std::process::id()))
+            .join("frontier.redb");
+        let _ = std::fs::remove_file(&path);
+        DiskFrontierConfig {
+            path: path.to_string_lossy().to_string(),
+            ..Default::default()
+        }
+    });
+    FrontierConfig {
+        default_delay_ms: 0,
+        disk,
+        ..Default::default()
+    }
+}
+
+/// Queues every URL, then rediscovers each one, as links to already queued
+/// pages are in a real crawl.
+fn enqueue(frontier: &mut Frontier, urls: &[String]) {
+    for url in urls.iter().chain(urls) {
+        frontier.push(Task::new(url.clone())).unwrap();
+    }
+}
+
+fn drain(frontier: &mut Frontier) -> usize {
+    let mut polled = 0;
+    while let FrontierPoll::Ready(_) = frontier.poll(Instant::now()).unwrap() {
+        polled += 1;
+    }
+    polled
+}
+
+fn bench_frontier(c: &mut Criterion) {
+    let urls = urls();
+    let mut group = c.benchmark_group("frontier");
+    group.throughput(Throughput::Elements(URLS as u64));
+    group.sample_size(10);
+
+    for (name, disk) in [("memory", false), ("disk", true)] {
+        group.bench_function(BenchmarkId::new("enqueue", name), |b| {
+            b.iter(|| {
+                let mut frontier = Frontier::open(config(disk)).unwrap();
+                enqueue(&mut frontier, &urls);
+                frontier.len()
+            })
+        });
+        group.bench_function(BenchmarkId::new("enqueue_poll", name), |b| {
+            b.iter(|| {
+                let mut frontier = Frontier::open(config(disk)).unwrap();
+                enqueue(&mut frontier, &urls);
+                drain(&mut frontier)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_frontier);
+criterion_main!(benches);
@@ -0,0 +1,187 @@
+use std::collections::HashSet;
+use std::sync::{Mutex, RwLock};
+use std::thread;
+use std::time::Instant;
+
+use crawler::canonical::canonicalize;
+use crawler::modules::storage::frontier::{Frontier, FrontierConfig, FrontierPoll};
+use crawler::modules::storage::sharded::ShardedFrontier;
+use crawler::modules::storage::state::SharedState;
+use crawler::seen::{SeenConfig, SeenKind};
+use crawler::task::Task;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const URLS: usize = 20_000;
+const WORKERS: [usize; 4] = [1, 2, 4, 8];
+
+fn urls() -> Vec<String> {
+    (0..URLS)
+        .map(|i| format!("https://host{}.example.com/page/{}", i % 64, i))
+        .collect()
+}
+
+/// Splits `urls` between `workers` threads and runs `claim` on each.
+fn run(workers: usize, urls: &[String], claim: impl Fn(&str) -> bool + Sync) -> usize {
+    let chunk = urls.len().div_ceil(workers);
+    thread::scope(|scope| {
+        let handles: Vec<_> = urls
+            .chunks(chunk)
+            .map(|chunk| scope.spawn(|| chunk.iter().filter(|url| claim(url)).count()))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).sum()
+    })
+}
+
+fn bench_try_claim(c: &mut Criterion) {
+    let urls = urls();
+    let mut group = c.benchmark_group("try_claim");
+    group.throughput(Throughput::Elements(URLS as u64));
+
+    for workers in WORKERS {
+        // The previous design: one global lock around a set of full URLs.
+        group.bench_with_input(
+            BenchmarkId::new("global_rwlock", workers),
+            &workers,
+            |b, &workers| {
+                b.iter(|| {
+                    let visited = RwLock::new(HashSet::new());
+                    run(workers, &urls, |url| {
+                        let url = canonicalize(url).unwrap();
+                        visited.write().unwrap().insert(url)
+                    })
+                })
+            },
+        );
+
+        for kind in [SeenKind::Fingerprint, SeenKind::Bloom] {
+            group.bench_with_input(
+                BenchmarkId::new(format!("{kind:?}").to_lowercase(), workers),
+                &workers,
+                |b, &workers| {
+                    b.iter(|| {
+                        let state = SharedState::new();
+                        state
+                            .set_visited_config(&SeenConfig {
+                                kind,
+                                ..Default::default()
+                            })
+                            .unwrap();
+                        run(workers, &urls, |url| state.try_claim(url).unwrap())
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+/// Splits `urls` between `workers` threads, each of which queues its share
+/// with `push` and then takes tasks with `poll` until the queue runs dry.
+fn run_queue(
+    workers: usize,
+    urls: &[String],
+    push: impl Fn(&str) + Sync,
+    poll: impl Fn() -> bool + Sync,
+) -> usize {
+    let chunk = urls.len().div_ceil(workers);
+    thread::scope(|scope| {
+        let handles: Vec<_> = urls
+            .chunks(chunk)
+            .map(|chunk| {
+                scope.spawn(|| {
+                    chunk.iter().for_each(|url| push(url));
+                    std::iter::from_fn(|| poll().then_some(())).count()
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).sum()
+    })
+}
+
+fn frontier_config() -> FrontierConfig {
+    FrontierConfig {
+        default_delay_ms: 0,
+        ..Default::default()
+    }
+}
+
+fn bench_enqueue_poll(c: &mut Criterion) {
+    let urls = urls();
+    let mut group = c.benchmark_group("enqueue_poll");
+    group.throughput(Throughput::Elements(URLS as u64));
+    group.sample_size(20);
+
+    for workers in WORKERS {
+        // The previous design: one lock around a single frontier.
+        group.bench_with_input(
+            BenchmarkId::new("global_mutex", workers),
+            &workers,
+            |b, &workers| {
+                b.iter(|| {
+                    let frontier = Mutex::new(Frontier::new(frontier_config()));
+                    run_queue(
+                        workers,
+                        &urls,
+                        |url| {
+                            let task = Task::new(canonicalize(url).unwrap());
+                            frontier.lock().unwrap().push(task).unwrap();
+                        },
+                        || {
+                            let poll = frontier.lock().unwrap().poll(Instant::now()).unwrap();
+                            matches!(poll, FrontierPoll::Ready(_))
+                        },
+                    )
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("sharded", workers),
+            &workers,
+            |b, &workers| {
+                b.iter(|| {
+                    let frontier = ShardedFrontier::new(frontier_config());
+                    run_queue(
+                        workers,
+                        &urls,
+                        |url| {
+                            let task = Task::new(canonicalize(url).unwrap());
+                            frontier.push(task).unwrap();
+                        },
+                        || {
+                            matches!(
+                                frontier.poll(Instant::now()).unwrap(),
+                                FrontierPoll::Ready(_)
+                            )
+                        },
+                    )
+                })
+            },
+        );
+
+        // Everything a worker does to queue and take a URL: canonicalizing,
+        // the visited and trap checks, scoring and the budget.
+        group.bench_with_input(
+            BenchmarkId::new("shared_state", workers),
+            &workers,
+            |b, &workers| {
+                b.iter(|| {
+                    let state = SharedState::new();
+                    state.set_frontier_config(frontier_config()).unwrap();
+                    run_queue(
+                        workers,
+                        &urls,
+                        |url| {
+                            state.add_url(url.to_string()).unwrap();
+                        },
+                        || matches!(state.poll_task().unwrap(), FrontierPoll::Ready(_)),
+                    )
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_try_claim, bench_enqueue_poll);
+criterion_main!(benches);
@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+/// Limits applied to each domain separately.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DomainBudget {
+    pub max_pages: Option<u64>,
+    pub max_depth: Option<u32>,
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    pub max_pages: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub max_duration_secs: Option<u64>,
+    /// Default limits for every domain.
+    pub per_domain: DomainBudget,
+    /// Overrides for specific hosts; unset fields fall back to `per_domain`.
+    pub domains: HashMap<String, DomainBudget>,
+}
+
+/// The global budget that ended a crawl.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BudgetExhausted {
+    Pages(u64),
+    Bytes(u64),
+    Duration(Duration),
+}
+
+impl fmt::Display for BudgetExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetExhausted::Pages(limit) => write!(f, "page budget of {limit} pages"),
+            BudgetExhausted::Bytes(limit) => write!(f, "byte budget of {limit} bytes"),
+            BudgetExhausted::Duration(limit) => {
+                write!(f, "time budget of {}s", limit.as_secs())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomainUsage {
+    pub pages: u64,
+    pub bytes: u64,
+}
+
+/// What a crawl has consumed so far, as saved in a checkpoint.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetUsage {
+    pub pages: u64,
+    pub bytes: u64,
+    pub elapsed_secs: u64,
+    pub domains: HashMap<String, DomainUsage>,
+}
+
+/// Tracks what a crawl has consumed against its `BudgetConfig`.
+#[derive(Debug)]
+pub struct CrawlBudget {
+    config: BudgetConfig,
+    started: Instant,
+    pages: AtomicU64,
+    bytes: AtomicU64,
+    domains: DashMap<String, DomainUsage>,
+}
+
+impl CrawlBudget {
+    pub fn new(config: BudgetConfig) -> Self {
+        Self {
+            config,
+            started: Instant::now(),
+            pages: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            domains: DashMap::new(),
+        }
+    }
+
+    /// Continues a crawl that already consumed `usage`.
+    pub fn resume(config: BudgetConfig, usage: &BudgetUsage) -> Self {
+        let now = Instant::now();
+        let elapsed = Duration::from_secs(usage.elapsed_secs);
+        Self {
+            config,
+            started: now.checked_sub(elapsed).unwrap_or(now),
+            pages: AtomicU64::new(usage.pages),
+            bytes: AtomicU64::new(usage.bytes),
+            domains: usage
+                .domains
+                .iter()
+                .map(|(host, usage)| (host.clone(), *usage))
+                .collect(),
+        }
+    }
+
+    pub fn usage(&self) -> BudgetUsage {
+        BudgetUsage {
+            pages: self.pages(),
+            bytes: self.bytes(),
+            elapsed_secs: self.elapsed().as_secs(),
+            domains: self
+                .domains
+                .iter()
+                .map(|entry| (entry.key().clone(), *entry.value()))
+                .collect(),
+        }
+    }
+
+    pub fn config(&self) -> &BudgetConfig {
+        &self.config
+    }
+
+    pub fn pages(&self) -> u64 {
+        self.pages.load(Ordering::SeqCst)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Returns the global budget that has run out, if any.
+    pub fn check(&self) -> Result<(), BudgetExhausted> {
+        if let Some(limit) = self.config.max_pages {
+            if self.pages() >= limit {
+                return Err(BudgetExhausted::Pages(limit));
+            }
+        }
+        if let Some(limit) = self.config.max_bytes {
+            if self.bytes() >= limit {
+                return Err(BudgetExhausted::Bytes(limit));
+            }
+        }
+        if let Some(secs) = self.config.max_duration_secs {
+            let limit = Duration::from_secs(secs);
+            if self.elapsed() >= limit {
+                return Err(BudgetExhausted::Duration(limit));
+            }
+        }
+        Ok(())
+    }
+
+    fn domain_limit<T>(&self, host: &str, field: impl Fn(&DomainBudget) -> Option<T>) -> Option<T> {
+        self.config
+            .domains
+            .get(host)
+            .and_then(&field)
+            .or_else(|| field(&self.config.per_domain))
+    }
+
+    /// Whether a URL on `host` found at `depth` may be queued at all.
+    pub fn admits_depth(&self, host: &str, depth: u32) -> bool {
+        self.domain_limit(host, |budget| budget.max_depth)
+            .is_none_or(|max_depth| depth <= max_depth)
+    }
+
+    /// Whether `host` still has page and byte quota left for another fetch.
+    pub fn admits_fetch(&self, host: &str) -> bool {
+        let usage = self
+            .domains
+            .get(host)
+            .map(|usage| *usage)
+            .unwrap_or_default();
+        self.domain_admits(host, &usage)
+    }
+
+    fn domain_admits(&self, host: &str, usage: &DomainUsage) -> bool {
+        let pages_ok = self
+            .domain_limit(host, |budget| budget.max_pages)
+            .is_none_or(|limit| usage.pages < limit);
+        let bytes_ok = self
+            .domain_limit(host, |budget| budget.max_bytes)
+            .is_none_or(|limit| usage.bytes < limit);
+        pages_ok && bytes_ok
+    }
+
+    /// Charges a page from `host` against the global and domain page counts
+    /// before it is fetched, so concurrent fetches cannot overshoot
+    /// `max_pages`. Fails once a global budget runs out; `false` means
+    /// `host` has no quota left.
+    pub fn reserve(&self, host: &str) -> Result<bool, BudgetExhausted> {
+        self.check()?;
+        let limit = self.config.max_pages.unwrap_or(u64::MAX);
+        self.pages
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |pages| {
+                (pages < limit).then_some(pages + 1)
+            })
+            .map_err(|_| BudgetExhausted::Pages(limit))?;
+        let mut usage = self.domains.entry(host.to_string()).or_default();
+        if !self.domain_admits(host, &usage) {
+            self.pages.fetch_sub(1, Ordering::SeqCst);
+            return Ok(false);
+        }
+        usage.pages += 1;
+        Ok(true)
+    }
+
+    /// Gives back a page reserved for a fetch that did not happen.
+    pub fn release(&self, host: &str) {
+        self.pages.fetch_sub(1, Ordering::SeqCst);
+        if let Some(mut usage) = self.domains.get_mut(host) {
+            usage.pages = usage.pages.saturating_sub(1);
+        }
+    }
+
+    /// Adds the `bytes` of a page from `host` that was reserved and fetched.
+    pub fn record_fetch(&self, host: &str, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.domains.entry(host.to_string()).or_default().bytes += bytes;
+    }
+}
+
+impl Default for CrawlBudget {
+    fn default() -> Self {
+        Self::new(BudgetConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fetch(budget: &CrawlBudget, host: &str, bytes: u64) {
+        assert_eq!(budget.reserve(host), Ok(true));
+        budget.record_fetch(host, bytes);
+    }
+
+    #[test]
+    fn test_global_budgets() {
+        let budget = CrawlBudget::new(BudgetConfig {
+            max_pages: Some(2),
+            max_bytes: Some(1_000),
+            ..Default::default()
+        });
+        assert!(budget.check().is_ok());
+        fetch(&budget, "example.com", 100);
+        assert!(budget.check().is_ok());
+        fetch(&budget, "example.com", 100);
+        assert_eq!(budget.check(), Err(BudgetExhausted::Pages(2)));
+
+        let budget = CrawlBudget::new(BudgetConfig {
+            max_bytes: Some(1_000),
+            ..Default::default()
+        });
+        fetch(&budget, "example.com", 1_500);
+        assert_eq!(budget.check(), Err(BudgetExhausted::Bytes(1_000)));
+
+        let budget = CrawlBudget::new(BudgetConfig {
+            max_duration_secs: Some(0),
+            ..Default::default()
+        });
+        assert_eq!(
+            budget.check(),
+            Err(BudgetExhausted::Duration(Duration::from_secs(0)))
+        );
+    }
+
+    #[test]
+    fn test_domain_budgets() {
+        let mut domains = HashMap::new();
+        domains.insert(
+            "big.example.com".to_string(),
+            DomainBudget {
+                max_pages: Some(3),
+                ..Default::default()
+            },
+        );
+        let budget = CrawlBudget::new(BudgetConfig {
+            per_domain: DomainBudget {
+                max_pages: Some(1),
+                max_depth: Some(2),
+                max_bytes: None,
+            },
+            domains,
+            ..Default::default()
+        });
+
+        assert!(budget.admits_depth("example.com", 2));
+        assert!(!budget.admits_depth("example.com", 3));
+        assert!(!budget.admits_depth("big.example.com", 3));
+
+        fetch(&budget, "example.com", 10);
+        assert!(!budget.admits_fetch("example.com"));
+        assert_eq!(budget.reserve("example.com"), Ok(false));
+        assert_eq!(budget.pages(), 1);
+
+        fetch(&budget, "big.example.com", 10);
+        assert!(budget.admits_fetch("big.example.com"));
+        assert!(budget.admits_fetch("other.org"));
+        assert!(budget.check().is_ok());
+    }
+
+    #[test]
+    fn test_concurrent_reservations() {
+        let budget = CrawlBudget::new(BudgetConfig {
+            max_pages: Some(10),
+            per_domain: DomainBudget {
+                max_pages: Some(4),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let hosts = ["a.com", "b.com", "c.com"];
+        let reserved: Vec<&str> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..8)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut reserved = Vec::new();
+                        for host in hosts.iter().cycle().take(30) {
+                            if let Ok(true) = budget.reserve(host) {
+                                reserved.push(*host);
+                            }
+                        }
+                        reserved
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+        assert_eq!(reserved.len(), 10);
+        assert_eq!(budget.pages(), 10);
+        for host in hosts {
+            assert!(budget.usage().domains[host].pages <= 4);
+        }
+
+        budget.release("a.com");
+        assert_eq!(budget.pages(), 9);
+        assert_eq!(budget.reserve("a.com"), Ok(true));
+    }
+
+    #[test]
+    fn test_resume_keeps_usage() {
+        let config = BudgetConfig {
+            max_pages: Some(3),
+            per_domain: DomainBudget {
+                max_pages: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let budget = CrawlBudget::new(config.clone());
+        fetch(&budget, "example.com", 10);
+        fetch(&budget, "example.com", 5);
+        fetch(&budget, "other.org", 1);
+
+        let resumed = CrawlBudget::resume(config, &budget.usage());
+        assert_eq!(resumed.usage(), budget.usage());
+        assert!(!resumed.admits_fetch("example.com"));
+        assert_eq!(resumed.check(), Err(BudgetExhausted::Pages(3)));
+    }
+}
@@ -0,0 +1,329 @@
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::error::CrawlerError;
+use crate::tracking::{TrackingConfig, TrackingRules};
+
+static CANONICALIZER: OnceLock<UrlCanonicalizer> = OnceLock::new();
+
+/// Returns the process-wide canonicalizer, falling back to the default
+/// options if `init_canonicalizer` was never called.
+pub fn get_canonicalizer() -> &'static UrlCanonicalizer {
+    CANONICALIZER.get_or_init(UrlCanonicalizer::default)
+}
+
+/// Installs the process-wide canonicalizer. Fails if one is already in use.
+pub fn init_canonicalizer(config: CanonicalizerConfig) -> Result<(), CrawlerError> {
+    CANONICALIZER
+        .set(UrlCanonicalizer::new(config)?)
+        .map_err(|_| CrawlerError::Other("URL canonicalizer already initialized".to_string()))
+}
+
+/// Canonicalizes `url` with the process-wide canonicalizer.
+pub fn canonicalize(url: &str) -> Result<String, CrawlerError> {
+    Ok(get_canonicalizer().canonicalize(url)?.to_string())
+}
+
+/// Like `canonicalize`, but also reports whether tracking rules removed any
+/// parameters from the URL.
+pub fn canonicalize_tracked(url: &str) -> Result<(String, bool), CrawlerError> {
+    let (url, stripped) = get_canonicalizer().canonicalize_tracked(url)?;
+    Ok((url.to_string(), stripped))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrailingSlash {
+    /// Leave the path as it was found.
+    #[default]
+    Keep,
+    /// Append `/` to paths whose last segment has no file extension.
+    Add,
+    /// Drop a trailing `/` from every path except the root.
+    Remove,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CanonicalizerConfig {
+    pub sort_query: bool,
+    pub trailing_slash: TrailingSlash,
+    pub tracking: TrackingConfig,
+}
+
+/// Rewrites URLs into an RFC 3986 normal form so equivalent spellings of the
+/// same page compare equal in the frontier and in storage.
+///
+/// Parsing through `url::Url` already lowercases the scheme and host, drops
+/// default ports and resolves dot segments; on top of that we normalize
+/// percent-encoding, strip fragments and empty queries and apply the
+/// optional query-sorting and trailing-slash rules. Tracking and session
+/// parameters matched by the configured `TrackingRules` are removed from both
+/// the query and path parameters.
+#[derive(Debug, Clone)]
+pub struct UrlCanonicalizer {
+    config: CanonicalizerConfig,
+    tracking: TrackingRules,
+}
+
+impl Default for UrlCanonicalizer {
+    fn default() -> Self {
+        Self::new(CanonicalizerConfig::default()).expect("default tracking rules are valid")
+    }
+}
+
+impl UrlCanonicalizer {
+    pub fn new(config: CanonicalizerConfig) -> Result<Self, CrawlerError> {
+        let tracking = TrackingRules::new(&config.tracking)?;
+        Ok(Self { config, tracking })
+    }
+
+    pub fn config(&self) -> &CanonicalizerConfig {
+        &self.config
+    }
+
+    pub fn canonicalize(&self, url: &str) -> Result<Url, CrawlerError> {
+        Ok(self.canonicalize_tracked(url)?.0)
+    }
+
+    pub fn canonicalize_tracked(&self, url: &str) -> Result<(Url, bool), CrawlerError> {
+        let url = Url::parse(url.trim())?;
+        Ok(self.canonicalize_url(&url))
+    }
+
+    /// Returns the canonical form of `url` and whether any tracking
+    /// parameters were stripped from it.
+    pub fn canonicalize_url(&self, url: &Url) -> (Url, bool) {
+        let mut url = url.clone();
+        url.set_fragment(None);
+
+        if url.cannot_be_a_base() {
+            return (url, false);
+        }
+
+        if let Some(host) = url.host_str() {
+            if host.len() > 1 && host.ends_with('.') {
+                let host = host.trim_end_matches('.').to_string();
+                let _ = url.set_host(Some(&host));
+            }
+        }
+
+        let host = url.host_str().unwrap_or("").to_string();
+        let original_path = normalize_percent_encoding(url.path());
+        let mut path = self.tracking.strip_path(&host, &original_path);
+        let mut stripped = path != original_path;
+        match self.config.trailing_slash {
+            TrailingSlash::Keep => {}
+            TrailingSlash::Add => {
+                let last = path.rsplit('/').next().unwrap_or("");
+                if !path.ends_with('/') && !last.contains('.') {
+                    path.push('/');
+                }
+            }
+            TrailingSlash::Remove => {
+                while path.len() > 1 && path.ends_with('/') {
+                    path.pop();
+                }
+            }
+        }
+        url.set_path(&path);
+
+        let query = url.query().map(|query| {
+            let kept = self.tracking.strip_query(&host, query);
+            stripped |= kept != query;
+            self.normalize_query(&kept)
+        });
+        match query {
+            Some(query) if !query.is_empty() => url.set_query(Some(&query)),
+            _ => url.set_query(None),
+        }
+
+        (url, stripped)
+    }
+
+    fn normalize_query(&self, query: &str) -> String {
+        let mut params: Vec<String> = query
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .map(normalize_percent_encoding)
+            .collect();
+        if self.config.sort_query {
+            params.sort_by(|a, b| {
+                let (a_key, a_value) = a.split_once('=').unwrap_or((a, ""));
+                let (b_key, b_value) = b.split_once('=').unwrap_or((b, ""));
+                a_key.cmp(b_key).then(a_value.cmp(b_value))
+            });
+        }
+        params.join("&")
+    }
+}
+
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+/// Decodes percent-escaped unreserved characters and upper-cases the hex
+/// digits of every escape that has to stay encoded (RFC 3986 §6.2.2.2).
+fn normalize_percent_encoding(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = String::with_capacity(input.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            let hex = &input[i + 1..i + 3];
+            let byte = u8::from_str_radix(hex, 16).unwrap_or_default();
+            if is_unreserved(byte) {
+                output.push(byte as char);
+            } else {
+                output.push('%');
+                output.push_str(&hex.to_ascii_uppercase());
+            }
+            i += 3;
+            continue;
+        }
+        let ch = input[i..].chars().next().unwrap_or_default();
+        output.push(ch);
+        i += ch.len_utf8();
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(url: &str) -> String {
+        UrlCanonicalizer::default()
+            .canonicalize(url)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_case_port_and_dot_segments() {
+        assert_eq!(
+            canonical("HTTP://Example.COM:80/a/../b?y=2&x=1#f"),
+            "http://example.com/b?y=2&x=1"
+        );
+        assert_eq!(
+            canonical("https://example.com:443/./c/./d/"),
+            "https://example.com/c/d/"
+        );
+        assert_eq!(
+            canonical("https://example.com:8443/"),
+            "https://example.com:8443/"
+        );
+        assert_eq!(canonical("http://example.com."), "http://example.com/");
+    }
+
+    #[test]
+    fn test_percent_encoding() {
+        assert_eq!(
+            canonical("http://example.com/%7Euser/%2fdocs?q=%41%3d"),
+            "http://example.com/~user/%2Fdocs?q=A%3D"
+        );
+    }
+
+    #[test]
+    fn test_empty_query_and_fragment() {
+        assert_eq!(
+            canonical("http://example.com/page?#top"),
+            "http://example.com/page"
+        );
+        assert_eq!(
+            canonical("http://example.com/page?&&a=1&"),
+            "http://example.com/page?a=1"
+        );
+    }
+
+    #[test]
+    fn test_sorted_query() {
+        let canonicalizer = UrlCanonicalizer::new(CanonicalizerConfig {
+            sort_query: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let a = canonicalizer
+            .canonicalize("HTTP://Example.com:80/a/../b?y=2&x=1#f")
+            .unwrap();
+        let b = canonicalizer
+            .canonicalize("http://example.com/b?x=1&y=2")
+            .unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.as_str(), "http://example.com/b?x=1&y=2");
+    }
+
+    #[test]
+    fn test_trailing_slash() {
+        let add = UrlCanonicalizer::new(CanonicalizerConfig {
+            trailing_slash: TrailingSlash::Add,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            add.canonicalize("http://example.com/docs")
+                .unwrap()
+                .as_str(),
+            "http://example.com/docs/"
+        );
+        assert_eq!(
+            add.canonicalize("http://example.com/index.html")
+                .unwrap()
+                .as_str(),
+            "http://example.com/index.html"
+        );
+
+        let remove = UrlCanonicalizer::new(CanonicalizerConfig {
+            trailing_slash: TrailingSlash::Remove,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            remove
+                .canonicalize("http://example.com/docs/")
+                .unwrap()
+                .as_str(),
+            "http://example.com/docs"
+        );
+        assert_eq!(
+            remove.canonicalize("http://example.com/").unwrap().as_str(),
+            "http://example.com/"
+        );
+    }
+
+    #[test]
+    fn test_tracking_params_stripped() {
+        let canonicalizer = UrlCanonicalizer::default();
+        let (url, stripped) = canonicalizer
+            .canonicalize_tracked(
+                "https://example.com/shop;jsessionid=0A1B?id=7&utm_source=news&fbclid=xyz#top",
+            )
+            .unwrap();
+        assert!(stripped);
+        assert_eq!(url.as_str(), "https://example.com/shop?id=7");
+
+        let (url, stripped) = canonicalizer
+            .canonicalize_tracked("https://example.com/shop?PHPSESSID=42")
+            .unwrap();
+        assert!(stripped);
+        assert_eq!(url.as_str(), "https://example.com/shop");
+
+        let (_, stripped) = canonicalizer
+            .canonicalize_tracked("https://example.com/shop?id=7")
+            .unwrap();
+        assert!(!stripped);
+    }
+
+    #[test]
+    fn test_invalid_url() {
+        assert!(UrlCanonicalizer::default()
+            .canonicalize("not a url")
+            .is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::budget::{BudgetConfig, BudgetUsage, CrawlBudget};
+use crate::error::CrawlerError;
+use crate::limiter::get_rate_limiter;
+use crate::modules::storage::state::SharedState;
+use crate::priority::ScorerState;
+use crate::seen::{fingerprint, SeenConfig};
+use crate::task::Task;
+use crate::tracking::get_tracking_stats;
+use crate::trap::TrapSnapshot;
+
+const VERSION: u32 = 1;
+const CHECKPOINT_FILE: &str = "checkpoint.json";
+const VISITED_FILE: &str = "visited.bin";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheckpointConfig {
+    /// Crawl directory the checkpoint is written to. `None` disables periodic
+    /// checkpoints; an interrupted crawl is then saved to `DEFAULT_DIR`.
+    pub dir: Option<String>,
+    pub interval_secs: u64,
+}
+
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            interval_secs: 60,
+        }
+    }
+}
+
+/// Everything needed to continue an interrupted crawl, apart from the visited
+/// set, which is written next to it in its own binary format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub seed: String,
+    /// Fingerprint of the configuration the crawl was started with.
+    pub config_hash: String,
+    pub saved_at: DateTime<Utc>,
+    pub budget: BudgetUsage,
+    pub urls_rewritten: usize,
+    pub duplicates_avoided: usize,
+    pub rate_limiter_tokens: usize,
+    pub host_delays_ms: HashMap<String, u64>,
+    /// Queued tasks, or `None` when the frontier is disk-backed and resumes
+    /// from its own store.
+    pub queued: Option<Vec<Task>>,
+    /// Tasks being fetched when the checkpoint was taken.
+    pub in_flight: Vec<Task>,
+    /// What a stateful scorer such as OPIC has learned.
+    #[serde(default)]
+    pub scorer: Option<ScorerState>,
+    /// Counts behind the trap heuristics.
+    #[serde(default)]
+    pub traps: TrapSnapshot,
+}
+
+/// Hash identifying a configuration file's contents.
+pub fn config_hash(contents: &str) -> String {
+    format!("{:032x}", fingerprint(contents))
+}
+
+/// Reads the checkpoint in `dir`.
+pub fn load(dir: &Path) -> Result<Checkpoint, CrawlerError> {
+    let data = fs::read(dir.join(CHECKPOINT_FILE)).map_err(CrawlerError::IoError)?;
+    let checkpoint: Checkpoint = serde_json::from_slice(&data)
+        .map_err(|e| CrawlerError::Other(format!("Invalid checkpoint: {}", e)))?;
+    if checkpoint.version != VERSION {
+        return Err(CrawlerError::Other(format!(
+            "Unsupported checkpoint version {}",
+            checkpoint.version
+        )));
+    }
+    Ok(checkpoint)
+}
+
+/// Visited set settings that resume from the set saved in `dir`.
+pub fn visited_config(dir: &Path, config: &SeenConfig) -> SeenConfig {
+    SeenConfig {
+        path: Some(dir.join(VISITED_FILE).to_string_lossy().into_owned()),
+        ..config.clone()
+    }
+}
+
+/// Loads `checkpoint` into `state`. The visited set must already have been
+/// loaded through `visited_config`, and the frontier, scorer and trap
+/// detector set up from the crawl's configuration. Returns the number of
+/// tasks queued again.
+pub fn restore(
+    checkpoint: &Checkpoint,
+    state: &SharedState,
+    budget: BudgetConfig,
+) -> Result<usize, CrawlerError> {
+    state.set_budget(CrawlBudget::resume(budget, &checkpoint.budget).into())?;
+    get_tracking_stats().restore(checkpoint.urls_rewritten, checkpoint.duplicates_avoided);
+    get_rate_limiter().restore_tokens(checkpoint.rate_limiter_tokens);
+    if let Some(scorer) = &checkpoint.scorer {
+        state.scorer()?.restore(scorer);
+    }
+    state.trap_detector()?.restore(checkpoint.traps.clone());
+    for (host, delay) in &checkpoint.host_delays_ms {
+        state.set_host_delay(host, Duration::from_millis(*delay))?;
+    }
+    state.restore_tasks(
+        checkpoint.queued.clone().unwrap_or_default(),
+        checkpoint.in_flight.clone(),
+    )
+}
+
+/// Directory an interrupted crawl is saved to when none is configured.
+pub const DEFAULT_DIR: &str = "Freyr/checkpoint";
+
+/// Writes the crawl's state to a directory, periodically if given an
+/// interval.
+#[derive(Debug)]
+pub struct Checkpointer {
+    dir: PathBuf,
+    seed: String,
+    config_hash: String,
+    interval: Option<Duration>,
+    last: Instant,
+}
+
+impl Checkpointer {
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        seed: &str,
+        config_hash: &str,
+        interval: Option<Duration>,
+    ) -> Self {
+        Self {
+            dir: dir.into(),
+            seed: seed.to_string(),
+            config_hash: config_hash.to_string(),
+            interval,
+            last: Instant::now(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn is_periodic(&self) -> bool {
+        self.interval.is_some()
+    }
+
+    /// Saves a checkpoint if `interval` has passed since the last one.
+    pub fn maybe_save(&mut self, state: &SharedState) -> Result<bool, CrawlerError> {
+        match self.interval {
+            Some(interval) if self.last.elapsed() >= interval => {}
+            _ => return Ok(false),
+        }
+        self.save(state)?;
+        Ok(true)
+    }
+
+    /// Saves a checkpoint now. The visited set is written before the
+    /// checkpoint file, so a crash in between leaves at worst a set that is
+    /// ahead of the queue, never URLs that would be fetched twice.
+    pub fn save(&mut self, state: &SharedState) -> Result<(), CrawlerError> {
+        fs::create_dir_all(&self.dir).map_err(CrawlerError::IoError)?;
+        // A disk-backed frontier is made durable in place instead of copied.
+        state.flush()?;
+        state.save_visited(&self.dir.join(VISITED_FILE))?;
+
+        let checkpoint = Checkpoint {
+            version: VERSION,
+            seed: self.seed.clone(),
+            config_hash: self.config_hash.clone(),
+            saved_at: Utc::now(),
+            budget: state.budget()?.usage(),
+            urls_rewritten: get_tracking_stats().urls_rewritten(),
+            duplicates_avoided: get_tracking_stats().duplicates_avoided(),
+            rate_limiter_tokens: get_rate_limiter().tokens(),
+            host_delays_ms: state.frontier_config()?.host_delays_ms,
+            queued: state.queued_snapshot()?,
+            in_flight: state.in_flight_tasks(),
+            scorer: state.scorer()?.state(),
+            traps: state.trap_detector()?.snapshot(),
+        };
+        let data = serde_json::to_vec_pretty(&checkpoint)
+            .map_err(|e| CrawlerError::Other(e.to_string()))?;
+        let tmp = self.dir.join(format!("{CHECKPOINT_FILE}.tmp"));
+        fs::write(&tmp, data).map_err(CrawlerError::IoError)?;
+        fs::rename(&tmp, self.dir.join(CHECKPOINT_FILE)).map_err(CrawlerError::IoError)?;
+        self.last = Instant::now();
+        tracing::info!("Saved checkpoint to {}", self.dir.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::priority::OpicScorer;
+    use crate::seen::SeenKind;
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let dir = std::env::temp_dir().join(format!("thamur-checkpoint-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let state = SharedState::new();
+        state.set_scorer(Arc::new(OpicScorer::default())).unwrap();
+        state.add_url("https://example.com/".to_string()).unwrap();
+        state.add_url("https://example.com/a".to_string()).unwrap();
+        let first = match state.poll_task().unwrap() {
+            crate::modules::storage::frontier::FrontierPoll::Ready(task) => task,
+            other => panic!("unexpected poll result: {:?}", other),
+        };
+        assert!(state.claim_task(&first).unwrap());
+        state.record_fetch(first.url(), 100).unwrap();
+
+        let mut checkpointer = Checkpointer::new(&dir, first.url(), "hash", Some(Duration::ZERO));
+        checkpointer.save(&state).unwrap();
+
+        let checkpoint = load(&dir).unwrap();
+        assert_eq!(checkpoint.seed, first.url());
+        assert_eq!(checkpoint.budget.pages, 1);
+        assert_eq!(checkpoint.in_flight, vec![first.clone()]);
+        assert_eq!(checkpoint.queued.as_ref().map(Vec::len), Some(1));
+        assert_eq!(checkpoint.scorer, state.scorer().unwrap().state());
+        assert_eq!(checkpoint.traps.pattern_counts.len(), 2);
+
+        let resumed = SharedState::new();
+        resumed.set_scorer(Arc::new(OpicScorer::default())).unwrap();
+        // `SharedState::new` starts with an exact set.
+        let seen = SeenConfig {
+            kind: SeenKind::Exact,
+            ..Default::default()
+        };
+        resumed
+            .set_visited_config(&visited_config(&dir, &seen))
+            .unwrap();
+        assert_eq!(
+            restore(&checkpoint, &resumed, BudgetConfig::default()).unwrap(),
+            2
+        );
+        assert_eq!(resumed.budget().unwrap().pages(), 1);
+        assert_eq!(resumed.scorer().unwrap().state(), checkpoint.scorer);
+        assert_eq!(
+            resumed.trap_detector().unwrap().snapshot(),
+            checkpoint.traps
+        );
+        // The interrupted fetch is retried once, but not claimed twice.
+        assert!(resumed.claim_task(&first).unwrap());
+        assert!(!resumed.claim_task(&first).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
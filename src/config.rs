@@ -3,12 +3,53 @@ use serde_json;
 use std::fs::File;
 use std::path::Path;
 
+use crate::budget::BudgetConfig;
+use crate::canonical::CanonicalizerConfig;
+use crate::checkpoint::CheckpointConfig;
+use crate::modules::parser::readability::ReadabilityConfig;
+use crate::modules::parser::rules::ExtractionConfig;
+use crate::modules::storage::frontier::FrontierConfig;
+use crate::pipeline::PipelineConfig;
+use crate::priority::ScorerKind;
+use crate::scope::ScopeConfig;
+use crate::seen::SeenConfig;
+use crate::shutdown::ShutdownConfig;
+use crate::trap::TrapConfig;
+
 #[derive(Debug, Deserialize)]
 pub struct CrawlerConfig {
     pub user_agent: String,
     pub max_depth: u32,
     pub max_threads: u32,
+    /// Jobs the thread pool queues before `execute` waits; twice
+    /// `max_threads` when unset.
+    #[serde(default)]
+    pub queue_size: Option<usize>,
     pub timeout: u64,
+    #[serde(default)]
+    pub canonicalization: CanonicalizerConfig,
+    #[serde(default)]
+    pub scope: ScopeConfig,
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    #[serde(default)]
+    pub traps: TrapConfig,
+    #[serde(default)]
+    pub politeness: FrontierConfig,
+    #[serde(default)]
+    pub priority: ScorerKind,
+    #[serde(default)]
+    pub visited: SeenConfig,
+    #[serde(default)]
+    pub checkpoint: CheckpointConfig,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    #[serde(default)]
+    pub pipeline: PipelineConfig,
+    #[serde(default)]
+    pub extraction: ExtractionConfig,
+    #[serde(default)]
+    pub readability: ReadabilityConfig,
 }
 
 pub fn load_config(path: &str) -> Result<CrawlerConfig, Box<dyn std::error::Error>> {
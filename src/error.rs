@@ -1,6 +1,7 @@
-use std::fmt;
 use std::sync::{MutexGuard, PoisonError, RwLockReadGuard, RwLockWriteGuard};
 use thiserror::Error;
+
+use crate::budget::BudgetExhausted;
 use tracing::error;
 
 // Custom error enum for URL validation failures
@@ -36,6 +37,20 @@ pub enum CrawlerError {
     RateLimitError(u64),
     #[error("No token available")]
     NoTokenAvailable,
+    #[error("Crawl stopped: {0} exhausted")]
+    BudgetExhausted(BudgetExhausted),
+    #[error("Frontier store error: {0}")]
+    StoreError(String),
+    #[error("Thread pool is shut down")]
+    PoolClosed,
+    #[error("Task panicked: {0}")]
+    TaskPanicked(String),
+    #[error("Task was cancelled")]
+    TaskCancelled,
+    #[error("Task ran past its deadline")]
+    TaskTimedOut,
+    #[error("Crawl stopped after {0} worker panics")]
+    TooManyPanics(usize),
 }
 
 impl From<url::ParseError> for CrawlerError {
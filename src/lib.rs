@@ -4,11 +4,23 @@ pub mod modules {
     pub mod storage;
     pub mod utils;
 }
+pub mod budget;
+pub mod canonical;
+pub mod checkpoint;
 pub mod config;
 pub mod error;
 pub mod limiter;
+pub mod pipeline;
+pub mod priority;
+// robots.txt support is not wired into the crawl yet.
+#[allow(dead_code)]
 pub mod robot;
+pub mod scope;
+pub mod seen;
+pub mod shutdown;
 pub mod storage;
 pub mod task;
 pub mod thread;
+pub mod tracking;
+pub mod trap;
 pub mod validator;
@@ -1,8 +1,6 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::OnceLock;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-
-use tokio::time::error::Elapsed;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::error::CrawlerError;
 
@@ -53,6 +51,14 @@ impl RateLimiter {
     pub fn consume_token(&self) {
         self.tokens.fetch_sub(1, Ordering::Relaxed);
     }
+    pub fn tokens(&self) -> usize {
+        self.tokens.load(Ordering::Relaxed)
+    }
+    /// Restores the token count saved in a checkpoint.
+    pub fn restore_tokens(&self, tokens: usize) {
+        self.tokens
+            .store(tokens.min(self.capacity), Ordering::Relaxed);
+    }
 }
 
 #[cfg(test)]
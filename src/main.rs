@@ -1,19 +1,31 @@
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command};
 use std::io::Write;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
-use chrono::{DateTime, Utc};
+use crawler::budget::CrawlBudget;
+use crawler::canonical::init_canonicalizer;
+use crawler::checkpoint::{self, Checkpointer};
+use crawler::config::load_config;
 use crawler::modules::fetcher::client::Fetcher;
-use crawler::modules::parser::parser::parse_html_links;
+use crawler::modules::parser::pool::{ParseOptions, ParsePool};
+use crawler::modules::parser::readability::Readability;
+use crawler::modules::parser::rules::ExtractionRules;
 use crawler::modules::storage;
-use crawler::modules::storage::state::{mark_url_processed, SharedState};
-use crawler::storage::{get_storage_config_path, DataEntry, Storage, StorageConfig};
-use crawler::{config::load_config, thread::ThreadPool};
-use reqwest::{self, Client};
+use crawler::modules::storage::state::get_global_instance;
+use crawler::pipeline::{Pipeline, PipelineConfig, PipelineStop};
+use crawler::scope::{CrawlScope, ScopeConfig};
+use crawler::shutdown::{get_shutdown, install_signal_handlers};
+use crawler::storage::{get_storage_config_path, FileStore};
+use crawler::thread::{TaskOptions, ThreadPool};
+use crawler::tracking::get_tracking_stats;
+use crawler::trap::TrapDetector;
+use reqwest::Client;
 use select::document::Document;
-use select::node::Node;
 use select::predicate::Name;
 use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct CrawledData {
@@ -21,6 +33,7 @@ struct CrawledData {
     links: Vec<String>,
 }
 
+#[allow(dead_code)]
 async fn test_fetch(url: &str) -> Result<CrawledData, Box<dyn std::error::Error>> {
     let client = Client::new();
     let res = client.get(url).send().await?;
@@ -46,28 +59,132 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .help("The URL to crawl")
                 .required(false)
                 .index(1),
-        );
+        )
+        .subcommand(
+            Command::new("resume")
+                .about("Continue an interrupted crawl from its checkpoint")
+                .arg(
+                    Arg::new("dir")
+                        .help("The crawl directory holding the checkpoint")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .help("Resume even if config.json changed since the checkpoint")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .args_conflicts_with_subcommands(true)
+        .get_matches();
+
+    let config = load_config("config.json")?;
+    let config_hash = checkpoint::config_hash(&std::fs::read_to_string("config.json")?);
+    init_canonicalizer(config.canonicalization)?;
+    let scope = config.scope;
+    let pipeline = config.pipeline;
+    let rules = match &config.extraction.rules_file {
+        Some(path) => ExtractionRules::load(path)?,
+        None => ExtractionRules::default(),
+    };
+    let parse_options = ParseOptions::new(&config.user_agent, rules)
+        .with_readability(Readability::new(&config.readability)?);
+    let mut budget = config.budget;
+    budget.per_domain.max_depth.get_or_insert(config.max_depth);
+    let state = get_global_instance();
+    state.set_trap_detector(Arc::new(TrapDetector::new(config.traps)))?;
+    state.set_frontier_config(config.politeness)?;
+    state.set_scorer(Arc::from(config.priority.build()))?;
 
-    let url = match matches.get_matches().get_one::<String>("url") {
-        Some(url) => url.to_owned(),
-        None => {
-            print!("Enter URL to crawl: ");
-            std::io::stdout().flush().unwrap();
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input)?;
-            input.trim().to_string()
+    let interval = Duration::from_secs(config.checkpoint.interval_secs);
+    let drain_timeout = Duration::from_secs(config.shutdown.drain_timeout_secs);
+    let (url, checkpointer) = match matches.subcommand() {
+        Some(("resume", args)) => {
+            let dir = Path::new(args.get_one::<String>("dir").expect("dir is required"));
+            let saved = checkpoint::load(dir)?;
+            if saved.config_hash != config_hash {
+                if !args.get_flag("force") {
+                    return Err(format!(
+                        "config.json changed since the checkpoint in {} was taken; \
+                         pass --force to resume with the new configuration",
+                        dir.display()
+                    )
+                    .into());
+                }
+                eprintln!("Warning: config.json changed since the checkpoint was taken");
+            }
+            state.set_visited_config(&checkpoint::visited_config(dir, &config.visited))?;
+            let restored = checkpoint::restore(&saved, &state, budget)?;
+            println!(
+                "Resuming crawl of {} from {} with {} queued URLs",
+                saved.seed, saved.saved_at, restored
+            );
+            let checkpointer = Checkpointer::new(dir, &saved.seed, &config_hash, Some(interval));
+            (saved.seed, checkpointer)
+        }
+        _ => {
+            let url = match matches.get_one::<String>("url") {
+                Some(url) => url.to_owned(),
+                None => {
+                    print!("Enter URL to crawl: ");
+                    std::io::stdout().flush().unwrap();
+                    let mut input = String::new();
+                    std::io::stdin().read_line(&mut input)?;
+                    input.trim().to_string()
+                }
+            };
+            state.set_visited_config(&config.visited)?;
+            state.set_budget(Arc::new(CrawlBudget::new(budget)))?;
+            let checkpointer = match &config.checkpoint.dir {
+                Some(dir) => Checkpointer::new(dir, &url, &config_hash, Some(interval)),
+                None => Checkpointer::new(checkpoint::DEFAULT_DIR, &url, &config_hash, None),
+            };
+            (url, checkpointer)
         }
     };
 
-    // Create a thread pool
-    let mut pool = ThreadPool::new(1);
+    install_signal_handlers();
 
-    pool.execute(|| async move {
-        if let Err(e) = crawl_url(&url).await {
-            eprintln!("Error crawling {}: {}", url, e);
-        }
-    })
-    .await?;
+    // Create a thread pool. Each job is a whole crawl: the pipeline runs its
+    // own fetch, parse and store workers, so the pool only bounds how many
+    // crawls run at once and gives them cancellation and a final report.
+    let workers = config.max_threads as usize;
+    let mut pool = ThreadPool::with_queue_size(workers, config.queue_size.unwrap_or(workers * 2))
+        .with_cancel_grace(drain_timeout);
+
+    // HTML is parsed on its own threads so IO never waits behind it.
+    let parser = Arc::new(ParsePool::new(workers, workers * 2));
+    let fetcher = Arc::new(Fetcher::new(&config.user_agent).await?);
+
+    let options = TaskOptions {
+        name: Some(format!("crawl {}", url)),
+        deadline: None,
+    };
+    let crawl = pool
+        .execute_with(options, move |cancel| async move {
+            if let Err(e) = crawl_url(
+                &url,
+                &scope,
+                pipeline,
+                parse_options,
+                Some(checkpointer),
+                cancel,
+                fetcher,
+                parser,
+            )
+            .await
+            {
+                eprintln!("Error crawling {}: {}", url, e);
+            }
+        })
+        .await?;
+
+    // In-flight fetches get the drain timeout after a shutdown signal.
+    let cancel = crawl.token().clone();
+    tokio::spawn(async move {
+        get_shutdown().drain_expired(drain_timeout).await;
+        cancel.cancel();
+    });
 
     // pool.execute(|| async move {
     //     crawl_url("http://books.toscrape.com").await;
@@ -79,54 +196,125 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // .await?;
 
     pool.shutdown().await;
+    if let Err(e) = crawl.await {
+        eprintln!("Crawl task failed: {}", e);
+    }
+    println!("Tasks: {}", pool.report());
 
     Ok(())
 }
 
+#[allow(dead_code)]
 async fn add_url(url: String) -> Result<(), Box<dyn std::error::Error>> {
     let state = storage::state::get_global_instance();
     state.add_url(url)?;
     Ok(())
 }
 
-async fn crawl_url(url: &str) -> Result<CrawledData, Box<dyn std::error::Error>> {
-    let fetcher = Fetcher::new().await?;
-    let (html, status, _) = fetcher.fetch_page(url).await?;
-    mark_url_processed(url.to_string());
-
-    let urls = parse_html_links(&html, url)?;
-    for url in &urls {
-        let (data, status, content) = fetcher.fetch_page(&url).await?;
-        let storage = Storage::new(StorageConfig::from(
-            &get_storage_config_path(),
-            Some(url::Url::parse(&url)?.domain().unwrap()),
-        )?);
-        let data_entry = DataEntry {
-            url: url.to_string(),
-            status_code: status.as_u16() as i32,
-            content_type: content,
-            title: extract_title(&data),
-            crawled_at: Utc::now(),
-        };
-        storage.save_data(&data_entry)?;
-        mark_url_processed(url.to_owned());
+#[allow(clippy::too_many_arguments)]
+async fn crawl_url(
+    url: &str,
+    scope: &ScopeConfig,
+    pipeline: PipelineConfig,
+    parse_options: ParseOptions,
+    mut checkpointer: Option<Checkpointer>,
+    cancel: CancellationToken,
+    fetcher: Arc<Fetcher>,
+    parser: Arc<ParsePool>,
+) -> Result<CrawledData, Box<dyn std::error::Error>> {
+    let scope = Arc::new(CrawlScope::new(scope, &url::Url::parse(url)?)?);
+    let state = get_global_instance();
+    state.add_url(url.to_string())?;
+
+    let store = Arc::new(FileStore::new(&get_storage_config_path()));
+    let pipeline = Pipeline::new(
+        pipeline,
+        state.clone(),
+        fetcher,
+        parser.clone(),
+        scope,
+        store,
+    )
+    .with_parse_options(parse_options);
+    let outcome = pipeline.run(&cancel, checkpointer.as_mut()).await?;
+    let interrupted = outcome.stop == PipelineStop::Interrupted;
+    let stopped_by = match outcome.stop {
+        PipelineStop::Budget(reason) => Some(reason),
+        _ => None,
+    };
+    let seed_links = outcome.seed_links;
+    for panic in &outcome.panics {
+        println!(
+            "Worker panicked at the {:?} stage on {}: {}",
+            panic.stage, panic.url, panic.message
+        );
+    }
+
+    state.flush()?;
+    if let Some(checkpointer) = checkpointer.as_mut() {
+        if interrupted || checkpointer.is_periodic() {
+            checkpointer.save(&state)?;
+            println!("Saved checkpoint to {}", checkpointer.dir().display());
+        }
+    }
+    let budget = state.budget()?;
+    match stopped_by {
+        _ if interrupted => println!(
+            "Crawl interrupted after {} pages ({} bytes); continue it with `resume {}`",
+            budget.pages(),
+            budget.bytes(),
+            checkpointer
+                .as_ref()
+                .map_or(checkpoint::DEFAULT_DIR.into(), |c| c
+                    .dir()
+                    .display()
+                    .to_string())
+        ),
+        Some(reason) => println!(
+            "Crawl stopped after {} pages ({} bytes): {} exhausted",
+            budget.pages(),
+            budget.bytes(),
+            reason
+        ),
+        None => println!(
+            "Crawl finished after {} pages ({} bytes)",
+            budget.pages(),
+            budget.bytes()
+        ),
     }
 
+    for trap in state.trap_detector()?.report() {
+        println!(
+            "Suspected trap ({}) at {}: {} URLs rejected, e.g. {}",
+            trap.kind,
+            trap.pattern,
+            trap.rejected,
+            trap.samples.join(", ")
+        );
+    }
+
+    println!("Visited {}", state.visited_stats()?);
+    println!("Pipeline: {}", pipeline.metrics());
+    println!("Parsing: {}", parser.metrics());
+
+    let stats = get_tracking_stats();
+    println!(
+        "Tracking rules rewrote {} URLs and avoided {} duplicates",
+        stats.urls_rewritten(),
+        stats.duplicates_avoided()
+    );
+
     Ok(CrawledData {
         url: url.to_string(),
-        links: urls,
+        links: seed_links.unwrap_or_default(),
     })
 }
-fn extract_title(html: &str) -> Option<String> {
-    Document::from(html)
-        .find(Name("title"))
-        .next()
-        .map(|node: Node| node.text())
-}
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    use crawler::modules::fetcher::client::DEFAULT_USER_AGENT;
     use httpmock::prelude::*;
 
     #[tokio::test]
@@ -144,7 +332,17 @@ mod tests {
         });
 
         // Call your crawler function
-        let result = crawl_url(&server.url("/")).await;
+        let result = crawl_url(
+            &server.url("/"),
+            &ScopeConfig::default(),
+            PipelineConfig::default(),
+            ParseOptions::default(),
+            None,
+            CancellationToken::new(),
+            Arc::new(Fetcher::new(DEFAULT_USER_AGENT).await.unwrap()),
+            Arc::new(ParsePool::new(1, 1)),
+        )
+        .await;
 
         dbg!(&result);
 
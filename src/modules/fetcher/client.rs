@@ -1,29 +1,61 @@
-use reqwest::{Client, StatusCode};
-use std::{f32::consts::E, sync::OnceLock, time::Duration};
+use reqwest::{header::USER_AGENT, Client, StatusCode};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    error::CrawlerError,
-    limiter::{get_rate_limiter, RateLimiter},
-    modules::storage::state::{get_global_instance, is_url_processed, mark_url_processed},
+    error::CrawlerError, limiter::get_rate_limiter, modules::storage::state::mark_url_processed,
 };
 
+/// What the crawler calls itself unless configured otherwise.
+pub const DEFAULT_USER_AGENT: &str = "Thamur/1.0";
+
+/// A successfully fetched page.
+#[derive(Debug, Clone)]
+pub struct FetchedPage {
+    pub body: String,
+    pub status: StatusCode,
+    pub content_type: Option<String>,
+    /// Where the request ended up after following HTTP redirects.
+    pub url: String,
+    /// Values of the response's `X-Robots-Tag` headers.
+    pub robots_tags: Vec<String>,
+    /// Values of the response's `Link` headers.
+    pub link_headers: Vec<String>,
+}
+
 pub struct Fetcher {
     client: Client,
+    user_agent: String,
 }
 
 impl Fetcher {
-    pub async fn new() -> Result<Self, CrawlerError> {
+    /// Builds a fetcher that identifies itself as `user_agent`.
+    pub async fn new(user_agent: &str) -> Result<Self, CrawlerError> {
         let client = Client::builder()
             .timeout(Duration::new(10, 0))
             .build()
-            .map_err(|e| CrawlerError::HyperError(e))?;
-        Ok(Fetcher { client })
+            .map_err(CrawlerError::HyperError)?;
+        Ok(Fetcher {
+            client,
+            user_agent: user_agent.to_string(),
+        })
     }
 
+    /// Fetches `url`, giving up with `TaskCancelled` as soon as `cancel` is
+    /// cancelled, even in the middle of a download.
     pub async fn fetch_page(
         &self,
         url: &str,
-    ) -> Result<(String, StatusCode, Option<String>), CrawlerError> {
+        cancel: &CancellationToken,
+    ) -> Result<FetchedPage, CrawlerError> {
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(CrawlerError::TaskCancelled),
+            page = self.fetch(url) => page,
+        }
+    }
+
+    async fn fetch(&self, url: &str) -> Result<FetchedPage, CrawlerError> {
         // if is_url_processed(url) {
         //     todo!()
         // }
@@ -54,28 +86,41 @@ impl Fetcher {
             .client
             .get(url)
             .timeout(Duration::new(10, 0))
-            .header("UserAgent", "Thamur/1.0")
+            .header(USER_AGENT, &self.user_agent)
             .send()
             .await
-            .map_err(|e| CrawlerError::HyperError(e))?;
+            .map_err(CrawlerError::HyperError)?;
 
         let status = response.status();
+        let final_url = response.url().to_string();
         let headers = response.headers().clone();
         let content_type = headers.get("Content-Type");
+        let header_values = |name| {
+            headers
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .map(str::to_string)
+                .collect()
+        };
+        let robots_tags = header_values("X-Robots-Tag");
+        let link_headers = header_values("Link");
         println!("Going to fetch page: {}", url);
         if response.status().is_success() {
-            let text = response
-                .text()
-                .await
-                .map_err(|e| CrawlerError::HyperError(e))?;
+            let text = response.text().await.map_err(CrawlerError::HyperError)?;
             tracing::info!("Fetched page : {}", url);
             println!("Fetched page : {}", url);
             mark_url_processed(url.to_string());
-            Ok((
-                text,
+            Ok(FetchedPage {
+                body: text,
                 status,
-                content_type.map(|t| t.to_str().unwrap().to_string()),
-            ))
+                content_type: content_type
+                    .and_then(|t| t.to_str().ok())
+                    .map(str::to_string),
+                url: final_url,
+                robots_tags,
+                link_headers,
+            })
         } else {
             tracing::warn!(
                 "Failed to fetch page : {} with status {}",
@@ -91,3 +136,26 @@ impl Fetcher {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sends_configured_user_agent() {
+        let server = httpmock::MockServer::start();
+        let page = server.mock(|when, then| {
+            when.path("/").header("user-agent", "TestBot/2.0");
+            then.status(200).body("<html></html>");
+        });
+        let fetcher = Fetcher::new("TestBot/2.0").await.unwrap();
+
+        let fetched = fetcher
+            .fetch_page(&server.url("/"), &CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert_eq!(fetched.status, StatusCode::OK);
+        page.assert();
+    }
+}
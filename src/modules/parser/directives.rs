@@ -0,0 +1,143 @@
+use select::document::Document;
+use select::predicate::Name;
+use serde::{Deserialize, Serialize};
+
+/// Indexing directives a page gives crawlers through `<meta name="robots">`,
+/// a bot-specific meta tag or the `X-Robots-Tag` header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RobotsDirectives {
+    /// Do not index the page.
+    pub noindex: bool,
+    /// Do not follow the links on the page.
+    pub nofollow: bool,
+    /// Do not keep a cached copy of the page.
+    pub noarchive: bool,
+}
+
+impl RobotsDirectives {
+    /// Parses a comma separated directive list such as `noindex, nofollow`.
+    /// Unknown directives are ignored.
+    pub fn parse(value: &str) -> Self {
+        let mut directives = Self::default();
+        for directive in value.split(',') {
+            match directive.trim().to_ascii_lowercase().as_str() {
+                "noindex" => directives.noindex = true,
+                "nofollow" => directives.nofollow = true,
+                "noarchive" => directives.noarchive = true,
+                "none" => {
+                    directives.noindex = true;
+                    directives.nofollow = true;
+                }
+                _ => {}
+            }
+        }
+        directives
+    }
+
+    /// Combines directives from several sources; the most restrictive wins.
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            noindex: self.noindex || other.noindex,
+            nofollow: self.nofollow || other.nofollow,
+            noarchive: self.noarchive || other.noarchive,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The name a crawler answers to in robots directives: the product token of
+/// its user agent, e.g. `thamur` for `Thamur/1.0`.
+pub fn robot_name(user_agent: &str) -> String {
+    user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Directives of the page's `robots` meta tags and of those addressed to
+/// `robot` by name.
+pub fn meta_directives(document: &Document, robot: &str) -> RobotsDirectives {
+    document
+        .find(Name("meta"))
+        .filter(|meta| {
+            meta.attr("name").is_some_and(|name| {
+                name.eq_ignore_ascii_case("robots") || name.eq_ignore_ascii_case(robot)
+            })
+        })
+        .filter_map(|meta| meta.attr("content"))
+        .map(RobotsDirectives::parse)
+        .fold(RobotsDirectives::default(), RobotsDirectives::merge)
+}
+
+/// Directives with a value of their own, whose colon does not name a bot.
+const VALUED_DIRECTIVES: [&str; 4] = [
+    "unavailable_after",
+    "max-snippet",
+    "max-image-preview",
+    "max-video-preview",
+];
+
+/// Directives of `X-Robots-Tag` header values that apply to all robots or,
+/// through a `name:` prefix, to `robot`.
+pub fn header_directives(values: &[String], robot: &str) -> RobotsDirectives {
+    values
+        .iter()
+        .filter_map(|value| match value.split_once(':') {
+            Some((name, directives))
+                if !name.contains(',')
+                    && !VALUED_DIRECTIVES.contains(&name.trim().to_ascii_lowercase().as_str()) =>
+            {
+                name.trim()
+                    .eq_ignore_ascii_case(robot)
+                    .then_some(directives)
+            }
+            _ => Some(value.as_str()),
+        })
+        .map(RobotsDirectives::parse)
+        .fold(RobotsDirectives::default(), RobotsDirectives::merge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meta_and_header_directives() {
+        assert_eq!(robot_name("Thamur/1.0 (+https://example.com)"), "thamur");
+
+        let html = r#"
+            <meta name="robots" content="noarchive">
+            <meta name="Thamur" content="nofollow">
+            <meta name="googlebot" content="noindex">
+            <meta name="description" content="noindex">
+        "#;
+        assert_eq!(
+            meta_directives(&Document::from(html), "thamur"),
+            RobotsDirectives {
+                noindex: false,
+                nofollow: true,
+                noarchive: true,
+            }
+        );
+
+        let headers = vec![
+            "unavailable_after: 25 Jun 2030 15:00:00 PST".to_string(),
+            "googlebot: nofollow".to_string(),
+            "thamur: NONE".to_string(),
+        ];
+        assert_eq!(
+            header_directives(&headers, "thamur"),
+            RobotsDirectives {
+                noindex: true,
+                nofollow: true,
+                noarchive: false,
+            }
+        );
+        assert!(header_directives(&["index, follow".to_string()], "thamur").is_empty());
+    }
+}
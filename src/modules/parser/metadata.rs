@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+
+use select::document::Document;
+use select::node::Node;
+use select::predicate::{Attr, Name};
+use serde::{Deserialize, Serialize};
+
+/// Metadata a page publishes about itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PageMetadata {
+    pub description: Option<String>,
+    pub keywords: Vec<String>,
+    pub author: Option<String>,
+    /// OpenGraph properties by name, e.g. `og:image`; some repeat.
+    pub open_graph: BTreeMap<String, Vec<String>>,
+    /// Twitter card properties by name, e.g. `twitter:card`.
+    pub twitter: BTreeMap<String, String>,
+    /// Every `application/ld+json` block that parses as JSON.
+    pub json_ld: Vec<serde_json::Value>,
+    /// Top-level microdata and RDFa Lite items.
+    pub items: Vec<Item>,
+}
+
+/// A microdata (`itemscope`) or RDFa Lite (`typeof`) item.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Item {
+    /// `itemtype`, or `typeof` prefixed with the `vocab` in effect.
+    pub types: Vec<String>,
+    /// `itemid` or `resource`.
+    pub id: Option<String>,
+    pub properties: BTreeMap<String, Vec<PropertyValue>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PropertyValue {
+    Text(String),
+    Item(Item),
+}
+
+/// Collects the page's standard meta tags, OpenGraph and Twitter card
+/// properties, JSON-LD blocks and microdata/RDFa Lite items.
+pub fn extract_metadata(document: &Document) -> PageMetadata {
+    let mut metadata = PageMetadata::default();
+    for meta in document.find(Name("meta")) {
+        let Some(content) = meta.attr("content").map(str::trim) else {
+            continue;
+        };
+        let key = meta
+            .attr("property")
+            .or_else(|| meta.attr("name"))
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        match key.as_str() {
+            "description" => {
+                metadata
+                    .description
+                    .get_or_insert_with(|| content.to_string());
+            }
+            "author" => {
+                metadata.author.get_or_insert_with(|| content.to_string());
+            }
+            "keywords" => metadata.keywords.extend(
+                content
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|keyword| !keyword.is_empty())
+                    .map(str::to_string),
+            ),
+            key if key.starts_with("og:") => metadata
+                .open_graph
+                .entry(key.to_string())
+                .or_default()
+                .push(content.to_string()),
+            key if key.starts_with("twitter:") => {
+                metadata
+                    .twitter
+                    .entry(key.to_string())
+                    .or_insert_with(|| content.to_string());
+            }
+            _ => {}
+        }
+    }
+    metadata.json_ld = document
+        .find(Attr("type", "application/ld+json"))
+        .filter_map(|script| serde_json::from_str(&script.text()).ok())
+        .collect();
+    metadata.items = document
+        .find(|node: &Node| is_item(node) && property_names(node).is_empty())
+        .map(|node| item(&node))
+        .collect();
+    metadata
+}
+
+fn is_item(node: &Node) -> bool {
+    node.attr("itemscope").is_some() || node.attr("typeof").is_some()
+}
+
+fn property_names(node: &Node) -> Vec<String> {
+    node.attr("itemprop")
+        .or_else(|| node.attr("property"))
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+fn item(node: &Node) -> Item {
+    let types = match (node.attr("itemtype"), node.attr("typeof")) {
+        (Some(types), _) => types.split_whitespace().map(str::to_string).collect(),
+        (None, Some(types)) => {
+            let vocab = vocab(node);
+            types
+                .split_whitespace()
+                .map(|t| format!("{vocab}{t}"))
+                .collect()
+        }
+        (None, None) => Vec::new(),
+    };
+    let mut item = Item {
+        types,
+        id: node
+            .attr("itemid")
+            .or_else(|| node.attr("resource"))
+            .map(str::to_string),
+        properties: BTreeMap::new(),
+    };
+    add_properties(node, &mut item);
+    item
+}
+
+/// Adds the properties below `node` to `item`, stopping at nested items,
+/// which own the properties below them.
+fn add_properties(node: &Node, item: &mut Item) {
+    for child in node.children() {
+        let names = property_names(&child);
+        if !names.is_empty() {
+            let value = if is_item(&child) {
+                PropertyValue::Item(self::item(&child))
+            } else {
+                PropertyValue::Text(property_value(&child))
+            };
+            for name in names {
+                item.properties.entry(name).or_default().push(value.clone());
+            }
+        }
+        if !is_item(&child) {
+            add_properties(&child, item);
+        }
+    }
+}
+
+/// The `vocab` in effect at `node`, or nothing if none is declared.
+fn vocab(node: &Node) -> String {
+    std::iter::successors(Some(*node), |node| node.parent())
+        .find_map(|node| node.attr("vocab"))
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn property_value(node: &Node) -> String {
+    let attr = match node.name().unwrap_or_default() {
+        _ if node.attr("content").is_some() => "content",
+        "audio" | "embed" | "iframe" | "img" | "source" | "track" | "video" => "src",
+        "a" | "area" | "link" => "href",
+        "object" => "data",
+        "data" | "meter" => "value",
+        "time" if node.attr("datetime").is_some() => "datetime",
+        _ => {
+            let text = node.text();
+            return text.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+    };
+    node.attr(attr).unwrap_or_default().trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_metadata() {
+        let html = r#"
+            <html>
+                <head>
+                    <meta name="description" content="A good read">
+                    <meta name="keywords" content="rust, crawling, ">
+                    <meta name="author" content="Ada">
+                    <meta property="og:title" content="Read me">
+                    <meta property="og:image" content="https://example.com/1.png">
+                    <meta property="og:image" content="https://example.com/2.png">
+                    <meta name="twitter:card" content="summary">
+                    <script type="application/ld+json">{"@type": "Article", "headline": "Read me"}</script>
+                    <script type="application/ld+json">{not json</script>
+                </head>
+                <body>
+                    <div itemscope itemtype="https://schema.org/Product">
+                        <span itemprop="name">Kettle</span>
+                        <img itemprop="image" src="/kettle.png">
+                        <div itemprop="offers" itemscope itemtype="https://schema.org/Offer">
+                            <meta itemprop="price" content="20.00">
+                        </div>
+                    </div>
+                    <p vocab="https://schema.org/" typeof="Person">
+                        <span property="name">Grace   Hopper</span>
+                    </p>
+                </body>
+            </html>
+        "#;
+        let metadata = extract_metadata(&Document::from(html));
+        assert_eq!(metadata.description.as_deref(), Some("A good read"));
+        assert_eq!(metadata.keywords, vec!["rust", "crawling"]);
+        assert_eq!(metadata.author.as_deref(), Some("Ada"));
+        assert_eq!(metadata.open_graph["og:image"].len(), 2);
+        assert_eq!(metadata.twitter["twitter:card"], "summary");
+        assert_eq!(
+            metadata.json_ld,
+            vec![serde_json::json!({"@type": "Article", "headline": "Read me"})]
+        );
+
+        let text = |value: &str| PropertyValue::Text(value.to_string());
+        let offer = Item {
+            types: vec!["https://schema.org/Offer".to_string()],
+            id: None,
+            properties: BTreeMap::from([("price".to_string(), vec![text("20.00")])]),
+        };
+        assert_eq!(
+            metadata.items,
+            vec![
+                Item {
+                    types: vec!["https://schema.org/Product".to_string()],
+                    id: None,
+                    properties: BTreeMap::from([
+                        ("name".to_string(), vec![text("Kettle")]),
+                        ("image".to_string(), vec![text("/kettle.png")]),
+                        ("offers".to_string(), vec![PropertyValue::Item(offer)]),
+                    ]),
+                },
+                Item {
+                    types: vec!["https://schema.org/Person".to_string()],
+                    id: None,
+                    properties: BTreeMap::from([("name".to_string(), vec![text("Grace Hopper")])]),
+                },
+            ]
+        );
+    }
+}
@@ -1,2 +1,9 @@
 // Module for parsing HTML content and extracting links
+pub mod directives;
+pub mod metadata;
+#[allow(clippy::module_inception)]
 pub mod parser;
+pub mod pool;
+pub mod readability;
+pub mod relations;
+pub mod rules;
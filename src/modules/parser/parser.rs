@@ -1,46 +1,223 @@
 use select::document::Document;
 use select::node::Node;
-use select::predicate::Name;
+use select::predicate::{Element, Name};
+use serde::{Deserialize, Serialize};
 
 use crate::error::CrawlerError;
 use crate::validator::UrlValidator;
 
+/// What a link on a page points at, so the crawl policy can decide which
+/// kinds to follow and which only to record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+    /// Another page: `<a>`, `<area>` and page-level `<link>` relations.
+    Navigation,
+    /// Something the page loads: images, scripts, media, stylesheets.
+    Asset,
+    /// A document shown inside the page: frames and objects.
+    Embed,
+    /// A form's submission target.
+    Form,
+    /// A `<meta http-equiv="refresh">` target.
+    Redirect,
+}
+
+/// A link found on a page, resolved against the page URL, with what the
+/// page says about it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Link {
+    pub url: String,
+    pub kind: LinkKind,
+    /// Tag of the element the link came from, e.g. `a` or `img`.
+    pub element: String,
+    /// Anchor text, or `alt` text for images and areas, whitespace collapsed.
+    pub text: Option<String>,
+    /// Lowercased `rel` values, e.g. `nofollow`, `ugc` or `noopener`.
+    pub rel: Vec<String>,
+    pub title: Option<String>,
+    pub hreflang: Option<String>,
+    /// Index of the link among the page's links, in document order.
+    pub position: usize,
+}
+
+impl Link {
+    pub fn has_rel(&self, rel: &str) -> bool {
+        self.rel.iter().any(|value| value == rel)
+    }
+
+    /// Whether the page asks crawlers not to follow the link.
+    pub fn is_nofollow(&self) -> bool {
+        self.has_rel("nofollow")
+    }
+}
+
+/// `<link rel>` values that point at other pages rather than resources.
+const PAGE_RELATIONS: [&str; 4] = ["alternate", "canonical", "next", "prev"];
+
+/// URLs of the page's `<a href>` links. `document_links` has every kind of
+/// link with its details.
 pub fn parse_html_links(html: &str, base_url: &str) -> Result<Vec<String>, CrawlerError> {
-    let document = Document::from(html);
+    Ok(document_links(&Document::from(html), base_url)?
+        .into_iter()
+        .filter(|link| link.kind == LinkKind::Navigation && link.element == "a")
+        .map(|link| link.url)
+        .collect())
+}
+
+/// Text of the page's `<title>`.
+pub fn extract_title(document: &Document) -> Option<String> {
+    document
+        .find(Name("title"))
+        .next()
+        .map(|node: Node| node.text())
+}
+
+/// URL that relative links on the page resolve against: the first
+/// `<base href>`, itself resolved against the page URL, or else the page URL.
+pub fn document_base(document: &Document, page_url: &url::Url) -> url::Url {
+    document
+        .find(Name("base"))
+        .find_map(|base| base.attr("href"))
+        .and_then(|href| page_url.join(href.trim()).ok())
+        .filter(|base| base.scheme() == "http" || base.scheme() == "https")
+        .unwrap_or_else(|| page_url.clone())
+}
+
+/// Crawlable links of an already parsed page at `page_url`, in document
+/// order. Relative links resolve against the document's base URL.
+pub fn document_links(document: &Document, page_url: &str) -> Result<Vec<Link>, CrawlerError> {
     let validator = UrlValidator::new();
-    let base_url = url::Url::parse(base_url)?;
+    let base_url = document_base(document, &url::Url::parse(page_url)?);
     let links = document
-        .find(Name("a"))
-        .filter_map(|element: Node| element.attr("href"))
-        .filter_map(|href| match url::Url::parse(href) {
-            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {
-                if validator.is_valid(&url) {
-                    Some(url.to_string())
-                } else {
-                    None
-                }
-            }
-            Err(_) => {
-                if validator.is_valid_path(href) {
-                    if let Ok(url) = base_url.join(href) {
-                        if validator.is_valid(&url) {
-                            Some(url.to_string())
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            }
-            _ => None,
+        .find(Element)
+        .flat_map(|element| {
+            link_targets(&element)
+                .into_iter()
+                .map(move |(href, kind)| (element, href, kind))
+        })
+        .filter_map(|(element, href, kind)| {
+            let url = resolve(&validator, &base_url, href.trim())?;
+            Some((element, url, kind))
+        })
+        .enumerate()
+        .map(|(position, (element, url, kind))| Link {
+            url,
+            kind,
+            element: element.name().unwrap_or_default().to_string(),
+            text: link_text(&element),
+            rel: element
+                .attr("rel")
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(str::to_ascii_lowercase)
+                .collect(),
+            title: non_empty(element.attr("title")),
+            hreflang: non_empty(element.attr("hreflang")),
+            position,
         })
         .collect();
     Ok(links)
 }
 
+/// Visible text of a link: its content, or the `alt` of an image or area.
+fn link_text(element: &Node) -> Option<String> {
+    match element.name() {
+        Some("img" | "area") => non_empty(element.attr("alt")),
+        _ => {
+            let text = element.text();
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            non_empty(Some(&text))
+        }
+    }
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// The raw targets an element links to, with their kind.
+fn link_targets<'a>(element: &Node<'a>) -> Vec<(&'a str, LinkKind)> {
+    let attr = |name| element.attr(name).into_iter();
+    match element.name().unwrap_or_default() {
+        "a" | "area" => attr("href")
+            .map(|href| (href, LinkKind::Navigation))
+            .collect(),
+        "link" => {
+            let rel = element.attr("rel").unwrap_or_default().to_ascii_lowercase();
+            let kind = if rel
+                .split_whitespace()
+                .any(|rel| PAGE_RELATIONS.contains(&rel))
+            {
+                LinkKind::Navigation
+            } else {
+                LinkKind::Asset
+            };
+            attr("href").map(|href| (href, kind)).collect()
+        }
+        "iframe" | "frame" => attr("src").map(|src| (src, LinkKind::Embed)).collect(),
+        "object" => attr("data").map(|data| (data, LinkKind::Embed)).collect(),
+        "form" => attr("action")
+            .map(|action| (action, LinkKind::Form))
+            .collect(),
+        "img" | "source" => attr("src")
+            .chain(attr("srcset").flat_map(srcset_urls))
+            .map(|src| (src, LinkKind::Asset))
+            .collect(),
+        "script" | "video" | "audio" => attr("src").map(|src| (src, LinkKind::Asset)).collect(),
+        "meta" => {
+            let refresh = element
+                .attr("http-equiv")
+                .is_some_and(|equiv| equiv.eq_ignore_ascii_case("refresh"));
+            element
+                .attr("content")
+                .filter(|_| refresh)
+                .and_then(refresh_target)
+                .map(|target| (target, LinkKind::Redirect))
+                .into_iter()
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Candidate URLs of a `srcset`, e.g. `small.jpg 1x, large.jpg 2x`.
+fn srcset_urls(srcset: &str) -> impl Iterator<Item = &str> {
+    srcset
+        .split(',')
+        .filter_map(|candidate| candidate.split_whitespace().next())
+}
+
+/// Target of a meta refresh `content`, e.g. `0; url=/next`.
+pub fn refresh_target(content: &str) -> Option<&str> {
+    let (_, target) = content.split_once([';', ','])?;
+    let target = target.trim_start();
+    let target = match target.get(..4) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("url=") => &target[4..],
+        _ => target,
+    };
+    let target = target.trim().trim_matches(['\'', '"']);
+    (!target.is_empty()).then_some(target)
+}
+
+/// Resolves `href` against `base_url`, keeping only valid web URLs.
+fn resolve(validator: &UrlValidator, base_url: &url::Url, href: &str) -> Option<String> {
+    match url::Url::parse(href) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {
+            validator.is_valid(&url).then(|| url.to_string())
+        }
+        Err(_) if validator.is_valid_path(href) => base_url
+            .join(href)
+            .ok()
+            .filter(|url| validator.is_valid(url))
+            .map(|url| url.to_string()),
+        _ => None,
+    }
+}
+
 pub fn normalize_url(base_url: &str, relative_url: &str) -> Result<url::Url, url::ParseError> {
     let base = url::Url::parse(base_url)?;
     let normalized = match url::Url::parse(relative_url).map(|r| r.host().map(|h| h.to_string())) {
@@ -55,6 +232,7 @@ pub fn normalize_url(base_url: &str, relative_url: &str) -> Result<url::Url, url
     }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -71,6 +249,28 @@ mod tests {
         let base_url = "https://example.com";
         let expected = vec!["https://example.com/", "https://example.com/about"];
         assert_eq!(parse_html_links(html, base_url).unwrap(), expected);
+
+        // Assets, frames, forms, areas and <link> relations are left out.
+        let html = r#"
+            <html>
+                <head>
+                    <link rel="canonical" href="/canonical">
+                    <link rel="stylesheet" href="/style.css">
+                    <meta http-equiv="refresh" content="0; url=/refresh">
+                </head>
+                <body>
+                    <img src="/logo.png">
+                    <iframe src="/frame"></iframe>
+                    <form action="/search"></form>
+                    <map><area href="/area" alt="Area"></map>
+                    <a href="/page">Page</a>
+                </body>
+            </html>
+        "#;
+        assert_eq!(
+            parse_html_links(html, base_url).unwrap(),
+            vec!["https://example.com/page"]
+        );
     }
     #[test]
     fn test_extract_links() {
@@ -104,6 +304,131 @@ mod tests {
             .is_empty());
     }
     #[test]
+    fn test_link_kinds() {
+        let html = r#"
+            <html>
+                <head>
+                    <meta http-equiv="Refresh" content="5; URL='/moved'">
+                    <link rel="stylesheet" href="/style.css">
+                    <link rel="next" href="/page/2">
+                    <script src="/app.js"></script>
+                </head>
+                <body>
+                    <a href="/about">About</a>
+                    <map><area href="/region"></map>
+                    <iframe src="/frame"></iframe>
+                    <object data="/movie"></object>
+                    <form action="/search"></form>
+                    <img src="/logo.png" srcset="/logo-1x.png 1x, /logo-2x.png 2x">
+                    <picture><source srcset="/wide.webp"></picture>
+                    <video src="/clip.mp4"></video>
+                    <audio src="/song.mp3"></audio>
+                </body>
+            </html>
+        "#;
+        let links = document_links(&Document::from(html), "https://example.com/").unwrap();
+        let links: Vec<_> = links
+            .iter()
+            .map(|link| {
+                (
+                    link.url.trim_start_matches("https://example.com"),
+                    link.kind,
+                )
+            })
+            .collect();
+        assert_eq!(
+            links,
+            vec![
+                ("/moved", LinkKind::Redirect),
+                ("/style.css", LinkKind::Asset),
+                ("/page/2", LinkKind::Navigation),
+                ("/app.js", LinkKind::Asset),
+                ("/about", LinkKind::Navigation),
+                ("/region", LinkKind::Navigation),
+                ("/frame", LinkKind::Embed),
+                ("/movie", LinkKind::Embed),
+                ("/search", LinkKind::Form),
+                ("/logo.png", LinkKind::Asset),
+                ("/logo-1x.png", LinkKind::Asset),
+                ("/logo-2x.png", LinkKind::Asset),
+                ("/wide.webp", LinkKind::Asset),
+                ("/clip.mp4", LinkKind::Asset),
+                ("/song.mp3", LinkKind::Asset),
+            ]
+        );
+        assert_eq!(
+            refresh_target("0;url=https://example.com/"),
+            Some("https://example.com/")
+        );
+        assert_eq!(refresh_target("30"), None);
+    }
+    #[test]
+    fn test_link_details() {
+        let html = r#"
+            <p>Intro</p>
+            <a href="/docs" title=" Docs " hreflang="en">Read   the
+                <b>docs</b></a>
+            <a href="/ad" rel="Sponsored NOFOLLOW noopener"></a>
+            <img src="/logo.png" alt="Logo">
+        "#;
+        let links = document_links(&Document::from(html), "https://example.com/").unwrap();
+        assert_eq!(
+            links[0],
+            Link {
+                url: "https://example.com/docs".to_string(),
+                kind: LinkKind::Navigation,
+                element: "a".to_string(),
+                text: Some("Read the docs".to_string()),
+                rel: vec![],
+                title: Some("Docs".to_string()),
+                hreflang: Some("en".to_string()),
+                position: 0,
+            }
+        );
+        assert_eq!(links[1].rel, vec!["sponsored", "nofollow", "noopener"]);
+        assert!(links[1].is_nofollow());
+        assert_eq!(links[1].text, None);
+        assert_eq!(links[2].element, "img");
+        assert_eq!(links[2].text, Some("Logo".to_string()));
+        assert_eq!(links[2].position, 2);
+    }
+    #[test]
+    fn test_base_href() {
+        let html = r#"
+            <html>
+                <head>
+                    <base href="/docs/v2/">
+                    <meta http-equiv="refresh" content="0;url=latest.html">
+                </head>
+                <body>
+                    <a href="intro.html">Intro</a>
+                    <a href="/about">About</a>
+                    <a href="https://other.com/">Other</a>
+                </body>
+            </html>
+        "#;
+        let expected = vec![
+            "https://example.com/docs/v2/intro.html",
+            "https://example.com/about",
+            "https://other.com/",
+        ];
+        assert_eq!(
+            parse_html_links(html, "https://example.com/guide/page.html").unwrap(),
+            expected
+        );
+        let links =
+            document_links(&Document::from(html), "https://example.com/guide/page.html").unwrap();
+        assert_eq!(links[0].kind, LinkKind::Redirect);
+        assert_eq!(links[0].url, "https://example.com/docs/v2/latest.html");
+
+        // Without a base, links resolve against the page itself.
+        let html = r#"<a href="intro.html">Intro</a>"#;
+        assert_eq!(
+            parse_html_links(html, "https://example.com/guide/page.html").unwrap(),
+            vec!["https://example.com/guide/intro.html"]
+        );
+    }
+    #[test]
     fn test_normalize_url() {
         let base_url = "http://test.com/normalize";
         let relative_url = "/relative";
@@ -0,0 +1,309 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use flume::{bounded, Sender};
+use select::document::Document;
+use tokio::sync::oneshot;
+
+use crate::error::CrawlerError;
+use crate::modules::fetcher::client::DEFAULT_USER_AGENT;
+use crate::modules::parser::directives::{meta_directives, robot_name, RobotsDirectives};
+use crate::modules::parser::metadata::{extract_metadata, PageMetadata};
+use crate::modules::parser::parser::{document_links, extract_title, Link, LinkKind};
+use crate::modules::parser::readability::{MainContent, Readability};
+use crate::modules::parser::rules::{ExtractionRules, FieldValue};
+use crate::thread::panic_message;
+
+/// What the crawler takes from a fetched page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedPage {
+    pub links: Vec<Link>,
+    pub title: Option<String>,
+    /// Target of the page's meta refresh, if it has one.
+    pub refresh: Option<String>,
+    /// Directives of the page's robots meta tags.
+    pub robots: RobotsDirectives,
+    pub metadata: PageMetadata,
+    /// Fields extracted by the rules matching the page URL.
+    pub fields: BTreeMap<String, FieldValue>,
+    /// The page's main content without boilerplate.
+    pub content: Option<MainContent>,
+}
+
+/// What parsing looks for beyond links and the title.
+#[derive(Debug)]
+pub struct ParseOptions {
+    /// Name robots meta tags address the crawler by.
+    pub robot: String,
+    pub rules: ExtractionRules,
+    pub readability: Readability,
+}
+
+impl ParseOptions {
+    pub fn new(user_agent: &str, rules: ExtractionRules) -> Self {
+        Self {
+            robot: robot_name(user_agent),
+            rules,
+            readability: Readability::default(),
+        }
+    }
+
+    pub fn with_readability(mut self, readability: Readability) -> Self {
+        self.readability = readability;
+        self
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self::new(DEFAULT_USER_AGENT, ExtractionRules::default())
+    }
+}
+
+/// Parses `html` once and extracts everything the crawler needs from it.
+/// `base_url` is the page's own URL; a `<base href>` in it takes precedence.
+pub fn parse_page(
+    html: &str,
+    base_url: &str,
+    options: &ParseOptions,
+) -> Result<ParsedPage, CrawlerError> {
+    let document = Document::from(html);
+    let links = document_links(&document, base_url)?;
+    let refresh = links
+        .iter()
+        .find(|link| link.kind == LinkKind::Redirect)
+        .map(|link| link.url.clone());
+    Ok(ParsedPage {
+        links,
+        title: extract_title(&document),
+        refresh,
+        robots: meta_directives(&document, &options.robot),
+        metadata: extract_metadata(&document),
+        fields: options.rules.extract(base_url, &document),
+        content: options.readability.extract(base_url, &document),
+    })
+}
+
+struct ParseJob {
+    /// Does the work and returns what sends the result back, so the worker
+    /// can record the timing first.
+    work: Box<dyn FnOnce() -> Box<dyn FnOnce()> + Send>,
+    queued_at: Instant,
+}
+
+/// Counters for sizing the parse pool against the fetchers.
+#[derive(Debug, Default)]
+pub struct ParseMetrics {
+    depth: AtomicUsize,
+    peak_depth: AtomicUsize,
+    parsed: AtomicU64,
+    wait_micros: AtomicU64,
+    parse_micros: AtomicU64,
+}
+
+impl ParseMetrics {
+    /// Pages waiting for a parser right now.
+    pub fn queue_depth(&self) -> usize {
+        self.depth.load(Ordering::Relaxed)
+    }
+    pub fn peak_queue_depth(&self) -> usize {
+        self.peak_depth.load(Ordering::Relaxed)
+    }
+    pub fn parsed(&self) -> u64 {
+        self.parsed.load(Ordering::Relaxed)
+    }
+    /// Average time a page waited in the queue.
+    pub fn mean_wait(&self) -> Duration {
+        self.mean(&self.wait_micros)
+    }
+    /// Average time spent parsing a page.
+    pub fn mean_parse(&self) -> Duration {
+        self.mean(&self.parse_micros)
+    }
+
+    fn mean(&self, total: &AtomicU64) -> Duration {
+        let parsed = self.parsed().max(1);
+        Duration::from_micros(total.load(Ordering::Relaxed) / parsed)
+    }
+}
+
+impl fmt::Display for ParseMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} pages parsed, {:?} queued and {:?} parsing on average, queue depth {} (peak {})",
+            self.parsed(),
+            self.mean_wait(),
+            self.mean_parse(),
+            self.queue_depth(),
+            self.peak_queue_depth()
+        )
+    }
+}
+
+/// Dedicated OS threads that parse HTML, so large pages never block the
+/// async workers doing IO. Pages reach the threads through a bounded queue;
+/// `parse` waits for room in it.
+pub struct ParsePool {
+    sender: Option<Sender<ParseJob>>,
+    workers: Vec<thread::JoinHandle<()>>,
+    metrics: Arc<ParseMetrics>,
+    parse_page: PageParser,
+}
+
+type PageParser = fn(&str, &str, &ParseOptions) -> Result<ParsedPage, CrawlerError>;
+
+impl ParsePool {
+    pub fn new(threads: usize, queue_size: usize) -> Self {
+        let (sender, receiver) = bounded::<ParseJob>(queue_size);
+        let metrics = Arc::new(ParseMetrics::default());
+        let workers = (0..threads.max(1))
+            .map(|i| {
+                let receiver = receiver.clone();
+                let metrics = metrics.clone();
+                thread::Builder::new()
+                    .name(format!("parser-{i}"))
+                    .spawn(move || {
+                        while let Ok(job) = receiver.recv() {
+                            metrics.depth.fetch_sub(1, Ordering::Relaxed);
+                            let started = Instant::now();
+                            let reply = (job.work)();
+                            let wait = started - job.queued_at;
+                            metrics
+                                .wait_micros
+                                .fetch_add(wait.as_micros() as u64, Ordering::Relaxed);
+                            metrics
+                                .parse_micros
+                                .fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+                            metrics.parsed.fetch_add(1, Ordering::Relaxed);
+                            reply();
+                        }
+                    })
+                    .expect("failed to spawn parser thread")
+            })
+            .collect();
+        Self {
+            sender: Some(sender),
+            workers,
+            metrics,
+            parse_page,
+        }
+    }
+
+    /// Parses pages with `parse` instead of `parse_page`, e.g. one that
+    /// panics like a parser bug would.
+    #[cfg(test)]
+    pub(crate) fn with_page_parser(mut self, parse: PageParser) -> Self {
+        self.parse_page = parse;
+        self
+    }
+
+    /// Parses `html` on a parser thread.
+    pub async fn parse(
+        &self,
+        html: String,
+        base_url: &str,
+        options: Arc<ParseOptions>,
+    ) -> Result<ParsedPage, CrawlerError> {
+        let base_url = base_url.to_string();
+        let parse = self.parse_page;
+        self.run(move || parse(&html, &base_url, &options)).await
+    }
+
+    /// Runs any other CPU-bound document work on a parser thread.
+    pub async fn run<T, F>(&self, f: F) -> Result<T, CrawlerError>
+    where
+        F: FnOnce() -> Result<T, CrawlerError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let sender = self.sender.as_ref().ok_or(CrawlerError::PoolClosed)?;
+        let (reply, parsed) = oneshot::channel();
+        let job = ParseJob {
+            work: Box::new(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+                    Err(CrawlerError::TaskPanicked(panic_message(payload)))
+                });
+                Box::new(move || {
+                    let _ = reply.send(result);
+                })
+            }),
+            queued_at: Instant::now(),
+        };
+        let depth = self.metrics.depth.fetch_add(1, Ordering::Relaxed) + 1;
+        self.metrics.peak_depth.fetch_max(depth, Ordering::Relaxed);
+        if sender.send_async(job).await.is_err() {
+            self.metrics.depth.fetch_sub(1, Ordering::Relaxed);
+            return Err(CrawlerError::PoolClosed);
+        }
+        parsed.await.map_err(|_| CrawlerError::TaskCancelled)?
+    }
+
+    pub fn metrics(&self) -> &ParseMetrics {
+        &self.metrics
+    }
+}
+
+impl Drop for ParsePool {
+    fn drop(&mut self) {
+        // The threads exit once the queued pages are parsed.
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_parse_pool() {
+        let pool = ParsePool::new(2, 1);
+        let options = Arc::new(ParseOptions::default());
+        let pages = (0..8).map(|i| {
+            let html = format!(
+                "<html><head><title>Page {i}</title></head>\
+                 <body><a href=\"/next/{i}\">next</a></body></html>"
+            );
+            pool.parse(html, "https://example.com/", options.clone())
+        });
+        let parsed = futures::future::join_all(pages).await;
+
+        for (i, page) in parsed.into_iter().enumerate() {
+            let page = page.unwrap();
+            assert_eq!(page.title, Some(format!("Page {i}")));
+            let links: Vec<_> = page.links.into_iter().map(|link| link.url).collect();
+            assert_eq!(links, vec![format!("https://example.com/next/{i}")]);
+        }
+        assert_eq!(pool.metrics().parsed(), 8);
+        assert_eq!(pool.metrics().queue_depth(), 0);
+        assert!(pool.metrics().peak_queue_depth() >= 1);
+    }
+
+    #[tokio::test]
+    async fn test_parse_pool_survives_panics() {
+        let pool = ParsePool::new(1, 1);
+        let panicked = pool
+            .run(|| -> Result<(), _> { panic!("cannot parse https://example.com/") })
+            .await;
+        assert!(matches!(
+            panicked,
+            Err(CrawlerError::TaskPanicked(message)) if message == "cannot parse https://example.com/"
+        ));
+        let page = pool
+            .parse(
+                "<title>Fine</title>".to_string(),
+                "https://example.com/",
+                Arc::new(ParseOptions::default()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.title, Some("Fine".to_string()));
+    }
+}
@@ -0,0 +1,484 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+use select::document::Document;
+use select::node::Node;
+use select::predicate::Name;
+use serde::{Deserialize, Serialize};
+
+use crate::error::CrawlerError;
+use crate::modules::parser::rules::Selector;
+use crate::scope::is_same_or_subdomain;
+
+/// Elements whose text can be a paragraph of the main content.
+const BLOCKS: [&str; 12] = [
+    "p",
+    "pre",
+    "blockquote",
+    "li",
+    "td",
+    "div",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadabilityConfig {
+    /// Extract the main content of each page.
+    pub enabled: bool,
+    /// Reading speed used for the reading time.
+    pub words_per_minute: usize,
+    pub boilerplate: BoilerplateConfig,
+    /// Additions to `boilerplate` for a domain and its subdomains.
+    pub domains: HashMap<String, DomainBoilerplate>,
+}
+
+impl Default for ReadabilityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            words_per_minute: 200,
+            boilerplate: BoilerplateConfig::default(),
+            domains: HashMap::new(),
+        }
+    }
+}
+
+/// How to tell boilerplate such as navigation, footers and ads from content.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoilerplateConfig {
+    /// Selectors of elements that are never content.
+    pub remove: Vec<String>,
+    /// Regex on class and id marking blocks as boilerplate.
+    pub unlikely: String,
+    /// Regex on class and id favouring a block as the main content. Blocks
+    /// `unlikely` also matches, such as `sidebar-content`, stay boilerplate.
+    pub likely: String,
+    /// Blocks with a larger share of their text in links are boilerplate.
+    pub max_link_density: f64,
+    /// Shorter paragraphs do not count towards a block's score.
+    pub min_text_length: usize,
+    /// Blocks with fewer characters of text per element are boilerplate.
+    pub min_text_density: f64,
+}
+
+impl Default for BoilerplateConfig {
+    fn default() -> Self {
+        Self {
+            remove: [
+                "nav", "header", "footer", "aside", "script", "style", "noscript", "form",
+                "iframe", "button", "select",
+            ]
+            .map(str::to_string)
+            .to_vec(),
+            unlikely: r"(?i)\b(ads?|advert\w*|banner|breadcrumbs?|comments?|cookies?|footer|menu|nav\w*|popup|promo\w*|related|share|sidebar|social|sponsor\w*|subscribe)\b".to_string(),
+            likely: r"(?i)\b(article|body|content|entry|main|post|story|text)\b".to_string(),
+            max_link_density: 0.5,
+            min_text_length: 25,
+            min_text_density: 8.0,
+        }
+    }
+}
+
+/// Per-domain boilerplate settings; unset values keep the global ones.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DomainBoilerplate {
+    /// Selectors removed in addition to the global ones.
+    pub remove: Vec<String>,
+    pub unlikely: Option<String>,
+    pub max_link_density: Option<f64>,
+    pub min_text_length: Option<usize>,
+}
+
+/// The main content of a page as clean text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MainContent {
+    /// Paragraphs separated by blank lines.
+    pub text: String,
+    pub word_count: usize,
+    pub reading_minutes: usize,
+}
+
+#[derive(Debug)]
+struct Boilerplate {
+    remove: Vec<Selector>,
+    unlikely: Regex,
+    likely: Regex,
+    max_link_density: f64,
+    min_text_length: usize,
+    min_text_density: f64,
+}
+
+/// Which nodes of a document are leaf blocks and which are or sit inside
+/// boilerplate, by node index.
+struct Blocks {
+    leaf: Vec<bool>,
+    excluded: Vec<bool>,
+}
+
+impl Blocks {
+    /// Whether `node` is a block that can be a paragraph of the content.
+    fn is_paragraph(&self, node: &Node) -> bool {
+        self.leaf[node.index()] && !self.excluded[node.index()]
+    }
+}
+
+/// Readability-style extraction: paragraphs score the blocks around them,
+/// the best block minus its links is the main content.
+#[derive(Debug)]
+pub struct Readability {
+    enabled: bool,
+    words_per_minute: usize,
+    default: Boilerplate,
+    domains: Vec<(String, Boilerplate)>,
+}
+
+impl Default for Readability {
+    fn default() -> Self {
+        Self::new(&ReadabilityConfig::default()).expect("default readability config is valid")
+    }
+}
+
+impl Readability {
+    pub fn new(config: &ReadabilityConfig) -> Result<Self, CrawlerError> {
+        let global = &config.boilerplate;
+        let domains = config
+            .domains
+            .iter()
+            .map(|(domain, overrides)| {
+                let merged = BoilerplateConfig {
+                    remove: global
+                        .remove
+                        .iter()
+                        .chain(&overrides.remove)
+                        .cloned()
+                        .collect(),
+                    unlikely: overrides
+                        .unlikely
+                        .clone()
+                        .unwrap_or_else(|| global.unlikely.clone()),
+                    likely: global.likely.clone(),
+                    max_link_density: overrides
+                        .max_link_density
+                        .unwrap_or(global.max_link_density),
+                    min_text_length: overrides.min_text_length.unwrap_or(global.min_text_length),
+                    min_text_density: global.min_text_density,
+                };
+                Ok((domain.to_lowercase(), Boilerplate::new(&merged)?))
+            })
+            .collect::<Result<_, CrawlerError>>()?;
+        Ok(Self {
+            enabled: config.enabled,
+            words_per_minute: config.words_per_minute.max(1),
+            default: Boilerplate::new(global)?,
+            domains,
+        })
+    }
+
+    /// The main content of the page at `url`, if it has any.
+    pub fn extract(&self, url: &str, document: &Document) -> Option<MainContent> {
+        if !self.enabled {
+            return None;
+        }
+        let boilerplate = self.boilerplate_for(url);
+        let blocks = boilerplate.blocks(document);
+        let candidate = boilerplate.best_candidate(document, &blocks)?;
+        let paragraphs: Vec<String> = candidate
+            .find(|node: &Node| blocks.is_paragraph(node))
+            .filter_map(|block| {
+                let text = clean_text(&block);
+                let heading = block.name().is_some_and(|name| name.starts_with('h'));
+                let keep = !text.is_empty()
+                    && (heading || text.len() >= boilerplate.min_text_length)
+                    && link_density(&block, &text) <= boilerplate.max_link_density;
+                keep.then_some(text)
+            })
+            .collect();
+        let text = paragraphs.join("\n\n");
+        let word_count = text.split_whitespace().count();
+        if word_count == 0 {
+            return None;
+        }
+        Some(MainContent {
+            text,
+            word_count,
+            reading_minutes: word_count.div_ceil(self.words_per_minute),
+        })
+    }
+
+    fn boilerplate_for(&self, url: &str) -> &Boilerplate {
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase));
+        host.and_then(|host| {
+            self.domains
+                .iter()
+                .filter(|(domain, _)| is_same_or_subdomain(&host, domain))
+                .max_by_key(|(domain, _)| domain.len())
+        })
+        .map_or(&self.default, |(_, boilerplate)| boilerplate)
+    }
+}
+
+impl Boilerplate {
+    fn new(config: &BoilerplateConfig) -> Result<Self, CrawlerError> {
+        let compile = |pattern: &str| {
+            Regex::new(pattern).map_err(|e| {
+                CrawlerError::Other(format!("Invalid boilerplate pattern {pattern}: {e}"))
+            })
+        };
+        Ok(Self {
+            remove: config
+                .remove
+                .iter()
+                .map(|selector| Selector::parse(selector))
+                .collect::<Result<_, _>>()?,
+            unlikely: compile(&config.unlikely)?,
+            likely: compile(&config.likely)?,
+            max_link_density: config.max_link_density,
+            min_text_length: config.min_text_length,
+            min_text_density: config.min_text_density,
+        })
+    }
+
+    /// The block whose paragraphs make it the most likely main content.
+    fn best_candidate<'a>(&self, document: &'a Document, blocks: &Blocks) -> Option<Node<'a>> {
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+        for block in document.find(|node: &Node| blocks.is_paragraph(node)) {
+            let text = clean_text(&block);
+            if text.len() < self.min_text_length
+                || text_density(&block, &text) < self.min_text_density
+            {
+                continue;
+            }
+            let score =
+                1.0 + text.matches(',').count() as f64 + (text.len() as f64 / 100.0).min(3.0);
+            // Paragraphs vote for the blocks around them, never for themselves.
+            if let Some(parent) = block.parent() {
+                *scores.entry(parent.index()).or_default() += score;
+                if let Some(grandparent) = parent.parent() {
+                    *scores.entry(grandparent.index()).or_default() += score / 2.0;
+                }
+            }
+        }
+        scores
+            .into_iter()
+            .map(|(index, score)| {
+                let node = document
+                    .nth(index)
+                    .expect("scored nodes are in the document");
+                let text = clean_text(&node);
+                let score = (score + self.class_weight(&node)) * (1.0 - link_density(&node, &text));
+                (node, score)
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(node, _)| node)
+    }
+
+    fn class_weight(&self, node: &Node) -> f64 {
+        let names = class_and_id(node);
+        let mut weight = 0.0;
+        if self.likely.is_match(&names) {
+            weight += 25.0;
+        }
+        if self.unlikely.is_match(&names) {
+            weight -= 25.0;
+        }
+        weight
+    }
+
+    /// Whether `node` itself is boilerplate, regardless of its ancestors.
+    fn is_boilerplate(&self, node: &Node) -> bool {
+        if matches!(
+            node.name(),
+            None | Some("html" | "body" | "main" | "article")
+        ) {
+            return false;
+        }
+        self.remove.iter().any(|selector| selector.matches(node))
+            || self.unlikely.is_match(&class_and_id(node))
+    }
+
+    /// Flags every node of `document` in a walk down the tree for
+    /// boilerplate and one back up for blocks nested in blocks.
+    fn blocks(&self, document: &Document) -> Blocks {
+        let count = document.nodes.len();
+        // Parents come before their children. Node indices do not promise
+        // that, as the HTML parser moves misnested elements.
+        let mut order = Vec::with_capacity(count);
+        let mut stack: Vec<Node> = document
+            .find(|node: &Node| node.parent().is_none())
+            .collect();
+        while let Some(node) = stack.pop() {
+            order.push(node);
+            stack.extend(node.children());
+        }
+
+        let mut excluded = vec![false; count];
+        for node in &order {
+            excluded[node.index()] = node.parent().is_some_and(|parent| excluded[parent.index()])
+                || self.is_boilerplate(node);
+        }
+        let mut has_block = vec![false; count];
+        let mut leaf = vec![false; count];
+        for node in order.iter().rev() {
+            let block = node.name().is_some_and(|name| BLOCKS.contains(&name));
+            leaf[node.index()] = block && !has_block[node.index()];
+            if let Some(parent) = node.parent() {
+                has_block[parent.index()] |= block || has_block[node.index()];
+            }
+        }
+        Blocks { leaf, excluded }
+    }
+}
+
+fn class_and_id(node: &Node) -> String {
+    format!(
+        "{} {}",
+        node.attr("class").unwrap_or_default(),
+        node.attr("id").unwrap_or_default()
+    )
+}
+
+fn clean_text(node: &Node) -> String {
+    node.text().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Share of the text of `node` that sits in links.
+fn link_density(node: &Node, text: &str) -> f64 {
+    if text.is_empty() {
+        return 0.0;
+    }
+    let linked: usize = node.find(Name("a")).map(|a| clean_text(&a).len()).sum();
+    linked as f64 / text.len() as f64
+}
+
+/// Characters of text per element in `node`, itself included.
+fn text_density(node: &Node, text: &str) -> f64 {
+    let elements = 1 + node
+        .descendants()
+        .filter(|node| node.name().is_some())
+        .count();
+    text.len() as f64 / elements as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEWS: &str = include_str!("fixtures/news_article.html");
+    const BLOG: &str = include_str!("fixtures/blog_post.html");
+    const DOCS: &str = include_str!("fixtures/docs_page.html");
+    const FEATURE: &str = include_str!("fixtures/magazine_feature.html");
+
+    fn extract(readability: &Readability, url: &str, html: &str) -> MainContent {
+        readability.extract(url, &Document::from(html)).unwrap()
+    }
+
+    #[test]
+    fn test_news_article() {
+        let content = extract(&Readability::default(), "https://news.example.com/a", NEWS);
+        assert!(content
+            .text
+            .starts_with("City council approves new bike lanes"));
+        assert!(content
+            .text
+            .contains("twelve kilometres of protected lanes"));
+        assert!(content
+            .text
+            .contains("construction is expected to begin in spring"));
+        for boilerplate in [
+            "Home",
+            "Subscribe now",
+            "Advertisement",
+            "All rights reserved",
+        ] {
+            assert!(!content.text.contains(boilerplate), "kept {boilerplate:?}");
+        }
+        assert_eq!(content.word_count, content.text.split_whitespace().count());
+        assert_eq!(content.reading_minutes, 1);
+    }
+
+    #[test]
+    fn test_blog_post() {
+        let content = extract(&Readability::default(), "https://blog.example.org/p", BLOG);
+        assert!(content.text.contains("sourdough starter"));
+        assert!(content.text.contains("Feed it twice a day"));
+        // Comments, share buttons and the link-heavy related posts are dropped.
+        assert!(!content.text.contains("Great recipe"));
+        assert!(!content.text.contains("Share on"));
+        assert!(!content.text.contains("Ten breads to bake"));
+        assert_eq!(content.text.split("\n\n").count(), 5);
+    }
+
+    #[test]
+    fn test_unlikely_beats_likely() {
+        let content = extract(
+            &Readability::default(),
+            "https://mag.example.com/f",
+            FEATURE,
+        );
+        assert!(content.text.starts_with("The last lighthouse keepers"));
+        assert!(content.text.contains("handed over the keys"));
+        assert!(content.text.contains("now being scanned"));
+        // `sidebar-content` and `nav-body` match both patterns.
+        assert!(!content.text.contains("Most popular this week"));
+        assert!(!content.text.contains("Continue reading"));
+        assert_eq!(content.text.split("\n\n").count(), 4);
+    }
+
+    #[test]
+    fn test_domain_boilerplate() {
+        let url = "https://docs.example.net/guide/install";
+        let content = extract(&Readability::default(), url, DOCS);
+        assert!(content.text.contains("On this page"));
+
+        let config = ReadabilityConfig {
+            words_per_minute: 10,
+            domains: HashMap::from([(
+                "example.net".to_string(),
+                DomainBoilerplate {
+                    remove: vec!["div.toc".to_string(), "p.edit-link".to_string()],
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let readability = Readability::new(&config).unwrap();
+        let content = extract(&readability, url, DOCS);
+        assert!(content.text.contains("cargo install thamur"));
+        assert!(!content.text.contains("On this page"));
+        assert!(!content.text.contains("Edit this page"));
+        assert_eq!(content.reading_minutes, content.word_count.div_ceil(10));
+
+        // Other domains keep the global settings.
+        let content = extract(&readability, "https://other.example.com/", DOCS);
+        assert!(content.text.contains("On this page"));
+    }
+
+    #[test]
+    fn test_no_content() {
+        let html = "<html><body><nav><a href=\"/\">Home</a></nav></body></html>";
+        assert_eq!(
+            Readability::default().extract("https://example.com/", &Document::from(html)),
+            None
+        );
+
+        let disabled = Readability::new(&ReadabilityConfig {
+            enabled: false,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            disabled.extract("https://example.com/", &Document::from(NEWS)),
+            None
+        );
+    }
+}
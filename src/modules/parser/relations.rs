@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+
+use crate::modules::parser::parser::Link;
+
+/// A translation or regional variant of a page.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Alternate {
+    pub url: String,
+    pub hreflang: String,
+}
+
+/// How a page relates to other URLs: its canonical version, its language
+/// alternates and its neighbours in a paginated series.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PageRelations {
+    pub canonical: Option<String>,
+    pub alternates: Vec<Alternate>,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+impl PageRelations {
+    /// Collects the relations declared by `Link` response headers and by the
+    /// page's own links. Header values resolve against `page_url`; where both
+    /// declare the same relation, the header wins.
+    pub fn collect(links: &[Link], link_headers: &[String], page_url: &url::Url) -> Self {
+        let mut relations = Self::default();
+        let header_links = link_headers
+            .iter()
+            .flat_map(|value| parse_link_header(value))
+            .filter_map(|link| {
+                let url = page_url.join(&link.url).ok()?;
+                Some(HeaderLink {
+                    url: url.to_string(),
+                    ..link
+                })
+            });
+        for link in header_links {
+            relations.add(&link.url, &link.rel, link.hreflang.as_deref(), true);
+        }
+        for link in links {
+            // Only `<link>` can name a canonical; `<a rel=next>` also paginates.
+            let canonical_allowed = link.element == "link";
+            relations.add(
+                &link.url,
+                &link.rel,
+                link.hreflang.as_deref(),
+                canonical_allowed,
+            );
+        }
+        relations
+    }
+
+    fn add(&mut self, url: &str, rel: &[String], hreflang: Option<&str>, canonical_allowed: bool) {
+        for rel in rel {
+            match rel.as_str() {
+                "canonical" if canonical_allowed => {
+                    self.canonical.get_or_insert_with(|| url.to_string());
+                }
+                "alternate" => {
+                    if let Some(hreflang) = hreflang {
+                        let alternate = Alternate {
+                            url: url.to_string(),
+                            hreflang: hreflang.to_ascii_lowercase(),
+                        };
+                        if !self.alternates.contains(&alternate) {
+                            self.alternates.push(alternate);
+                        }
+                    }
+                }
+                "next" => {
+                    self.next.get_or_insert_with(|| url.to_string());
+                }
+                "prev" | "previous" => {
+                    self.prev.get_or_insert_with(|| url.to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// One entry of a `Link` header, e.g. `<https://example.com/>; rel="canonical"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderLink {
+    pub url: String,
+    /// Lowercased relation types.
+    pub rel: Vec<String>,
+    pub hreflang: Option<String>,
+}
+
+/// Parses the entries of a `Link` header value. Target URLs are returned as
+/// written, possibly relative.
+pub fn parse_link_header(value: &str) -> Vec<HeaderLink> {
+    let mut links = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>').map(|end| start + end) else {
+            break;
+        };
+        let url = rest[start + 1..end].trim().to_string();
+        rest = &rest[end + 1..];
+        let params = &rest[..rest.find('<').unwrap_or(rest.len())];
+        let mut link = HeaderLink {
+            url,
+            rel: Vec::new(),
+            hreflang: None,
+        };
+        for param in params.split(';') {
+            let Some((name, value)) = param.split_once('=') else {
+                continue;
+            };
+            let value = value.trim().trim_end_matches(',').trim().trim_matches('"');
+            match name.trim().to_ascii_lowercase().as_str() {
+                "rel" => {
+                    link.rel = value
+                        .split_whitespace()
+                        .map(str::to_ascii_lowercase)
+                        .collect()
+                }
+                "hreflang" => link.hreflang = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        links.push(link);
+    }
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::parser::parser::document_links;
+    use select::document::Document;
+
+    #[test]
+    fn test_collect_relations() {
+        let html = r#"
+            <head>
+                <link rel="canonical" href="/article">
+                <link rel="alternate" hreflang="de" href="/de/article">
+                <link rel="alternate" hreflang="EN" href="/article">
+                <link rel="prev" href="/article/1">
+            </head>
+            <body>
+                <a rel="canonical" href="/ignored">Not a canonical</a>
+                <a rel="next" href="/article/3">Next</a>
+            </body>
+        "#;
+        let page = url::Url::parse("https://example.com/article/2").unwrap();
+        let links = document_links(&Document::from(html), page.as_str()).unwrap();
+
+        let relations = PageRelations::collect(&links, &[], &page);
+        assert_eq!(
+            relations,
+            PageRelations {
+                canonical: Some("https://example.com/article".to_string()),
+                alternates: vec![
+                    Alternate {
+                        url: "https://example.com/de/article".to_string(),
+                        hreflang: "de".to_string(),
+                    },
+                    Alternate {
+                        url: "https://example.com/article".to_string(),
+                        hreflang: "en".to_string(),
+                    },
+                ],
+                next: Some("https://example.com/article/3".to_string()),
+                prev: Some("https://example.com/article/1".to_string()),
+            }
+        );
+
+        let headers = vec![r#"</print/article>; rel="canonical", <https://example.fr/>; rel=alternate; hreflang="fr""#.to_string()];
+        let relations = PageRelations::collect(&links, &headers, &page);
+        assert_eq!(
+            relations.canonical.as_deref(),
+            Some("https://example.com/print/article")
+        );
+        assert_eq!(relations.alternates[0].hreflang, "fr");
+    }
+}
@@ -0,0 +1,525 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use regex::Regex;
+use select::document::Document;
+use select::node::Node;
+use select::predicate::{Attr, Class, Name, Predicate};
+use serde::{Deserialize, Serialize};
+
+use crate::error::CrawlerError;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtractionConfig {
+    /// JSON file with the extraction rules; nothing is extracted when unset.
+    pub rules_file: Option<String>,
+}
+
+/// Fields to extract from pages whose URL matches `url`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtractionRule {
+    /// Regex matched against the page URL.
+    pub url: String,
+    pub fields: BTreeMap<String, FieldRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldRule {
+    /// CSS selector: tags, `*`, `.class`, `#id`, `[attr]` and `[attr=value]`,
+    /// combined with descendant and `>` child combinators and `,` lists. Other
+    /// CSS, such as pseudo-classes, is refused when the rules are loaded.
+    pub selector: String,
+    #[serde(default)]
+    pub take: Take,
+    /// Keep every match instead of the first one.
+    #[serde(default)]
+    pub multiple: bool,
+    /// Regex applied to each value, keeping its first group or else the whole
+    /// match. Values it does not match are dropped.
+    #[serde(default)]
+    pub pattern: Option<String>,
+}
+
+/// What to take from a matched element.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Take {
+    /// Its text, whitespace collapsed.
+    #[default]
+    Text,
+    /// Its inner HTML.
+    Html,
+    /// The value of an attribute.
+    Attr(String),
+}
+
+/// An extracted field.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FieldValue {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Compiled extraction rules.
+#[derive(Debug, Default)]
+pub struct ExtractionRules {
+    rules: Vec<(Regex, Vec<(String, CompiledField)>)>,
+}
+
+#[derive(Debug)]
+struct CompiledField {
+    selector: Selector,
+    take: Take,
+    multiple: bool,
+    pattern: Option<Regex>,
+}
+
+impl ExtractionRules {
+    pub fn new(rules: &[ExtractionRule]) -> Result<Self, CrawlerError> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let fields = rule
+                    .fields
+                    .iter()
+                    .map(|(name, field)| {
+                        let compiled = CompiledField {
+                            selector: Selector::parse(&field.selector)?,
+                            take: field.take.clone(),
+                            multiple: field.multiple,
+                            pattern: field.pattern.as_deref().map(compile).transpose()?,
+                        };
+                        Ok((name.clone(), compiled))
+                    })
+                    .collect::<Result<_, CrawlerError>>()?;
+                Ok((compile(&rule.url)?, fields))
+            })
+            .collect::<Result<_, CrawlerError>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Reads and compiles the rules in a JSON file.
+    pub fn load(path: &str) -> Result<Self, CrawlerError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| CrawlerError::Other(format!("Cannot read rules file {path}: {e}")))?;
+        let rules: Vec<ExtractionRule> = serde_json::from_str(&contents)
+            .map_err(|e| CrawlerError::Other(format!("Invalid rules file {path}: {e}")))?;
+        Self::new(&rules)
+    }
+
+    /// Extracts the fields of every rule matching `url`. Where rules define
+    /// the same field, the first one wins.
+    pub fn extract(&self, url: &str, document: &Document) -> BTreeMap<String, FieldValue> {
+        let mut fields = BTreeMap::new();
+        for (_, rule_fields) in self.rules.iter().filter(|(re, _)| re.is_match(url)) {
+            for (name, field) in rule_fields {
+                if fields.contains_key(name) {
+                    continue;
+                }
+                if let Some(value) = field.extract(document) {
+                    fields.insert(name.clone(), value);
+                }
+            }
+        }
+        fields
+    }
+}
+
+impl CompiledField {
+    fn extract(&self, document: &Document) -> Option<FieldValue> {
+        let mut values = document
+            .find(|node: &Node| self.selector.matches(node))
+            .filter_map(|node| self.value(&node));
+        if self.multiple {
+            Some(FieldValue::Many(values.collect()))
+        } else {
+            values.next().map(FieldValue::One)
+        }
+    }
+
+    fn value(&self, node: &Node) -> Option<String> {
+        let value = match &self.take {
+            Take::Text => node.text().split_whitespace().collect::<Vec<_>>().join(" "),
+            Take::Html => node.inner_html(),
+            Take::Attr(name) => node.attr(name)?.to_string(),
+        };
+        match &self.pattern {
+            Some(pattern) => {
+                let captures = pattern.captures(&value)?;
+                let matched = captures.get(1).or_else(|| captures.get(0))?;
+                Some(matched.as_str().to_string())
+            }
+            None => Some(value),
+        }
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex, CrawlerError> {
+    Regex::new(pattern)
+        .map_err(|e| CrawlerError::Other(format!("Invalid extraction pattern {pattern}: {e}")))
+}
+
+/// A parsed selector list; matches nodes any of its selectors match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Selector(Vec<Complex>);
+
+/// Compound selectors joined by combinators, left to right.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Complex {
+    first: Compound,
+    rest: Vec<(Combinator, Compound)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+/// Conditions on a single element, e.g. `a.nav[href]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Compound {
+    name: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    attrs: Vec<(String, Option<String>)>,
+}
+
+/// Splits `text` at the characters `is_separator` accepts, except inside
+/// attribute brackets and quoted values. Each piece comes with the separator
+/// that ended it. `None` if a bracket or quote is left open.
+fn split_outside(
+    text: &str,
+    is_separator: impl Fn(char) -> bool,
+) -> Option<Vec<(&str, Option<char>)>> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut in_brackets = false;
+    for (i, ch) in text.char_indices() {
+        if let Some(open) = quote {
+            if ch == open {
+                quote = None;
+            }
+        } else if in_brackets {
+            match ch {
+                '"' | '\'' => quote = Some(ch),
+                ']' => in_brackets = false,
+                _ => {}
+            }
+        } else if ch == '[' {
+            in_brackets = true;
+        } else if is_separator(ch) {
+            pieces.push((&text[start..i], Some(ch)));
+            start = i + ch.len_utf8();
+        }
+    }
+    pieces.push((&text[start..], None));
+    (quote.is_none() && !in_brackets).then_some(pieces)
+}
+
+/// Tag, class, id and attribute names: letters, digits, `-` and `_`.
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == '-' || ch == '_')
+}
+
+/// Byte offset of the `]` closing an attribute selector, skipping quoted
+/// values.
+fn closing_bracket(text: &str) -> Option<usize> {
+    let mut quote = None;
+    text.char_indices().find_map(|(i, ch)| {
+        match (quote, ch) {
+            (Some(open), _) if ch == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(ch),
+            (None, ']') => return Some(i),
+            (None, _) => {}
+        }
+        None
+    })
+}
+
+/// An attribute selector's value: quoted, or a bare name.
+fn attr_value(value: &str) -> Option<String> {
+    let unquoted = ['"', '\''].into_iter().find_map(|quote| {
+        value
+            .strip_prefix(quote)?
+            .strip_suffix(quote)
+            .filter(|inner| !inner.contains(quote))
+    });
+    match unquoted {
+        Some(inner) => Some(inner.to_string()),
+        None => is_name(value).then(|| value.to_string()),
+    }
+}
+
+impl Selector {
+    /// Parses the subset of CSS documented on `FieldRule::selector`; anything
+    /// else, such as pseudo-classes or `^=`, is rejected rather than read as
+    /// something it is not.
+    pub(crate) fn parse(selector: &str) -> Result<Self, CrawlerError> {
+        let invalid = || CrawlerError::Other(format!("Invalid selector {selector:?}"));
+        let list = split_outside(selector, |ch| ch == ',')
+            .ok_or_else(invalid)?
+            .into_iter()
+            .map(|(complex, _)| Complex::parse(complex).ok_or_else(invalid))
+            .collect::<Result<_, _>>()?;
+        Ok(Self(list))
+    }
+
+    pub(crate) fn matches(&self, node: &Node) -> bool {
+        self.0.iter().any(|complex| complex.matches(node))
+    }
+}
+
+impl Complex {
+    fn parse(selector: &str) -> Option<Self> {
+        let pieces = split_outside(selector, |ch| {
+            ch.is_whitespace() || matches!(ch, '>' | '+' | '~')
+        })?;
+        let mut compounds = Vec::new();
+        let mut combinator = None;
+        for (piece, separator) in pieces {
+            if !piece.is_empty() {
+                compounds.push((combinator.take(), Compound::parse(piece)?));
+            }
+            match separator {
+                Some(ch) if ch.is_whitespace() && !compounds.is_empty() => {
+                    combinator.get_or_insert(Combinator::Descendant);
+                }
+                None => {}
+                Some(ch) if ch.is_whitespace() => {}
+                Some('>') if !compounds.is_empty() && combinator != Some(Combinator::Child) => {
+                    combinator = Some(Combinator::Child);
+                }
+                // Sibling combinators and misplaced `>`.
+                Some(_) => return None,
+            }
+        }
+        if combinator == Some(Combinator::Child) {
+            return None;
+        }
+        let mut compounds = compounds.into_iter();
+        let (_, first) = compounds.next()?;
+        let rest = compounds
+            .map(|(combinator, compound)| Some((combinator?, compound)))
+            .collect::<Option<_>>()?;
+        Some(Self { first, rest })
+    }
+
+    fn matches(&self, node: &Node) -> bool {
+        let compounds: Vec<_> = std::iter::once(&self.first)
+            .chain(self.rest.iter().map(|(_, compound)| compound))
+            .collect();
+        self.matches_from(node, &compounds)
+    }
+
+    /// Whether `node` matches the last of `compounds` with the ones before
+    /// it matching its ancestors as the combinators require.
+    fn matches_from(&self, node: &Node, compounds: &[&Compound]) -> bool {
+        let Some((last, before)) = compounds.split_last() else {
+            return true;
+        };
+        if !last.matches(node) {
+            return false;
+        }
+        if before.is_empty() {
+            return true;
+        }
+        match self.rest[before.len() - 1].0 {
+            Combinator::Child => node
+                .parent()
+                .is_some_and(|parent| self.matches_from(&parent, before)),
+            Combinator::Descendant => std::iter::successors(node.parent(), |node| node.parent())
+                .any(|ancestor| self.matches_from(&ancestor, before)),
+        }
+    }
+}
+
+impl Compound {
+    fn parse(token: &str) -> Option<Self> {
+        let mut compound = Compound::default();
+        let name_end = token.find(['.', '#', '[']).unwrap_or(token.len());
+        match &token[..name_end] {
+            "" | "*" => {}
+            name if is_name(name) => compound.name = Some(name.to_ascii_lowercase()),
+            _ => return None,
+        }
+        let mut rest = &token[name_end..];
+        while let Some(kind) = rest.chars().next() {
+            rest = &rest[1..];
+            if kind == '[' {
+                let end = closing_bracket(rest)?;
+                let (name, value) = match rest[..end].split_once('=') {
+                    Some((name, value)) => (name.trim(), Some(attr_value(value.trim())?)),
+                    None => (rest[..end].trim(), None),
+                };
+                if !is_name(name) {
+                    return None;
+                }
+                compound.attrs.push((name.to_string(), value));
+                rest = &rest[end + 1..];
+                continue;
+            }
+            let end = rest.find(['.', '#', '[']).unwrap_or(rest.len());
+            let value = rest[..end].to_string();
+            if !is_name(&value) {
+                return None;
+            }
+            match kind {
+                '.' => compound.classes.push(value),
+                '#' => compound.id = Some(value),
+                _ => return None,
+            }
+            rest = &rest[end..];
+        }
+        Some(compound)
+    }
+
+    fn matches(&self, node: &Node) -> bool {
+        node.name().is_some()
+            && self
+                .name
+                .as_deref()
+                .is_none_or(|name| Name(name).matches(node))
+            && self
+                .id
+                .as_deref()
+                .is_none_or(|id| Attr("id", id).matches(node))
+            && self
+                .classes
+                .iter()
+                .all(|class| Class(class.as_str()).matches(node))
+            && self.attrs.iter().all(|(name, value)| match value {
+                Some(value) => Attr(name.as_str(), value.as_str()).matches(node),
+                None => Attr(name.as_str(), ()).matches(node),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selectors() {
+        let html = r#"
+            <div id="main" class="page wide">
+                <ul class="nav"><li><a href="/a" data-x="1">A</a></li></ul>
+                <p>Intro <a href="/b">B</a></p>
+            </div>
+            <a href="/c">C</a>
+        "#;
+        let document = Document::from(html);
+        let hrefs = |selector: &str| -> Vec<&str> {
+            let selector = Selector::parse(selector).unwrap();
+            document
+                .find(|node: &Node| selector.matches(node))
+                .filter_map(|node| node.attr("href"))
+                .collect()
+        };
+        assert_eq!(hrefs("a"), vec!["/a", "/b", "/c"]);
+        assert_eq!(hrefs("#main a"), vec!["/a", "/b"]);
+        assert_eq!(hrefs("div.page.wide > p > a"), vec!["/b"]);
+        assert_eq!(hrefs("ul.nav a[data-x='1'], body > a"), vec!["/a", "/c"]);
+        assert_eq!(hrefs("*[href=\"/c\"]"), vec!["/c"]);
+        assert!(Selector::parse("div >").is_err());
+        assert!(Selector::parse("a[href").is_err());
+    }
+
+    #[test]
+    fn test_quoted_attribute_values() {
+        let document =
+            Document::from(r#"<a title="x, y" href="/a">A</a><a title="a b]" href="/b">B</a>"#);
+        let hrefs = |selector: &str| -> Vec<&str> {
+            let selector = Selector::parse(selector).unwrap();
+            document
+                .find(|node: &Node| selector.matches(node))
+                .filter_map(|node| node.attr("href"))
+                .collect()
+        };
+        assert_eq!(hrefs(r#"a[title="x, y"]"#), vec!["/a"]);
+        assert_eq!(hrefs("a[title='a b]']"), vec!["/b"]);
+        assert_eq!(
+            hrefs(r#"[title="x, y"], a[title='a b]']"#),
+            vec!["/a", "/b"]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_selectors() {
+        for selector in [
+            "a:first-child",
+            "a::before",
+            "li:not(.nav)",
+            r#"a[href^="/x"]"#,
+            r#"a[href$=".pdf"]"#,
+            "a[href*=x]",
+            "a[class~=x]",
+            "a[lang|=en]",
+            r#"a[href="/x" i]"#,
+            "a[href=/x]",
+            "h1 + p",
+            "h1 ~ p",
+            "h1+p",
+            "> a",
+            "div > > a",
+            "a,,b",
+            "a,",
+            "",
+            r#"a[title="x]"#,
+        ] {
+            assert!(Selector::parse(selector).is_err(), "{selector:?} parsed");
+        }
+    }
+
+    #[test]
+    fn test_extract_fields() {
+        let rules: Vec<ExtractionRule> = serde_json::from_str(
+            r##"[
+                {
+                    "url": "/products/",
+                    "fields": {
+                        "price": {"selector": ".price", "pattern": "([0-9.]+)"},
+                        "images": {"selector": "img", "take": {"attr": "src"}, "multiple": true},
+                        "summary": {"selector": "#summary", "take": "html"},
+                        "missing": {"selector": ".nothing"}
+                    }
+                },
+                {"url": "/blog/", "fields": {"author": {"selector": ".author"}}}
+            ]"##,
+        )
+        .unwrap();
+        let rules = ExtractionRules::new(&rules).unwrap();
+        let document = Document::from(
+            r#"<span class="price">Now  $19.99</span>
+               <img src="/1.png"><img src="/2.png">
+               <div id="summary"><b>Great</b></div>
+               <span class="author">Ada</span>"#,
+        );
+
+        let fields = rules.extract("https://example.com/products/kettle", &document);
+        assert_eq!(
+            fields,
+            BTreeMap::from([
+                ("price".to_string(), FieldValue::One("19.99".to_string())),
+                (
+                    "images".to_string(),
+                    FieldValue::Many(vec!["/1.png".to_string(), "/2.png".to_string()])
+                ),
+                (
+                    "summary".to_string(),
+                    FieldValue::One("<b>Great</b>".to_string())
+                ),
+            ])
+        );
+        assert!(rules
+            .extract("https://example.com/about", &document)
+            .is_empty());
+    }
+}
@@ -0,0 +1,391 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use redb::{Database, ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+
+use crate::error::CrawlerError;
+use crate::priority::Priority;
+use crate::seen::{BloomFilter, SeenSet};
+use crate::task::Task;
+
+/// Queued tasks, keyed by host, priority and insertion order so that a range
+/// scan over one host yields its tasks best-first.
+const TASKS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("frontier_tasks");
+/// Queued URL to its key in `TASKS`, for deduplication and priority raises.
+const INDEX: TableDefinition<&str, &[u8]> = TableDefinition::new("frontier_index");
+const VISITED: TableDefinition<&str, ()> = TableDefinition::new("visited");
+
+/// URLs the index filter is first sized for; it grows as the queue does.
+const INDEX_FILTER_CAPACITY: usize = 100_000;
+const INDEX_FILTER_FALSE_POSITIVES: f64 = 0.01;
+
+pub(crate) type QueueKey = (Reverse<Priority>, u64);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiskFrontierConfig {
+    /// Database file; reopening it resumes the queued and visited URLs.
+    pub path: String,
+    /// Tasks per host kept in memory; the rest are only read back from disk
+    /// once the host's in-memory segment runs dry.
+    pub hot_per_host: usize,
+    /// Writes buffered before they are committed in one transaction. A crash
+    /// loses at most this many updates.
+    pub flush_every: usize,
+}
+
+impl Default for DiskFrontierConfig {
+    fn default() -> Self {
+        Self {
+            path: "Freyr/frontier.redb".to_string(),
+            hot_per_host: 1000,
+            flush_every: 10_000,
+        }
+    }
+}
+
+fn store_error(e: impl Into<redb::Error>) -> CrawlerError {
+    CrawlerError::StoreError(e.into().to_string())
+}
+
+/// Maps an `f64` to a `u64` with the same order as `f64::total_cmp`.
+fn sortable_bits(value: f64) -> u64 {
+    let bits = value.to_bits();
+    if bits >> 63 == 1 {
+        !bits
+    } else {
+        bits | 1 << 63
+    }
+}
+
+fn from_sortable_bits(bits: u64) -> f64 {
+    if bits >> 63 == 1 {
+        f64::from_bits(bits & !(1 << 63))
+    } else {
+        f64::from_bits(!bits)
+    }
+}
+
+fn host_prefix(host: &str) -> Vec<u8> {
+    let mut prefix = host.as_bytes().to_vec();
+    prefix.push(0);
+    prefix
+}
+
+fn encode_key(host: &str, (Reverse(priority), sequence): QueueKey) -> Vec<u8> {
+    let mut key = host_prefix(host);
+    key.extend_from_slice(&(!sortable_bits(priority.0)).to_be_bytes());
+    key.extend_from_slice(&sequence.to_be_bytes());
+    key
+}
+
+fn decode_key(key: &[u8]) -> Option<(String, QueueKey)> {
+    let split = key.iter().position(|&byte| byte == 0)?;
+    let host = std::str::from_utf8(&key[..split]).ok()?.to_string();
+    let rest = &key[split + 1..];
+    let priority = u64::from_be_bytes(rest.get(..8)?.try_into().ok()?);
+    let sequence = u64::from_be_bytes(rest.get(8..16)?.try_into().ok()?);
+    let priority = Priority(from_sortable_bits(!priority));
+    Some((host, (Reverse(priority), sequence)))
+}
+
+/// Embedded on-disk store backing a `Frontier` too large for memory. Every
+/// queued task goes to the store, so reopening the file after a restart
+/// recovers the whole queue along with the visited set.
+///
+/// Writes are buffered and committed in batches of `flush_every`, which keeps
+/// the per-URL cost close to the in-memory frontier. Reads consult the buffer
+/// first, so callers always see their own writes, and a Bloom filter of every
+/// URL ever indexed answers most lookups for unqueued URLs without a read
+/// transaction.
+pub struct DiskStore {
+    db: Database,
+    flush_every: usize,
+    /// Buffered task writes; `None` marks a deletion.
+    tasks: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    index: HashMap<String, Option<Vec<u8>>>,
+    /// May contain URLs that have since left the index, never misses one
+    /// that is in it.
+    indexed: BloomFilter,
+    visited: HashSet<String>,
+    pending: usize,
+}
+
+impl std::fmt::Debug for DiskStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiskStore")
+            .field("flush_every", &self.flush_every)
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+fn encode_task(task: &Task) -> Result<Vec<u8>, CrawlerError> {
+    serde_json::to_vec(task).map_err(|e| CrawlerError::StoreError(e.to_string()))
+}
+
+fn decode_task(value: &[u8]) -> Result<Task, CrawlerError> {
+    serde_json::from_slice(value).map_err(|e| CrawlerError::StoreError(e.to_string()))
+}
+
+impl DiskStore {
+    pub fn open(config: &DiskFrontierConfig) -> Result<Self, CrawlerError> {
+        if let Some(dir) = Path::new(&config.path).parent() {
+            std::fs::create_dir_all(dir).map_err(CrawlerError::IoError)?;
+        }
+        let db = Database::create(&config.path).map_err(store_error)?;
+        let txn = db.begin_write().map_err(store_error)?;
+        txn.open_table(TASKS).map_err(store_error)?;
+        txn.open_table(INDEX).map_err(store_error)?;
+        txn.open_table(VISITED).map_err(store_error)?;
+        txn.commit().map_err(store_error)?;
+
+        let indexed = BloomFilter::new(INDEX_FILTER_CAPACITY, INDEX_FILTER_FALSE_POSITIVES);
+        let txn = db.begin_read().map_err(store_error)?;
+        let index = txn.open_table(INDEX).map_err(store_error)?;
+        for entry in index.iter().map_err(store_error)? {
+            let (url, _) = entry.map_err(store_error)?;
+            indexed.insert(url.value());
+        }
+        drop(index);
+        drop(txn);
+        Ok(Self {
+            db,
+            flush_every: config.flush_every.max(1),
+            tasks: BTreeMap::new(),
+            index: HashMap::new(),
+            indexed,
+            visited: HashSet::new(),
+            pending: 0,
+        })
+    }
+
+    fn written(&mut self) -> Result<(), CrawlerError> {
+        self.pending += 1;
+        if self.pending >= self.flush_every {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Commits every buffered write in one durable transaction.
+    pub fn flush(&mut self) -> Result<(), CrawlerError> {
+        if self.pending == 0 {
+            return Ok(());
+        }
+        let txn = self.db.begin_write().map_err(store_error)?;
+        {
+            let mut tasks = txn.open_table(TASKS).map_err(store_error)?;
+            for (key, value) in &self.tasks {
+                match value {
+                    Some(value) => tasks.insert(key.as_slice(), value.as_slice()),
+                    None => tasks.remove(key.as_slice()),
+                }
+                .map_err(store_error)?;
+            }
+            let mut index = txn.open_table(INDEX).map_err(store_error)?;
+            for (url, key) in &self.index {
+                match key {
+                    Some(key) => index.insert(url.as_str(), key.as_slice()),
+                    None => index.remove(url.as_str()),
+                }
+                .map_err(store_error)?;
+            }
+            let mut visited = txn.open_table(VISITED).map_err(store_error)?;
+            for url in &self.visited {
+                visited.insert(url.as_str(), ()).map_err(store_error)?;
+            }
+        }
+        txn.commit().map_err(store_error)?;
+        self.tasks.clear();
+        self.index.clear();
+        self.visited.clear();
+        self.pending = 0;
+        Ok(())
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        host: &str,
+        key: QueueKey,
+        task: &Task,
+    ) -> Result<(), CrawlerError> {
+        let key = encode_key(host, key);
+        self.tasks.insert(key.clone(), Some(encode_task(task)?));
+        self.indexed.insert(task.url());
+        self.index.insert(task.url().to_string(), Some(key));
+        self.written()
+    }
+
+    /// Deletes a task the caller already holds.
+    pub(crate) fn delete(
+        &mut self,
+        host: &str,
+        key: QueueKey,
+        url: &str,
+    ) -> Result<(), CrawlerError> {
+        self.tasks.insert(encode_key(host, key), None);
+        self.index.insert(url.to_string(), None);
+        self.written()
+    }
+
+    /// Deletes a task and returns it.
+    pub(crate) fn remove(
+        &mut self,
+        host: &str,
+        key: QueueKey,
+    ) -> Result<Option<Task>, CrawlerError> {
+        let encoded = encode_key(host, key);
+        let value = match self.tasks.get(&encoded) {
+            Some(value) => value.clone(),
+            None => {
+                let txn = self.db.begin_read().map_err(store_error)?;
+                let tasks = txn.open_table(TASKS).map_err(store_error)?;
+                let value = tasks.get(encoded.as_slice()).map_err(store_error)?;
+                value.map(|value| value.value().to_vec())
+            }
+        };
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let task = decode_task(&value)?;
+        self.delete(host, key, task.url())?;
+        Ok(Some(task))
+    }
+
+    /// Host and key of a queued URL.
+    pub(crate) fn lookup(&self, url: &str) -> Result<Option<(String, QueueKey)>, CrawlerError> {
+        if let Some(key) = self.index.get(url) {
+            return Ok(key.as_deref().and_then(decode_key));
+        }
+        if !self.indexed.contains(url) {
+            return Ok(None);
+        }
+        let txn = self.db.begin_read().map_err(store_error)?;
+        let index = txn.open_table(INDEX).map_err(store_error)?;
+        Ok(index
+            .get(url)
+            .map_err(store_error)?
+            .and_then(|key| decode_key(key.value())))
+    }
+
+    /// Up to `limit` of `host`'s tasks, best first, including buffered writes.
+    pub(crate) fn load_host(
+        &self,
+        host: &str,
+        limit: usize,
+    ) -> Result<Vec<(QueueKey, Task)>, CrawlerError> {
+        let start = host_prefix(host);
+        let mut end = host.as_bytes().to_vec();
+        end.push(1);
+
+        let mut merged: BTreeMap<Vec<u8>, Option<Vec<u8>>> = BTreeMap::new();
+        let mut deleted = 0;
+        for (key, value) in self.tasks.range(start.clone()..end.clone()) {
+            deleted += usize::from(value.is_none());
+            merged.insert(key.clone(), value.clone());
+        }
+        // Buffered deletions can hide at most `deleted` of the stored tasks.
+        let txn = self.db.begin_read().map_err(store_error)?;
+        let tasks = txn.open_table(TASKS).map_err(store_error)?;
+        for entry in tasks
+            .range(start.as_slice()..end.as_slice())
+            .map_err(store_error)?
+            .take(limit.saturating_add(deleted))
+        {
+            let (key, value) = entry.map_err(store_error)?;
+            merged
+                .entry(key.value().to_vec())
+                .or_insert_with(|| Some(value.value().to_vec()));
+        }
+
+        let mut loaded = Vec::new();
+        for (key, value) in merged {
+            if loaded.len() >= limit {
+                break;
+            }
+            let (Some((_, key)), Some(value)) = (decode_key(&key), value) else {
+                continue;
+            };
+            loaded.push((key, decode_task(&value)?));
+        }
+        Ok(loaded)
+    }
+
+    /// Number of queued tasks per host and the highest sequence number in
+    /// use, for rebuilding the scheduler after a restart.
+    pub(crate) fn host_counts(&mut self) -> Result<(HashMap<String, usize>, u64), CrawlerError> {
+        self.flush()?;
+        let txn = self.db.begin_read().map_err(store_error)?;
+        let tasks = txn.open_table(TASKS).map_err(store_error)?;
+        let mut counts = HashMap::new();
+        let mut sequence = 0;
+        for entry in tasks.iter().map_err(store_error)? {
+            let (key, _) = entry.map_err(store_error)?;
+            if let Some((host, (_, seq))) = decode_key(key.value()) {
+                *counts.entry(host).or_default() += 1;
+                sequence = sequence.max(seq);
+            }
+        }
+        Ok((counts, sequence))
+    }
+
+    pub fn mark_visited(&mut self, url: &str) -> Result<(), CrawlerError> {
+        self.visited.insert(url.to_string());
+        self.written()
+    }
+
+    pub fn is_visited(&self, url: &str) -> Result<bool, CrawlerError> {
+        if self.visited.contains(url) {
+            return Ok(true);
+        }
+        let txn = self.db.begin_read().map_err(store_error)?;
+        let visited = txn.open_table(VISITED).map_err(store_error)?;
+        Ok(visited.get(url).map_err(store_error)?.is_some())
+    }
+
+    pub fn visited(&mut self) -> Result<Vec<String>, CrawlerError> {
+        self.flush()?;
+        let txn = self.db.begin_read().map_err(store_error)?;
+        let visited = txn.open_table(VISITED).map_err(store_error)?;
+        let mut urls = Vec::new();
+        for entry in visited.iter().map_err(store_error)? {
+            let (url, _) = entry.map_err(store_error)?;
+            urls.push(url.value().to_string());
+        }
+        Ok(urls)
+    }
+}
+
+impl Drop for DiskStore {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            tracing::error!("Failed to flush frontier store: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_order() {
+        let keys: Vec<Vec<u8>> = [(5.0, 2), (5.0, 3), (1.0, 1), (-1.0, 0)]
+            .into_iter()
+            .map(|(priority, sequence)| {
+                encode_key("example.com", (Reverse(Priority(priority)), sequence))
+            })
+            .collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+
+        assert_eq!(
+            decode_key(&keys[3]),
+            Some(("example.com".to_string(), (Reverse(Priority(-1.0)), 0)))
+        );
+    }
+}
@@ -0,0 +1,834 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::error::CrawlerError;
+use crate::modules::storage::disk::{DiskFrontierConfig, DiskStore, QueueKey};
+use crate::priority::Priority;
+use crate::task::Task;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrontierConfig {
+    /// Minimum time between two fetches from the same host.
+    pub default_delay_ms: u64,
+    /// Per-host overrides of `default_delay_ms`.
+    pub host_delays_ms: HashMap<String, u64>,
+    /// Spill the queue and visited set to an on-disk store instead of
+    /// keeping them in memory.
+    pub disk: Option<DiskFrontierConfig>,
+}
+
+impl Default for FrontierConfig {
+    fn default() -> Self {
+        Self {
+            default_delay_ms: 1000,
+            host_delays_ms: HashMap::new(),
+            disk: None,
+        }
+    }
+}
+
+/// Result of asking the frontier for work.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrontierPoll {
+    /// A task whose host may be fetched right now.
+    Ready(Task),
+    /// Tasks are queued, but no host is due before this instant.
+    Wait(Instant),
+    Empty,
+}
+
+/// What `Frontier::poll` would return, without taking anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FrontierHead {
+    /// Priority and scheduling sequence of the best due host. Across
+    /// frontiers sharing a sequence, the largest head is the one a single
+    /// frontier would have polled.
+    Due(Priority, Reverse<u64>),
+    Wait(Instant),
+    Empty,
+}
+
+/// Where a host currently sits in the scheduler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    /// No queued tasks.
+    Idle,
+    /// Queued tasks, but still inside the politeness delay.
+    Waiting,
+    /// Due for a fetch; the number is the sequence of its live `due` entry.
+    Due(u64),
+}
+
+#[derive(Debug)]
+struct HostQueue {
+    /// In-memory head of the host's queue. With a disk store this is a
+    /// prefix of the host's tasks in priority order; the rest are `cold`.
+    tasks: BTreeMap<QueueKey, Task>,
+    keys: HashMap<String, QueueKey>,
+    /// Tasks that only live in the disk store.
+    cold: usize,
+    next_fetch: Instant,
+    delay: Duration,
+    slot: Slot,
+}
+
+impl HostQueue {
+    fn new(delay: Duration) -> Self {
+        Self {
+            tasks: BTreeMap::new(),
+            keys: HashMap::new(),
+            cold: 0,
+            next_fetch: Instant::now(),
+            delay,
+            slot: Slot::Idle,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.tasks.len() + self.cold
+    }
+
+    fn head_priority(&self) -> Option<Priority> {
+        self.tasks
+            .keys()
+            .next()
+            .map(|(Reverse(priority), _)| *priority)
+    }
+}
+
+/// Mercator-style frontier. Each host has its own "back queue", ordered by
+/// task priority and FIFO among equal priorities. A waiting heap holds hosts
+/// until their politeness delay has passed; due hosts then compete on the
+/// priority of their best task. A single large site cannot starve the others,
+/// and no host is hit faster than its delay.
+///
+/// With `FrontierConfig::disk` set, tasks are written through to a
+/// `DiskStore` and each host keeps only its best `hot_per_host` tasks in
+/// memory, so memory grows with the number of hosts rather than URLs.
+#[derive(Debug)]
+pub struct Frontier {
+    config: FrontierConfig,
+    hosts: HashMap<String, HostQueue>,
+    waiting: BinaryHeap<Reverse<(Instant, u64, String)>>,
+    due: BinaryHeap<(Priority, Reverse<u64>, String)>,
+    /// Queued URL to host. Only used without a disk store, which keeps its
+    /// own index.
+    queued: HashMap<String, String>,
+    store: Option<DiskStore>,
+    len: usize,
+    /// Shared by the shards of a `ShardedFrontier`, so that equal priorities
+    /// stay first-come first-served across them.
+    sequence: Arc<AtomicU64>,
+}
+
+pub(crate) fn host_of(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+impl Default for Frontier {
+    fn default() -> Self {
+        Self::new(FrontierConfig::default())
+    }
+}
+
+impl Frontier {
+    pub fn new(config: FrontierConfig) -> Self {
+        Self {
+            config,
+            hosts: HashMap::new(),
+            waiting: BinaryHeap::new(),
+            due: BinaryHeap::new(),
+            queued: HashMap::new(),
+            store: None,
+            len: 0,
+            sequence: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Creates a frontier from `config`, opening its disk store if one is
+    /// configured and resuming any tasks left in it by a previous run.
+    pub fn open(config: FrontierConfig) -> Result<Self, CrawlerError> {
+        let Some(disk) = config.disk.clone() else {
+            return Ok(Self::new(config));
+        };
+        let mut store = DiskStore::open(&disk)?;
+        let (counts, sequence) = store.host_counts()?;
+        let mut frontier = Self::new(config);
+        frontier.store = Some(store);
+        frontier.sequence.store(sequence, Ordering::Relaxed);
+        for (host, count) in counts {
+            let mut queue = HostQueue::new(frontier.delay_for(&host));
+            queue.cold = count;
+            frontier.len += count;
+            frontier.hosts.insert(host.clone(), queue);
+            frontier.refill(&host)?;
+            frontier.schedule(&host);
+        }
+        if frontier.len > 0 {
+            tracing::info!("Resumed {} queued URLs from {}", frontier.len, disk.path);
+        }
+        Ok(frontier)
+    }
+
+    pub fn config(&self) -> &FrontierConfig {
+        &self.config
+    }
+
+    /// The disk store backing this frontier, if any.
+    pub fn store(&mut self) -> Option<&mut DiskStore> {
+        self.store.as_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Host and queue key of a queued URL.
+    fn locate(&self, url: &str) -> Result<Option<(String, QueueKey)>, CrawlerError> {
+        if let Some(store) = &self.store {
+            return store.lookup(url);
+        }
+        Ok(self.queued.get(url).and_then(|host| {
+            let key = self.hosts.get(host)?.keys.get(url)?;
+            Some((host.clone(), *key))
+        }))
+    }
+
+    pub fn contains(&self, url: &str) -> Result<bool, CrawlerError> {
+        Ok(self.locate(url)?.is_some())
+    }
+
+    /// Current priority of a queued URL.
+    pub fn priority_of(&self, url: &str) -> Result<Option<Priority>, CrawlerError> {
+        Ok(self
+            .locate(url)?
+            .map(|(_, (Reverse(priority), _))| priority))
+    }
+
+    fn delay_for(&self, host: &str) -> Duration {
+        let millis = self
+            .config
+            .host_delays_ms
+            .get(host)
+            .copied()
+            .unwrap_or(self.config.default_delay_ms);
+        Duration::from_millis(millis)
+    }
+
+    /// Applies new politeness settings. Hosts keep their queues and their
+    /// next allowed fetch time; only future delays change.
+    pub fn set_config(&mut self, config: FrontierConfig) {
+        self.config = config;
+        let delays: Vec<(String, Duration)> = self
+            .hosts
+            .keys()
+            .map(|host| (host.clone(), self.delay_for(host)))
+            .collect();
+        for (host, delay) in delays {
+            if let Some(queue) = self.hosts.get_mut(&host) {
+                queue.delay = delay;
+            }
+        }
+    }
+
+    /// Overrides the politeness delay for `host`, e.g. from a robots.txt
+    /// `Crawl-delay`.
+    pub fn set_host_delay(&mut self, host: &str, delay: Duration) {
+        self.config
+            .host_delays_ms
+            .insert(host.to_string(), delay.as_millis() as u64);
+        if let Some(queue) = self.hosts.get_mut(host) {
+            queue.delay = delay;
+        }
+    }
+
+    fn next_sequence(&mut self) -> u64 {
+        self.sequence.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Makes this frontier draw sequence numbers from `sequence`, which is
+    /// moved past any this frontier already used.
+    pub(crate) fn share_sequence(&mut self, sequence: Arc<AtomicU64>) {
+        sequence.fetch_max(self.sequence.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.sequence = sequence;
+    }
+
+    /// Puts an idle host with work on the waiting heap, or refreshes the
+    /// `due` entry of a due host whose best task may have changed.
+    fn schedule(&mut self, host: &str) {
+        let sequence = self.next_sequence();
+        let Some(queue) = self.hosts.get_mut(host) else {
+            return;
+        };
+        let Some(head) = queue.head_priority() else {
+            queue.slot = Slot::Idle;
+            return;
+        };
+        match queue.slot {
+            Slot::Idle => {
+                queue.slot = Slot::Waiting;
+                self.waiting
+                    .push(Reverse((queue.next_fetch, sequence, host.to_string())));
+            }
+            Slot::Waiting => {}
+            Slot::Due(_) => {
+                queue.slot = Slot::Due(sequence);
+                self.due.push((head, Reverse(sequence), host.to_string()));
+            }
+        }
+    }
+
+    /// Adds `task` to its host's queue. If the URL is already queued with a
+    /// lower priority, its priority is raised instead. Returns whether the
+    /// URL was newly queued.
+    pub fn push(&mut self, task: Task) -> Result<bool, CrawlerError> {
+        let url = task.url().to_string();
+        if let Some((host, key)) = self.locate(&url)? {
+            self.raise(&host, key, task.priority())?;
+            return Ok(false);
+        }
+        let host = host_of(&url);
+        let sequence = self.next_sequence();
+        let key = (Reverse(task.priority()), sequence);
+        if let Some(store) = &mut self.store {
+            store.insert(&host, key, &task)?;
+        } else {
+            self.queued.insert(url, host.clone());
+        }
+        self.len += 1;
+        self.place(&host, key, task);
+        self.schedule(&host);
+        Ok(true)
+    }
+
+    /// Puts a task that is already in the store (if any) into its host's
+    /// in-memory segment, or counts it as cold if it ranks below it.
+    fn place(&mut self, host: &str, key: QueueKey, task: Task) {
+        let hot_limit = match &self.config.disk {
+            Some(disk) if self.store.is_some() => disk.hot_per_host.max(1),
+            _ => usize::MAX,
+        };
+        let delay = self.delay_for(host);
+        let queue = self
+            .hosts
+            .entry(host.to_string())
+            .or_insert_with(|| HostQueue::new(delay));
+        let fits = queue.cold == 0 && queue.tasks.len() < hot_limit;
+        let outranks = queue
+            .tasks
+            .keys()
+            .next_back()
+            .is_some_and(|last| key < *last);
+        if !fits && !outranks {
+            queue.cold += 1;
+            return;
+        }
+        queue.keys.insert(task.url().to_string(), key);
+        queue.tasks.insert(key, task);
+        if queue.tasks.len() > hot_limit {
+            if let Some((_, evicted)) = queue.tasks.pop_last() {
+                queue.keys.remove(evicted.url());
+                queue.cold += 1;
+            }
+        }
+    }
+
+    /// Loads the best cold tasks of a host whose in-memory segment is empty.
+    fn refill(&mut self, host: &str) -> Result<(), CrawlerError> {
+        let (Some(store), Some(disk)) = (&self.store, &self.config.disk) else {
+            return Ok(());
+        };
+        let Some(queue) = self.hosts.get_mut(host) else {
+            return Ok(());
+        };
+        if !queue.tasks.is_empty() || queue.cold == 0 {
+            return Ok(());
+        }
+        for (key, task) in store.load_host(host, disk.hot_per_host.max(1))? {
+            queue.keys.insert(task.url().to_string(), key);
+            queue.tasks.insert(key, task);
+        }
+        queue.cold = queue.cold.saturating_sub(queue.tasks.len());
+        Ok(())
+    }
+
+    fn raise(&mut self, host: &str, key: QueueKey, priority: Priority) -> Result<(), CrawlerError> {
+        let (Reverse(current), sequence) = key;
+        if priority <= current {
+            return Ok(());
+        }
+        let raised = (Reverse(priority), sequence);
+        let Some(queue) = self.hosts.get_mut(host) else {
+            return Ok(());
+        };
+        let hot = match queue.tasks.remove(&key) {
+            Some(hot) => {
+                queue.keys.remove(hot.url());
+                Some(hot)
+            }
+            None => {
+                queue.cold = queue.cold.saturating_sub(1);
+                None
+            }
+        };
+        let stored = match &mut self.store {
+            Some(store) => store.remove(host, key)?,
+            None => None,
+        };
+        let Some(queued) = hot.or(stored) else {
+            return Ok(());
+        };
+        let queued = queued.with_priority(priority.0);
+        if let Some(store) = &mut self.store {
+            store.insert(host, raised, &queued)?;
+        }
+        self.place(host, raised, queued);
+        self.refill(host)?;
+        self.schedule(host);
+        Ok(())
+    }
+
+    /// Moves hosts whose politeness delay has passed from `waiting` to `due`.
+    fn promote(&mut self, now: Instant) {
+        while let Some(Reverse((at, _, _))) = self.waiting.peek() {
+            if *at > now {
+                break;
+            }
+            // Keeping the sequence from when the host started waiting makes
+            // equal priorities first come, first served.
+            let Some(Reverse((_, sequence, host))) = self.waiting.pop() else {
+                break;
+            };
+            if let Some(queue) = self.hosts.get_mut(&host) {
+                if let (Slot::Waiting, Some(head)) = (queue.slot, queue.head_priority()) {
+                    queue.slot = Slot::Due(sequence);
+                    self.due.push((head, Reverse(sequence), host));
+                }
+            }
+        }
+    }
+
+    /// Promotes due hosts and reports what `poll` would return.
+    pub(crate) fn head(&mut self, now: Instant) -> FrontierHead {
+        self.promote(now);
+        while let Some((priority, Reverse(sequence), host)) = self.due.peek() {
+            if let Some(queue) = self.hosts.get_mut(host) {
+                if queue.slot == Slot::Due(*sequence) {
+                    if !queue.tasks.is_empty() {
+                        return FrontierHead::Due(*priority, Reverse(*sequence));
+                    }
+                    queue.slot = Slot::Idle;
+                }
+            }
+            // Skipped by `poll` as well.
+            self.due.pop();
+        }
+        match self.waiting.peek() {
+            Some(Reverse((at, _, _))) => FrontierHead::Wait(*at),
+            None => FrontierHead::Empty,
+        }
+    }
+
+    /// Takes the highest-priority task among hosts that are due for a polite
+    /// fetch, charging that host its delay.
+    pub fn poll(&mut self, now: Instant) -> Result<FrontierPoll, CrawlerError> {
+        self.promote(now);
+        while let Some((_, Reverse(sequence), host)) = self.due.pop() {
+            let Some(queue) = self.hosts.get_mut(&host) else {
+                continue;
+            };
+            if queue.slot != Slot::Due(sequence) {
+                continue;
+            }
+            let Some((key, task)) = queue.tasks.pop_first() else {
+                queue.slot = Slot::Idle;
+                continue;
+            };
+            queue.keys.remove(task.url());
+            queue.next_fetch = now + queue.delay;
+            queue.slot = Slot::Idle;
+            self.len -= 1;
+            match &mut self.store {
+                Some(store) => store.delete(&host, key, task.url())?,
+                None => {
+                    self.queued.remove(task.url());
+                }
+            }
+            self.refill(&host)?;
+            self.schedule(&host);
+            return Ok(FrontierPoll::Ready(task));
+        }
+        Ok(match self.waiting.peek() {
+            Some(Reverse((at, _, _))) => FrontierPoll::Wait(*at),
+            None => FrontierPoll::Empty,
+        })
+    }
+
+    /// Copies of every queued task, or `None` if the queue lives in a disk
+    /// store, which persists on its own.
+    pub fn snapshot(&self) -> Option<Vec<Task>> {
+        if self.store.is_some() {
+            return None;
+        }
+        let mut hosts: Vec<&String> = self.hosts.keys().collect();
+        hosts.sort();
+        Some(
+            hosts
+                .into_iter()
+                .flat_map(|host| self.hosts[host].tasks.values().cloned())
+                .collect(),
+        )
+    }
+
+    /// Removes and returns every queued task, including those on disk.
+    pub fn drain(&mut self) -> Result<Vec<Task>, CrawlerError> {
+        let mut tasks = Vec::with_capacity(self.len);
+        let hosts: Vec<String> = self.hosts.keys().cloned().collect();
+        for host in hosts {
+            loop {
+                self.refill(&host)?;
+                let Some(queue) = self.hosts.get_mut(&host) else {
+                    break;
+                };
+                let Some((key, task)) = queue.tasks.pop_first() else {
+                    break;
+                };
+                queue.keys.remove(task.url());
+                if let Some(store) = &mut self.store {
+                    store.delete(&host, key, task.url())?;
+                }
+                tasks.push(task);
+            }
+        }
+        self.hosts.clear();
+        self.waiting.clear();
+        self.due.clear();
+        self.queued.clear();
+        self.len = 0;
+        Ok(tasks)
+    }
+
+    /// Number of queued URLs per host, deepest first.
+    pub fn queue_depths(&self) -> Vec<(String, usize)> {
+        let mut depths: Vec<(String, usize)> = self
+            .hosts
+            .iter()
+            .filter(|(_, queue)| queue.len() > 0)
+            .map(|(host, queue)| (host.clone(), queue.len()))
+            .collect();
+        depths.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        depths
+    }
+
+    /// Queued URLs, grouped by host in priority order.
+    pub fn urls(&self) -> Result<Vec<String>, CrawlerError> {
+        let mut hosts: Vec<&String> = self.hosts.keys().collect();
+        hosts.sort();
+        let mut urls = Vec::with_capacity(self.len);
+        for host in hosts {
+            match &self.store {
+                Some(store) => urls.extend(
+                    store
+                        .load_host(host, usize::MAX)?
+                        .into_iter()
+                        .map(|(_, task)| task.url().to_string()),
+                ),
+                None => urls.extend(
+                    self.hosts[host]
+                        .tasks
+                        .values()
+                        .map(|task| task.url().to_string()),
+                ),
+            }
+        }
+        Ok(urls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ready_url(poll: FrontierPoll) -> String {
+        match poll {
+            FrontierPoll::Ready(task) => task.url().to_string(),
+            other => panic!("expected a ready task, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_hosts_interleave() {
+        let mut frontier = Frontier::new(FrontierConfig {
+            default_delay_ms: 0,
+            ..Default::default()
+        });
+        for i in 0..3 {
+            frontier
+                .push(Task::new(format!("https://big.example.com/{i}")))
+                .unwrap();
+        }
+        frontier
+            .push(Task::new("https://small.example.org/".to_string()))
+            .unwrap();
+        assert!(!frontier
+            .push(Task::new("https://small.example.org/".to_string()))
+            .unwrap());
+        assert_eq!(
+            frontier.queue_depths(),
+            vec![
+                ("big.example.com".to_string(), 3),
+                ("small.example.org".to_string(), 1)
+            ]
+        );
+
+        let now = Instant::now();
+        let first = ready_url(frontier.poll(now).unwrap());
+        let second = ready_url(frontier.poll(now).unwrap());
+        assert_eq!(first, "https://big.example.com/0");
+        assert_eq!(second, "https://small.example.org/");
+        assert_eq!(
+            ready_url(frontier.poll(now).unwrap()),
+            "https://big.example.com/1"
+        );
+        assert_eq!(frontier.len(), 1);
+    }
+
+    #[test]
+    fn test_politeness_delay() {
+        let mut frontier = Frontier::new(FrontierConfig {
+            default_delay_ms: 1000,
+            ..Default::default()
+        });
+        frontier
+            .push(Task::new("https://example.com/a".to_string()))
+            .unwrap();
+        frontier
+            .push(Task::new("https://example.com/b".to_string()))
+            .unwrap();
+
+        let now = Instant::now();
+        assert_eq!(
+            ready_url(frontier.poll(now).unwrap()),
+            "https://example.com/a"
+        );
+        match frontier.poll(now).unwrap() {
+            FrontierPoll::Wait(due) => assert_eq!(due, now + Duration::from_secs(1)),
+            other => panic!("expected to wait, got {:?}", other),
+        }
+        assert_eq!(
+            ready_url(frontier.poll(now + Duration::from_secs(1)).unwrap()),
+            "https://example.com/b"
+        );
+        assert_eq!(frontier.poll(now).unwrap(), FrontierPoll::Empty);
+    }
+
+    #[test]
+    fn test_host_delay_override() {
+        let mut frontier = Frontier::default();
+        frontier.set_host_delay("fast.example.com", Duration::ZERO);
+        frontier
+            .push(Task::new("https://fast.example.com/a".to_string()))
+            .unwrap();
+        frontier
+            .push(Task::new("https://fast.example.com/b".to_string()))
+            .unwrap();
+
+        let now = Instant::now();
+        assert_eq!(
+            ready_url(frontier.poll(now).unwrap()),
+            "https://fast.example.com/a"
+        );
+        assert_eq!(
+            ready_url(frontier.poll(now).unwrap()),
+            "https://fast.example.com/b"
+        );
+    }
+
+    #[test]
+    fn test_priority_order_and_raise() {
+        let mut frontier = Frontier::new(FrontierConfig {
+            default_delay_ms: 0,
+            ..Default::default()
+        });
+        frontier
+            .push(Task::new("https://example.com/low".to_string()).with_priority(1.0))
+            .unwrap();
+        frontier
+            .push(Task::new("https://example.com/high".to_string()).with_priority(5.0))
+            .unwrap();
+        frontier
+            .push(Task::new("https://example.com/mid".to_string()).with_priority(3.0))
+            .unwrap();
+
+        // Rediscovery raises a queued URL but never lowers it.
+        assert!(!frontier
+            .push(Task::new("https://example.com/low".to_string()).with_priority(9.0))
+            .unwrap());
+        assert!(!frontier
+            .push(Task::new("https://example.com/high".to_string()).with_priority(0.0))
+            .unwrap());
+        assert_eq!(
+            frontier.priority_of("https://example.com/low").unwrap(),
+            Some(Priority(9.0))
+        );
+
+        let now = Instant::now();
+        assert_eq!(
+            ready_url(frontier.poll(now).unwrap()),
+            "https://example.com/low"
+        );
+        assert_eq!(
+            ready_url(frontier.poll(now).unwrap()),
+            "https://example.com/high"
+        );
+        assert_eq!(
+            ready_url(frontier.poll(now).unwrap()),
+            "https://example.com/mid"
+        );
+    }
+
+    #[test]
+    fn test_due_hosts_compete_on_priority() {
+        let mut frontier = Frontier::new(FrontierConfig {
+            default_delay_ms: 0,
+            ..Default::default()
+        });
+        frontier
+            .push(Task::new("https://a.example.com/".to_string()).with_priority(1.0))
+            .unwrap();
+        frontier
+            .push(Task::new("https://b.example.com/".to_string()).with_priority(2.0))
+            .unwrap();
+
+        let now = Instant::now();
+        assert_eq!(
+            ready_url(frontier.poll(now).unwrap()),
+            "https://b.example.com/"
+        );
+        assert_eq!(
+            ready_url(frontier.poll(now).unwrap()),
+            "https://a.example.com/"
+        );
+    }
+
+    fn disk_config(name: &str, hot_per_host: usize) -> FrontierConfig {
+        let dir = std::env::temp_dir().join(format!("thamur-frontier-{}", std::process::id()));
+        let path = dir.join(format!("{name}.redb"));
+        let _ = std::fs::remove_file(&path);
+        FrontierConfig {
+            default_delay_ms: 0,
+            disk: Some(DiskFrontierConfig {
+                path: path.to_string_lossy().to_string(),
+                hot_per_host,
+                flush_every: 10,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_disk_spill_keeps_priority_order() {
+        let config = disk_config("spill", 2);
+        let mut frontier = Frontier::open(config.clone()).unwrap();
+        for (i, priority) in [1.0, 4.0, 2.0, 5.0, 3.0].into_iter().enumerate() {
+            let url = format!("https://big.example.com/{i}");
+            frontier
+                .push(Task::new(url).with_priority(priority))
+                .unwrap();
+        }
+        frontier
+            .push(Task::new("https://small.example.org/".to_string()).with_priority(0.0))
+            .unwrap();
+        assert_eq!(frontier.hosts["big.example.com"].tasks.len(), 2);
+        assert_eq!(frontier.len(), 6);
+
+        // A cold URL can still be raised above the in-memory segment.
+        frontier
+            .push(Task::new("https://big.example.com/0".to_string()).with_priority(9.0))
+            .unwrap();
+        assert_eq!(
+            frontier.priority_of("https://big.example.com/0").unwrap(),
+            Some(Priority(9.0))
+        );
+
+        let now = Instant::now();
+        let order: Vec<String> = (0..6)
+            .map(|_| ready_url(frontier.poll(now).unwrap()))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                "https://big.example.com/0",
+                "https://big.example.com/3",
+                "https://big.example.com/1",
+                "https://big.example.com/4",
+                "https://big.example.com/2",
+                "https://small.example.org/",
+            ]
+        );
+        assert_eq!(frontier.poll(now).unwrap(), FrontierPoll::Empty);
+        let _ = std::fs::remove_file(config.disk.unwrap().path);
+    }
+
+    #[test]
+    fn test_disk_frontier_survives_restart() {
+        let config = disk_config("restart", 1);
+        {
+            let mut frontier = Frontier::open(config.clone()).unwrap();
+            frontier
+                .push(Task::with_depth("https://example.com/a".to_string(), 2).with_priority(1.0))
+                .unwrap();
+            frontier
+                .push(Task::new("https://example.com/b".to_string()).with_priority(2.0))
+                .unwrap();
+            frontier
+                .push(Task::new("https://example.org/".to_string()))
+                .unwrap();
+            assert_eq!(
+                ready_url(frontier.poll(Instant::now()).unwrap()),
+                "https://example.com/b"
+            );
+            frontier
+                .store()
+                .unwrap()
+                .mark_visited("https://example.com/b")
+                .unwrap();
+        }
+
+        let mut frontier = Frontier::open(config.clone()).unwrap();
+        assert_eq!(frontier.len(), 2);
+        assert!(frontier.contains("https://example.com/a").unwrap());
+        assert!(!frontier.contains("https://example.com/b").unwrap());
+        assert!(frontier
+            .store()
+            .unwrap()
+            .is_visited("https://example.com/b")
+            .unwrap());
+        assert!(!frontier
+            .push(Task::new("https://example.org/".to_string()))
+            .unwrap());
+
+        let mut tasks = frontier.drain().unwrap();
+        tasks.sort_by(|a, b| a.url().cmp(b.url()));
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].url(), "https://example.com/a");
+        assert_eq!(tasks[0].depth(), 2);
+        assert!(frontier.is_empty());
+        drop(frontier);
+        let _ = std::fs::remove_file(config.disk.unwrap().path);
+    }
+}
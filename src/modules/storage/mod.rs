@@ -1,8 +1,12 @@
+pub mod disk;
+pub mod frontier;
+pub mod sharded;
 pub mod state;
 
 // Module for storing crawled data
+#[allow(clippy::module_inception)]
 pub mod storage {
-    pub fn store_urls(urls: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn store_urls(_urls: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
         // TODO: Implement actual storage logic
         Ok(())
     }
@@ -0,0 +1,313 @@
+use std::path::Path;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::error::CrawlerError;
+use crate::modules::storage::disk::DiskStore;
+use crate::modules::storage::frontier::{
+    host_of, Frontier, FrontierConfig, FrontierHead, FrontierPoll,
+};
+use crate::seen::fingerprint;
+use crate::task::Task;
+
+/// Independently locked frontiers. Fixed, so that a host's tasks in a disk
+/// store are found in the same shard after a restart.
+const SHARDS: usize = 16;
+
+/// A `Frontier` split by host into independently locked shards. Queuing a
+/// URL only locks its host's shard; polling peeks at every shard and takes
+/// the best due host among them, so the order matches a single frontier
+/// apart from races between concurrent pollers.
+///
+/// With a disk store, each shard keeps its own database file, named after
+/// `DiskFrontierConfig::path` with the shard number before the extension.
+#[derive(Debug)]
+pub struct ShardedFrontier {
+    /// The configuration as given; shards get a copy with their own path.
+    config: Mutex<FrontierConfig>,
+    shards: Vec<Mutex<Frontier>>,
+}
+
+fn shard_path(path: &str, shard: usize) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}-{shard}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{shard}"),
+    };
+    path.with_file_name(name).to_string_lossy().to_string()
+}
+
+fn shard_config(config: &FrontierConfig, shard: usize) -> FrontierConfig {
+    let mut config = config.clone();
+    if let Some(disk) = &mut config.disk {
+        disk.path = shard_path(&disk.path, shard);
+    }
+    config
+}
+
+/// Shard holding `host`'s queue. FNV is stable across Rust releases, unlike
+/// the standard hasher.
+fn shard_of(host: &str) -> usize {
+    (fingerprint(host) % SHARDS as u128) as usize
+}
+
+impl Default for ShardedFrontier {
+    fn default() -> Self {
+        Self::new(FrontierConfig::default())
+    }
+}
+
+impl ShardedFrontier {
+    pub fn new(config: FrontierConfig) -> Self {
+        let sequence = Arc::new(AtomicU64::new(0));
+        let shards = (0..SHARDS)
+            .map(|shard| {
+                let mut frontier = Frontier::new(shard_config(&config, shard));
+                frontier.share_sequence(sequence.clone());
+                Mutex::new(frontier)
+            })
+            .collect();
+        Self {
+            config: Mutex::new(config),
+            shards,
+        }
+    }
+
+    /// Like `Frontier::open`, for every shard.
+    pub fn open(config: FrontierConfig) -> Result<Self, CrawlerError> {
+        let sequence = Arc::new(AtomicU64::new(0));
+        let shards = (0..SHARDS)
+            .map(|shard| {
+                let mut frontier = Frontier::open(shard_config(&config, shard))?;
+                frontier.share_sequence(sequence.clone());
+                Ok(Mutex::new(frontier))
+            })
+            .collect::<Result<_, CrawlerError>>()?;
+        Ok(Self {
+            config: Mutex::new(config),
+            shards,
+        })
+    }
+
+    pub fn config(&self) -> Result<FrontierConfig, CrawlerError> {
+        Ok(self.config.lock()?.clone())
+    }
+
+    /// Applies new politeness settings, keeping queued URLs. If the disk
+    /// store setting changes, the queue moves into the new stores and
+    /// anything a previous run left there is resumed.
+    pub fn set_config(&self, config: FrontierConfig) -> Result<(), CrawlerError> {
+        let mut current = self.config.lock()?;
+        let mut shards = self.lock_all()?;
+        if current.disk == config.disk {
+            for (shard, frontier) in shards.iter_mut().enumerate() {
+                frontier.set_config(shard_config(&config, shard));
+            }
+            *current = config;
+            return Ok(());
+        }
+        let mut tasks = Vec::new();
+        for frontier in shards.iter_mut() {
+            tasks.extend(frontier.drain()?);
+            // Close the old store first; it may be the same file.
+            **frontier = Frontier::new(FrontierConfig::default());
+        }
+        let sequence = Arc::new(AtomicU64::new(0));
+        for (shard, frontier) in shards.iter_mut().enumerate() {
+            **frontier = Frontier::open(shard_config(&config, shard))?;
+            frontier.share_sequence(sequence.clone());
+        }
+        for task in tasks {
+            shards[shard_of(&host_of(task.url()))].push(task)?;
+        }
+        *current = config;
+        Ok(())
+    }
+
+    /// Every shard, locked in order.
+    pub fn lock_all(&self) -> Result<Vec<MutexGuard<'_, Frontier>>, CrawlerError> {
+        self.shards.iter().map(|shard| Ok(shard.lock()?)).collect()
+    }
+
+    /// The shard `url` is queued in. Holding it makes checks and updates for
+    /// that URL atomic.
+    pub fn shard_for(&self, url: &str) -> Result<MutexGuard<'_, Frontier>, CrawlerError> {
+        Ok(self.shards[shard_of(&host_of(url))].lock()?)
+    }
+
+    pub fn push(&self, task: Task) -> Result<bool, CrawlerError> {
+        self.shard_for(task.url())?.push(task)
+    }
+
+    pub fn contains(&self, url: &str) -> Result<bool, CrawlerError> {
+        self.shard_for(url)?.contains(url)
+    }
+
+    pub fn len(&self) -> Result<usize, CrawlerError> {
+        let mut len = 0;
+        for shard in &self.shards {
+            len += shard.lock()?.len();
+        }
+        Ok(len)
+    }
+
+    pub fn is_empty(&self) -> Result<bool, CrawlerError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Overrides the politeness delay for `host`.
+    pub fn set_host_delay(&self, host: &str, delay: Duration) -> Result<(), CrawlerError> {
+        self.config
+            .lock()?
+            .host_delays_ms
+            .insert(host.to_string(), delay.as_millis() as u64);
+        self.shards[shard_of(host)]
+            .lock()?
+            .set_host_delay(host, delay);
+        Ok(())
+    }
+
+    /// Takes the highest-priority task among due hosts of all shards.
+    pub fn poll(&self, now: Instant) -> Result<FrontierPoll, CrawlerError> {
+        loop {
+            let mut best = None;
+            let mut wait: Option<Instant> = None;
+            for (shard, frontier) in self.shards.iter().enumerate() {
+                match frontier.lock()?.head(now) {
+                    FrontierHead::Due(priority, sequence) => {
+                        if best.is_none_or(|(head, _)| (priority, sequence) > head) {
+                            best = Some(((priority, sequence), shard));
+                        }
+                    }
+                    FrontierHead::Wait(at) => {
+                        wait = Some(wait.map_or(at, |wait| wait.min(at)));
+                    }
+                    FrontierHead::Empty => {}
+                }
+            }
+            let Some((_, shard)) = best else {
+                return Ok(wait.map_or(FrontierPoll::Empty, FrontierPoll::Wait));
+            };
+            // Another poller may have emptied the shard in between.
+            if let FrontierPoll::Ready(task) = self.shards[shard].lock()?.poll(now)? {
+                return Ok(FrontierPoll::Ready(task));
+            }
+        }
+    }
+
+    /// Runs `f` on each shard's disk store, if the frontier has them.
+    pub fn each_store(
+        &self,
+        mut f: impl FnMut(&mut DiskStore) -> Result<(), CrawlerError>,
+    ) -> Result<(), CrawlerError> {
+        for shard in &self.shards {
+            if let Some(store) = shard.lock()?.store() {
+                f(store)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_disk_backed(&self) -> Result<bool, CrawlerError> {
+        Ok(self.config.lock()?.disk.is_some())
+    }
+
+    /// Copies of every queued task, or `None` if the queue lives in disk
+    /// stores.
+    pub fn snapshot(&self) -> Result<Option<Vec<Task>>, CrawlerError> {
+        let mut tasks = Vec::new();
+        for shard in &self.shards {
+            match shard.lock()?.snapshot() {
+                Some(snapshot) => tasks.extend(snapshot),
+                None => return Ok(None),
+            }
+        }
+        tasks.sort_by_key(|task| host_of(task.url()));
+        Ok(Some(tasks))
+    }
+
+    /// Number of queued URLs per host, deepest first.
+    pub fn queue_depths(&self) -> Result<Vec<(String, usize)>, CrawlerError> {
+        let mut depths = Vec::new();
+        for shard in &self.shards {
+            depths.extend(shard.lock()?.queue_depths());
+        }
+        depths.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        Ok(depths)
+    }
+
+    /// Queued URLs, grouped by host in priority order.
+    pub fn urls(&self) -> Result<Vec<String>, CrawlerError> {
+        let mut urls = Vec::new();
+        for shard in &self.shards {
+            urls.extend(shard.lock()?.urls()?);
+        }
+        urls.sort_by_key(|url| host_of(url));
+        Ok(urls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ready_url(poll: FrontierPoll) -> String {
+        match poll {
+            FrontierPoll::Ready(task) => task.url().to_string(),
+            other => panic!("expected a ready task, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_shards_poll_in_global_order() {
+        let frontier = ShardedFrontier::new(FrontierConfig {
+            default_delay_ms: 0,
+            ..Default::default()
+        });
+        let hosts: Vec<String> = (0..40).map(|i| format!("host{i}.example.com")).collect();
+        assert!(
+            hosts
+                .iter()
+                .map(|host| shard_of(host))
+                .collect::<std::collections::HashSet<_>>()
+                .len()
+                > 1
+        );
+        for (i, host) in hosts.iter().enumerate() {
+            let task = Task::new(format!("https://{host}/")).with_priority((i % 4) as f64);
+            assert!(frontier.push(task).unwrap());
+        }
+        assert!(!frontier
+            .push(Task::new("https://host0.example.com/".to_string()))
+            .unwrap());
+        assert_eq!(frontier.len().unwrap(), 40);
+
+        // Best priority first, then first come first served across shards.
+        let now = Instant::now();
+        let polled: Vec<String> = (0..40)
+            .map(|_| ready_url(frontier.poll(now).unwrap()))
+            .collect();
+        let mut expected: Vec<(usize, String)> = hosts
+            .iter()
+            .enumerate()
+            .map(|(i, host)| (i, format!("https://{host}/")))
+            .collect();
+        expected.sort_by_key(|(i, _)| (std::cmp::Reverse(i % 4), *i));
+        assert_eq!(
+            polled,
+            expected.into_iter().map(|(_, url)| url).collect::<Vec<_>>()
+        );
+        assert_eq!(frontier.poll(now).unwrap(), FrontierPoll::Empty);
+    }
+
+    #[test]
+    fn test_shard_path() {
+        assert_eq!(
+            shard_path("Freyr/frontier.redb", 3),
+            "Freyr/frontier-3.redb"
+        );
+        assert_eq!(shard_path("queue", 0), "queue-0");
+    }
+}
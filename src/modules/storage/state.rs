@@ -1,10 +1,24 @@
-use std::collections::{HashSet, VecDeque};
-use std::sync::{Arc, Mutex, RwLock};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
 
+use arc_swap::ArcSwap;
+use dashmap::{DashMap, DashSet};
+
+use crate::budget::CrawlBudget;
+use crate::canonical::{canonicalize, canonicalize_tracked};
 use crate::error::CrawlerError;
-use crate::task::Task;
-use dashmap::DashMap;
+use crate::modules::storage::frontier::{host_of, FrontierConfig, FrontierPoll};
+use crate::modules::storage::sharded::ShardedFrontier;
+use crate::priority::{DepthScorer, PriorityScorer};
+use crate::seen::{self, SeenConfig, SeenSet, SeenStats, UrlSet};
+use crate::task::{Stage, Task};
+use crate::tracking::get_tracking_stats;
+use crate::trap::TrapDetector;
 use lazy_static::lazy_static;
+use std::time::Duration;
+use url::Url;
 
 lazy_static! {
     static ref GLOBAL_SHARED_STATE: Arc<SharedState> = SharedState::new();
@@ -15,97 +29,740 @@ pub fn get_global_instance() -> Arc<SharedState> {
 }
 
 pub fn is_url_processed(url: &str) -> bool {
-    GLOBAL_SHARED_STATE
-        .visited
-        .read()
-        .unwrap()
-        .contains(&Task::new(url.to_string()))
+    let url = canonicalize(url).unwrap_or_else(|_| url.to_string());
+    GLOBAL_SHARED_STATE.is_seen(&url).unwrap_or(false)
 }
 
 pub fn mark_url_processed(url: String) {
-    GLOBAL_SHARED_STATE
-        .visited
-        .write()
-        .unwrap()
-        .insert(Task::new(url));
+    let url = canonicalize(&url).unwrap_or(url);
+    if let Err(e) = GLOBAL_SHARED_STATE.mark_seen(&url) {
+        tracing::error!("Failed to record visited URL: {}", e);
+    }
 }
 
+/// State shared by every worker. Nothing on the enqueue and poll paths takes
+/// a global lock: the frontier, the visited set and the budget shard
+/// internally, and the policies are swapped in atomically.
 #[derive(Debug)]
 pub struct SharedState {
-    urls: Arc<Mutex<VecDeque<Task>>>,
-    visited: Arc<RwLock<HashSet<Task>>>,
+    urls: ShardedFrontier,
+    visited: ArcSwap<Box<dyn SeenSet>>,
+    visited_path: RwLock<Option<String>>,
+    /// The frontier has a disk store, which also keeps every visited URL so
+    /// that a restarted crawl does not fetch them again.
+    disk_backed: AtomicBool,
+    /// Claimed tasks whose fetch has not finished yet.
+    in_flight: DashMap<String, Task>,
+    /// URLs that were in flight when a checkpoint was taken; they are marked
+    /// visited but never finished, so `claim_task` lets them through once.
+    reclaimable: DashSet<String>,
+    budget: ArcSwap<CrawlBudget>,
+    traps: ArcSwap<TrapDetector>,
+    scorer: ArcSwap<Arc<dyn PriorityScorer>>,
 }
 
 impl SharedState {
     pub fn new() -> Arc<Self> {
         Arc::new(SharedState {
-            urls: Arc::new(Mutex::new(VecDeque::new())),
-            visited: Arc::new(RwLock::new(HashSet::new())),
+            urls: ShardedFrontier::default(),
+            visited: ArcSwap::from_pointee(Box::new(UrlSet::default())),
+            visited_path: RwLock::new(None),
+            disk_backed: AtomicBool::new(false),
+            in_flight: DashMap::new(),
+            reclaimable: DashSet::new(),
+            budget: ArcSwap::from_pointee(CrawlBudget::default()),
+            traps: ArcSwap::from_pointee(TrapDetector::default()),
+            scorer: ArcSwap::from_pointee(Arc::new(DepthScorer)),
         })
     }
     pub fn get_handle(self: &Arc<Self>) -> Arc<Self> {
         Arc::clone(self)
     }
-    pub fn add_url(&self, url: String) -> Result<(), CrawlerError> {
-        let mut urls = self.urls.lock()?;
-        urls.push_back(Task::CrawlUrl(url));
+
+    /// Applies new politeness settings to the frontier, keeping queued URLs.
+    /// If the disk store setting changes, the queue moves into the new store
+    /// and anything a previous run left there is resumed.
+    pub fn set_frontier_config(&self, config: FrontierConfig) -> Result<(), CrawlerError> {
+        let reopened = self.urls.config()?.disk != config.disk;
+        self.urls.set_config(config)?;
+        if reopened {
+            self.resume_visited(self.visited.load().as_ref().as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the visited set with the configured kind, resuming from its
+    /// file if one exists and from the frontier's disk store if it has one.
+    pub fn set_visited_config(&self, config: &SeenConfig) -> Result<(), CrawlerError> {
+        let visited = config.build()?;
+        self.resume_visited(visited.as_ref())?;
+        self.visited.store(Arc::new(visited));
+        *self.visited_path.write()? = config.path.clone();
+        Ok(())
+    }
+
+    /// Adds the visited URLs kept in the frontier's disk stores to `visited`.
+    fn resume_visited(&self, visited: &dyn SeenSet) -> Result<(), CrawlerError> {
+        self.disk_backed
+            .store(self.urls.is_disk_backed()?, Ordering::Relaxed);
+        self.urls.each_store(|store| {
+            for url in store.visited()? {
+                visited.insert(&url);
+            }
+            Ok(())
+        })
+    }
+
+    /// Size and memory use of the visited set.
+    pub fn visited_stats(&self) -> Result<SeenStats, CrawlerError> {
+        Ok(self.visited.load().stats())
+    }
+
+    /// Makes the disk-backed frontier durable and saves the visited set, if
+    /// either is configured to persist.
+    pub fn flush(&self) -> Result<(), CrawlerError> {
+        self.urls.each_store(|store| store.flush())?;
+        if let Some(path) = self.visited_path.read()?.as_deref() {
+            seen::save(self.visited.load().as_ref().as_ref(), Path::new(path))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the visited set to `path`.
+    pub fn save_visited(&self, path: &Path) -> Result<(), CrawlerError> {
+        seen::save(self.visited.load().as_ref().as_ref(), path)
+    }
+
+    pub fn frontier_config(&self) -> Result<FrontierConfig, CrawlerError> {
+        self.urls.config()
+    }
+
+    /// Copies of the queued tasks, or `None` if the frontier is disk-backed.
+    pub fn queued_snapshot(&self) -> Result<Option<Vec<Task>>, CrawlerError> {
+        self.urls.snapshot()
+    }
+
+    /// Tasks that were claimed but not yet finished.
+    pub fn in_flight_tasks(&self) -> Vec<Task> {
+        self.in_flight
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    /// Queues tasks saved in a checkpoint as they are, skipping the scope,
+    /// trap and budget checks they passed before. The visited set already
+    /// holds the `in_flight` URLs, so each may be claimed once more. Returns
+    /// how many tasks were newly queued.
+    pub fn restore_tasks(
+        &self,
+        queued: Vec<Task>,
+        in_flight: Vec<Task>,
+    ) -> Result<usize, CrawlerError> {
+        let mut restored = 0;
+        for task in in_flight {
+            self.reclaimable.insert(task.url().to_string());
+            restored += usize::from(self.urls.push(task)?);
+        }
+        for task in queued {
+            restored += usize::from(self.urls.push(task)?);
+        }
+        Ok(restored)
+    }
+
+    /// Overrides the politeness delay for a single host.
+    pub fn set_host_delay(&self, host: &str, delay: Duration) -> Result<(), CrawlerError> {
+        self.urls.set_host_delay(host, delay)
+    }
+
+    /// Number of queued URLs per host, deepest first.
+    pub fn host_queue_depths(&self) -> Result<Vec<(String, usize)>, CrawlerError> {
+        self.urls.queue_depths()
+    }
+
+    /// Replaces the budget enforced by `add_links` and `next_task`.
+    pub fn set_budget(&self, budget: Arc<CrawlBudget>) -> Result<(), CrawlerError> {
+        self.budget.store(budget);
+        Ok(())
+    }
+
+    pub fn budget(&self) -> Result<Arc<CrawlBudget>, CrawlerError> {
+        Ok(self.budget.load_full())
+    }
+
+    /// Replaces the trap heuristics applied to every URL before it is queued.
+    pub fn set_trap_detector(&self, traps: Arc<TrapDetector>) -> Result<(), CrawlerError> {
+        self.traps.store(traps);
+        Ok(())
+    }
+
+    pub fn trap_detector(&self) -> Result<Arc<TrapDetector>, CrawlerError> {
+        Ok(self.traps.load_full())
+    }
+
+    /// Replaces the scorer that assigns priorities to queued URLs.
+    pub fn set_scorer(&self, scorer: Arc<dyn PriorityScorer>) -> Result<(), CrawlerError> {
+        self.scorer.store(Arc::new(scorer));
         Ok(())
     }
+
+    pub fn scorer(&self) -> Result<Arc<dyn PriorityScorer>, CrawlerError> {
+        Ok(self.scorer.load().as_ref().clone())
+    }
+
+    /// Queues `url` as a seed unless its canonical form was already visited
+    /// or queued, or it looks like part of a crawler trap.
+    /// Returns whether the URL was added.
+    pub fn add_url(&self, url: String) -> Result<bool, CrawlerError> {
+        Ok(self.add(None, vec![url])? == 1)
+    }
     pub fn add_urls(&self, urls: Vec<String>) -> Result<(), CrawlerError> {
-        let mut links = self.urls.lock()?;
-        for url in urls {
-            links.push_back(Task::CrawlUrl(url));
+        self.add(None, urls)?;
+        Ok(())
+    }
+
+    /// Queues links found on `parent`, skipping duplicates and URLs whose
+    /// domain depth budget does not allow them. Links that are already queued
+    /// have their priority raised if the scorer now ranks them higher.
+    /// Returns how many were newly added.
+    pub fn add_links(&self, parent: &Task, urls: Vec<String>) -> Result<usize, CrawlerError> {
+        self.add(Some(parent), urls)
+    }
+
+    /// Queues a task as it is, such as a robots.txt or sitemap fetch, unless
+    /// its URL is already queued or, for anything but a `Recrawl`, visited.
+    /// Returns whether it was added.
+    pub fn add_task(&self, task: Task) -> Result<bool, CrawlerError> {
+        if task.stage() != Stage::Fetch {
+            return Err(CrawlerError::Other(
+                "only fetch tasks can be queued".to_string(),
+            ));
+        }
+        let (url, stripped) = canonicalize_tracked(task.url())?;
+        let task = task.with_url(url);
+        self.enqueue(task, stripped)
+    }
+
+    fn add(&self, parent: Option<&Task>, urls: Vec<String>) -> Result<usize, CrawlerError> {
+        let depth = parent.map_or(0, |parent| parent.depth() + 1);
+        let urls = urls
+            .iter()
+            .map(|url| canonicalize_tracked(url))
+            .collect::<Result<Vec<_>, _>>()?;
+        let budget = self.budget()?;
+        let urls: Vec<(String, bool)> = urls
+            .into_iter()
+            .filter(|(url, _)| budget.admits_depth(&host_of(url), depth))
+            .collect();
+        let links: Vec<String> = urls.iter().map(|(url, _)| url.clone()).collect();
+        let scores = self.scorer.load().score_links(parent, &links);
+
+        let mut added = 0;
+        for ((url, stripped), score) in urls.into_iter().zip(scores) {
+            let task = Task::with_depth(url, depth).with_priority(score);
+            if self.enqueue(task, stripped)? {
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    fn enqueue(&self, task: Task, stripped: bool) -> Result<bool, CrawlerError> {
+        let stats = get_tracking_stats();
+        if stripped {
+            stats.record_rewrite();
         }
+        // Holding the URL's shard makes the checks and the push one step.
+        let mut urls = self.urls.shard_for(task.url())?;
+        let queued = urls.contains(task.url())?;
+        let recrawl = matches!(task, Task::Recrawl { .. });
+        if queued || (!recrawl && self.visited.load().contains(task.url())) {
+            if stripped {
+                stats.record_duplicate_avoided();
+            }
+            if queued {
+                urls.push(task)?;
+            }
+            return Ok(false);
+        }
+        if self.traps.load().check(&Url::parse(task.url())?).is_err() {
+            return Ok(false);
+        }
+        urls.push(task)
+    }
+
+    /// Takes the next task whose host is due for a polite fetch and whose
+    /// domain still has budget left, reserving a page of the budget for it.
+    /// Fails with `CrawlerError::BudgetExhausted` once a global budget runs
+    /// out. A task that is not fetched after all must be given back with
+    /// `release_fetch`.
+    pub fn poll_task(&self) -> Result<FrontierPoll, CrawlerError> {
+        let budget = self.budget()?;
+        budget.check().map_err(CrawlerError::BudgetExhausted)?;
+        loop {
+            let task = match self.urls.poll(Instant::now())? {
+                FrontierPoll::Ready(task) => task,
+                poll => return Ok(poll),
+            };
+            match budget.reserve(&host_of(task.url())) {
+                Ok(true) => return Ok(FrontierPoll::Ready(task)),
+                Ok(false) => {
+                    tracing::info!("Domain budget exhausted, dropping {}", task.url());
+                }
+                Err(exhausted) => {
+                    // Another poller took the last page; keep the task queued.
+                    self.urls.push(task)?;
+                    return Err(CrawlerError::BudgetExhausted(exhausted));
+                }
+            }
+        }
+    }
+
+    /// Puts a task `poll_task` returned back in the frontier and gives back
+    /// the budget page reserved for it.
+    pub fn requeue_task(&self, task: Task) -> Result<(), CrawlerError> {
+        self.release_fetch(task.url())?;
+        self.urls.push(task)?;
         Ok(())
     }
 
+    /// Like `poll_task`, but returns `None` when no host is due yet.
+    pub fn next_task(&self) -> Result<Option<Task>, CrawlerError> {
+        match self.poll_task()? {
+            FrontierPoll::Ready(task) => Ok(Some(task)),
+            FrontierPoll::Wait(_) | FrontierPoll::Empty => Ok(None),
+        }
+    }
+
+    /// Takes the next queued URL that is ready to fetch, if any.
+    pub fn next_url(&self) -> Result<Option<String>, CrawlerError> {
+        Ok(self.next_task()?.map(|task| task.url().to_string()))
+    }
+
+    /// Charges the bytes of a fetched page against the crawl budget; the
+    /// page itself was reserved by `poll_task`.
+    pub fn record_fetch(&self, url: &str, bytes: u64) -> Result<(), CrawlerError> {
+        self.budget()?.record_fetch(&host_of(url), bytes);
+        Ok(())
+    }
+
+    /// Gives back the page `poll_task` reserved for `url` when it is not
+    /// fetched after all.
+    pub fn release_fetch(&self, url: &str) -> Result<(), CrawlerError> {
+        self.budget()?.release(&host_of(url));
+        Ok(())
+    }
+
+    /// Records an already canonical URL as visited, in the disk store too
+    /// when the frontier has one. Returns whether it was new.
+    fn mark_seen(&self, url: &str) -> Result<bool, CrawlerError> {
+        let new = self.visited.load().insert(url);
+        if new && self.disk_backed.load(Ordering::Relaxed) {
+            if let Some(store) = self.urls.shard_for(url)?.store() {
+                store.mark_visited(url)?;
+            }
+        }
+        Ok(new)
+    }
+
+    fn is_seen(&self, url: &str) -> Result<bool, CrawlerError> {
+        Ok(self.visited.load().contains(url))
+    }
+
     pub fn add_visited(&self, url: String) -> Result<(), CrawlerError> {
-        let mut visited = self.visited.write()?;
-        visited.insert(Task::new(url));
+        self.mark_seen(&canonicalize(&url)?)?;
         Ok(())
     }
 
+    /// Marks `url` as visited if nobody has yet. Test and insert are one
+    /// atomic step on the visited set's shard, so of several workers racing
+    /// for the same canonical URL exactly one gets `true` and fetches it.
+    pub fn try_claim(&self, url: &str) -> Result<bool, CrawlerError> {
+        self.mark_seen(&canonicalize(url)?)
+    }
+
+    /// Claims `task` like `try_claim` and tracks it as in flight until
+    /// `finish_task`, so a checkpoint taken meanwhile still re-queues it.
+    /// `Recrawl` tasks are claimed even though their URL was visited.
+    pub fn claim_task(&self, task: &Task) -> Result<bool, CrawlerError> {
+        let recrawl = matches!(task, Task::Recrawl { .. });
+        if !self.try_claim(task.url())? && !recrawl && self.reclaimable.remove(task.url()).is_none()
+        {
+            return Ok(false);
+        }
+        self.in_flight.insert(task.url().to_string(), task.clone());
+        Ok(true)
+    }
+
+    pub fn finish_task(&self, url: &str) {
+        self.in_flight.remove(url);
+    }
+
     pub fn is_visited(&self, url: &str) -> Result<bool, CrawlerError> {
-        let visited = self.visited.read()?;
-        Ok(visited.contains(&Task::new(url.to_string())))
+        self.is_seen(&canonicalize(url)?)
+    }
+
+    /// Whether `url` is waiting in the frontier.
+    pub fn is_queued(&self, url: &str) -> Result<bool, CrawlerError> {
+        self.urls.contains(&canonicalize(url)?)
     }
 
+    /// Every visited URL. Only the exact visited set and a disk-backed
+    /// frontier keep the URLs themselves; other sets fail here.
     pub fn get_visited(&self) -> Result<Vec<String>, CrawlerError> {
-        let visited = self.visited.read()?;
-        Ok(visited
-            .iter()
-            .cloned()
-            .map(|v| match v {
-                Task::CrawlUrl(url) => url,
-            })
-            .collect())
+        if self.urls.is_disk_backed()? {
+            let mut urls = Vec::new();
+            self.urls.each_store(|store| {
+                urls.extend(store.visited()?);
+                Ok(())
+            })?;
+            urls.sort();
+            return Ok(urls);
+        }
+        let visited = self.visited.load();
+        visited.urls().ok_or_else(|| {
+            CrawlerError::Other(format!(
+                "The {:?} visited set does not keep its URLs",
+                visited.stats().kind
+            ))
+        })
+    }
+
+    /// Number of URLs recorded as visited.
+    pub fn visited_count(&self) -> Result<usize, CrawlerError> {
+        Ok(self.visited.load().len())
     }
 
     pub fn get_urls(&self) -> Result<Vec<String>, CrawlerError> {
-        let urls = self.urls.lock()?;
-        Ok(urls
-            .iter()
-            .cloned()
-            .map(|v| match v {
-                Task::CrawlUrl(url) => url,
-            })
-            .collect())
+        self.urls.urls()
     }
 }
 
+#[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::budget::{BudgetConfig, BudgetExhausted, DomainBudget};
+    use crate::priority::{BacklinkScorer, Priority};
+    use crate::seen::SeenKind;
+    use crate::trap::TrapKind;
 
     #[test]
     fn test_shared_state() {
         let state = SharedState::new();
         let handle = state.get_handle();
 
-        handle.add_url("https://example.com".to_string());
-        handle.add_visited("https://example.com".to_string());
+        handle.add_url("https://example.com".to_string()).unwrap();
+        handle
+            .add_visited("https://example.com".to_string())
+            .unwrap();
 
         assert!(handle.is_visited("https://example.com").unwrap_or(false));
         assert!(!handle.is_visited("https://example.org").unwrap_or(false));
     }
+
+    #[test]
+    fn test_shared_state_canonical_dedup() {
+        let state = SharedState::new();
+
+        state
+            .add_visited("HTTP://Example.com:80/a/../b#section".to_string())
+            .unwrap();
+
+        assert!(state.is_visited("http://example.com/b").unwrap());
+        assert_eq!(state.get_visited().unwrap(), vec!["http://example.com/b"]);
+        assert!(state.is_visited("HTTP://EXAMPLE.COM/b#top").unwrap());
+        assert_eq!(state.visited_count().unwrap(), 1);
+        assert!(state.add_url("not a url".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_shared_state_tracking_dedup() {
+        let state = SharedState::new();
+        let avoided = get_tracking_stats().duplicates_avoided();
+
+        assert!(state
+            .add_url("https://example.com/promo?utm_source=a".to_string())
+            .unwrap());
+        assert!(!state
+            .add_url("https://example.com/promo?utm_source=b&fbclid=1".to_string())
+            .unwrap());
+        assert!(!state
+            .add_url("https://example.com/promo".to_string())
+            .unwrap());
+
+        assert_eq!(state.get_urls().unwrap(), vec!["https://example.com/promo"]);
+        assert!(get_tracking_stats().duplicates_avoided() > avoided);
+        assert_eq!(
+            state.next_url().unwrap().as_deref(),
+            Some("https://example.com/promo")
+        );
+        assert_eq!(state.next_url().unwrap(), None);
+    }
+
+    #[test]
+    fn test_shared_state_budgets() {
+        let state = SharedState::new();
+        let budget = Arc::new(CrawlBudget::new(BudgetConfig {
+            max_pages: Some(2),
+            per_domain: DomainBudget {
+                max_pages: Some(1),
+                max_depth: Some(1),
+                max_bytes: None,
+            },
+            ..Default::default()
+        }));
+        state.set_budget(budget).unwrap();
+
+        state.add_url("https://a.example.com/".to_string()).unwrap();
+        let seed = Task::new("https://a.example.com/".to_string());
+        let added = state
+            .add_links(
+                &seed,
+                vec![
+                    "https://a.example.com/next".to_string(),
+                    "https://b.example.com/".to_string(),
+                ],
+            )
+            .unwrap();
+        assert_eq!(added, 2);
+        let deep = Task::with_depth("https://b.example.com/".to_string(), 1);
+        assert_eq!(
+            state
+                .add_links(&deep, vec!["https://c.example.com/".to_string()])
+                .unwrap(),
+            0
+        );
+
+        let first = state.next_task().unwrap().unwrap();
+        assert_eq!(first.url(), "https://a.example.com/");
+        assert_eq!(first.depth(), 0);
+        state.record_fetch(first.url(), 10).unwrap();
+
+        // a.example.com has used its one-page quota, so its second URL is dropped.
+        let second = state.next_task().unwrap().unwrap();
+        assert_eq!(second.url(), "https://b.example.com/");
+        assert_eq!(second.depth(), 1);
+        state.record_fetch(second.url(), 10).unwrap();
+
+        assert!(matches!(
+            state.next_task(),
+            Err(CrawlerError::BudgetExhausted(BudgetExhausted::Pages(2)))
+        ));
+    }
+
+    #[test]
+    fn test_shared_state_trap_detection() {
+        let state = SharedState::new();
+
+        assert!(state
+            .add_url("https://example.com/a/b/a/b".to_string())
+            .unwrap());
+        assert!(!state
+            .add_url("https://example.com/a/b/a/b/a/b".to_string())
+            .unwrap());
+
+        let report = state.trap_detector().unwrap().report();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].kind, TrapKind::RepeatingSegments);
+        assert_eq!(report[0].samples, vec!["https://example.com/a/b/a/b/a/b"]);
+    }
+
+    #[test]
+    fn test_shared_state_per_host_queues() {
+        let state = SharedState::new();
+        state
+            .set_frontier_config(FrontierConfig {
+                default_delay_ms: 60_000,
+                ..Default::default()
+            })
+            .unwrap();
+        state
+            .add_urls(vec![
+                "https://a.example.com/1".to_string(),
+                "https://a.example.com/2".to_string(),
+                "https://b.example.com/1".to_string(),
+            ])
+            .unwrap();
+        assert_eq!(
+            state.host_queue_depths().unwrap(),
+            vec![
+                ("a.example.com".to_string(), 2),
+                ("b.example.com".to_string(), 1)
+            ]
+        );
+
+        assert_eq!(
+            state.next_url().unwrap().as_deref(),
+            Some("https://a.example.com/1")
+        );
+        assert_eq!(
+            state.next_url().unwrap().as_deref(),
+            Some("https://b.example.com/1")
+        );
+        // a.example.com is still inside its politeness delay.
+        assert!(matches!(state.poll_task().unwrap(), FrontierPoll::Wait(_)));
+        assert_eq!(state.next_url().unwrap(), None);
+    }
+
+    #[test]
+    fn test_shared_state_rediscovery_raises_priority() {
+        let state = SharedState::new();
+        state
+            .set_frontier_config(FrontierConfig {
+                default_delay_ms: 0,
+                ..Default::default()
+            })
+            .unwrap();
+        state
+            .set_scorer(Arc::new(BacklinkScorer::default()))
+            .unwrap();
+
+        let a = Task::new("https://example.com/a".to_string());
+        let b = Task::new("https://example.com/b".to_string());
+        state
+            .add_links(
+                &a,
+                vec![
+                    "https://example.com/popular".to_string(),
+                    "https://example.com/niche".to_string(),
+                ],
+            )
+            .unwrap();
+        assert_eq!(
+            state.get_urls().unwrap(),
+            vec!["https://example.com/popular", "https://example.com/niche"]
+        );
+
+        let added = state
+            .add_links(&b, vec!["https://example.com/niche".to_string()])
+            .unwrap();
+        assert_eq!(added, 0);
+
+        let next = state.next_task().unwrap().unwrap();
+        assert_eq!(next.url(), "https://example.com/niche");
+        assert_eq!(next.priority(), Priority(2.0));
+    }
+
+    #[test]
+    fn test_recrawl_visited_page() {
+        let state = SharedState::new();
+        state
+            .set_frontier_config(FrontierConfig {
+                default_delay_ms: 0,
+                ..Default::default()
+            })
+            .unwrap();
+        let url = "https://example.com/news".to_string();
+        assert!(state.add_url(url.clone()).unwrap());
+        let page = state.next_task().unwrap().unwrap();
+        assert!(state.claim_task(&page).unwrap());
+        state.finish_task(page.url());
+
+        // A plain fetch of a visited page is refused; a recrawl is not.
+        assert!(!state.add_task(Task::new(url.clone())).unwrap());
+        let recrawl = Task::Recrawl {
+            url: url.clone(),
+            depth: 0,
+            priority: Priority(1.0),
+        };
+        assert!(state.add_task(recrawl.clone()).unwrap());
+        assert!(!state.add_task(recrawl.clone()).unwrap());
+        let next = state.next_task().unwrap().unwrap();
+        assert_eq!(next, recrawl);
+        assert!(state.claim_task(&next).unwrap());
+    }
+
+    #[test]
+    fn test_disk_frontier_keeps_visited_set() {
+        let path = std::env::temp_dir()
+            .join(format!("thamur-state-{}", std::process::id()))
+            .join("frontier.redb");
+        let _ = std::fs::remove_file(&path);
+        let config = FrontierConfig {
+            default_delay_ms: 0,
+            disk: Some(crate::modules::storage::disk::DiskFrontierConfig {
+                path: path.to_string_lossy().to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let state = SharedState::new();
+        state.set_frontier_config(config.clone()).unwrap();
+        state.set_visited_config(&SeenConfig::default()).unwrap();
+        assert!(state.try_claim("https://example.com/done").unwrap());
+        state.flush().unwrap();
+        drop(state);
+
+        // No `visited.path`: the disk store alone carries the set over.
+        let resumed = SharedState::new();
+        resumed.set_frontier_config(config).unwrap();
+        resumed.set_visited_config(&SeenConfig::default()).unwrap();
+        assert!(resumed.is_visited("https://example.com/done").unwrap());
+        assert!(!resumed
+            .add_url("https://example.com/done".to_string())
+            .unwrap());
+        assert_eq!(
+            resumed.get_visited().unwrap(),
+            vec!["https://example.com/done"]
+        );
+
+        drop(resumed);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_shared_state_visited_set_persists() {
+        let path = std::env::temp_dir()
+            .join(format!("thamur-visited-{}", std::process::id()))
+            .join("visited.bin");
+        let config = SeenConfig {
+            kind: SeenKind::Bloom,
+            initial_capacity: 100,
+            path: Some(path.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let state = SharedState::new();
+        state.set_visited_config(&config).unwrap();
+        state
+            .add_visited("https://example.com/done".to_string())
+            .unwrap();
+        state.flush().unwrap();
+        assert_eq!(state.visited_stats().unwrap().kind, SeenKind::Bloom);
+
+        let resumed = SharedState::new();
+        resumed.set_visited_config(&config).unwrap();
+        assert!(resumed.is_visited("https://example.com/done").unwrap());
+        assert!(!resumed
+            .add_url("https://example.com/done".to_string())
+            .unwrap());
+        assert_eq!(resumed.visited_count().unwrap(), 1);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_shared_state_try_claim_is_exclusive() {
+        let state = SharedState::new();
+        let claims: Vec<bool> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let state = &state;
+                    scope.spawn(move || {
+                        // Different spellings of the same canonical URL.
+                        let url = format!("https://EXAMPLE.com/page#{i}");
+                        state.try_claim(&url).unwrap()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(claims.iter().filter(|claimed| **claimed).count(), 1);
+        assert!(state.is_visited("https://example.com/page").unwrap());
+        assert!(!state
+            .add_url("https://example.com/page".to_string())
+            .unwrap());
+    }
 }
@@ -20,6 +20,7 @@ pub fn extract_domain(url: &str) -> Result<Option<String>, ParseError> {
     Ok(domain)
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -0,0 +1,1563 @@
+use std::fmt;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use dashmap::DashSet;
+use flume::{bounded, Receiver, Sender};
+use futures::FutureExt;
+use select::document::Document;
+use select::predicate::Name;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+use crate::budget::BudgetExhausted;
+use crate::canonical::canonicalize;
+use crate::checkpoint::Checkpointer;
+use crate::error::CrawlerError;
+use crate::modules::fetcher::client::{FetchedPage, Fetcher};
+use crate::modules::parser::directives::header_directives;
+use crate::modules::parser::pool::{ParseOptions, ParsePool, ParsedPage};
+use crate::modules::parser::relations::PageRelations;
+use crate::modules::storage::frontier::{host_of, FrontierPoll};
+use crate::modules::storage::state::SharedState;
+use crate::scope::CrawlScope;
+use crate::shutdown::get_shutdown;
+use crate::storage::{DataEntry, PageStore, Redirect, RedirectKind};
+use crate::task::{Stage, Task};
+use crate::thread::panic_message;
+
+/// How often the dispatcher looks at the frontier again while pages are
+/// still moving through the later stages.
+const IDLE_POLL: Duration = Duration::from_millis(50);
+
+/// Priority of robots.txt fetches, so each host's is fetched before its
+/// pages; the page that revealed a new host is queued again behind it.
+/// Finite because JSON, which checkpoints and the disk frontier store tasks
+/// in, has no infinity.
+const ROBOTS_PRIORITY: f64 = f64::MAX;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PipelineConfig {
+    /// Workers downloading pages.
+    pub fetchers: usize,
+    /// Workers handing fetched bodies to the parse pool.
+    pub parsers: usize,
+    /// Workers writing pages to storage.
+    pub storers: usize,
+    /// Capacity of the channel in front of each stage. A full channel makes
+    /// the stage before it wait.
+    pub queue_size: usize,
+    /// Fetch each host's robots.txt and queue the sitemaps it lists.
+    pub discover_sitemaps: bool,
+    /// Worker panics tolerated before the crawl is stopped.
+    pub max_panics: usize,
+    /// Leave out pages marked `noindex` instead of storing them marked.
+    pub skip_noindex: bool,
+    /// Store a page that names another in-scope URL as its canonical
+    /// version only under that URL: the canonical is queued instead. If the
+    /// crawl cannot reach it, the page is kept under its own URL.
+    pub collapse_canonicals: bool,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            fetchers: 4,
+            parsers: 2,
+            storers: 1,
+            queue_size: 16,
+            discover_sitemaps: false,
+            max_panics: 10,
+            skip_noindex: false,
+            collapse_canonicals: true,
+        }
+    }
+}
+
+/// Throughput and backlog of one pipeline stage.
+#[derive(Debug, Default)]
+pub struct StageMetrics {
+    queued: AtomicUsize,
+    peak_queued: AtomicUsize,
+    processed: AtomicU64,
+    failed: AtomicU64,
+    busy_micros: AtomicU64,
+}
+
+impl StageMetrics {
+    /// Tasks waiting in the stage's channel right now.
+    pub fn queue_depth(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+    pub fn peak_queue_depth(&self) -> usize {
+        self.peak_queued.load(Ordering::Relaxed)
+    }
+    pub fn processed(&self) -> u64 {
+        self.processed.load(Ordering::Relaxed)
+    }
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+    /// Average time a worker spent on one task.
+    pub fn mean_busy(&self) -> Duration {
+        let done = (self.processed() + self.failed()).max(1);
+        Duration::from_micros(self.busy_micros.load(Ordering::Relaxed) / done)
+    }
+
+    fn enqueued(&self) {
+        let depth = self.queued.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak_queued.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    fn dequeued(&self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn finished(&self, started: Instant, ok: bool) {
+        self.busy_micros
+            .fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+        let counter = if ok { &self.processed } else { &self.failed };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Display for StageMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} done, {} failed, {:?} each, queue depth {} (peak {})",
+            self.processed(),
+            self.failed(),
+            self.mean_busy(),
+            self.queue_depth(),
+            self.peak_queue_depth()
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PipelineMetrics {
+    pub fetch: StageMetrics,
+    pub parse: StageMetrics,
+    pub store: StageMetrics,
+}
+
+impl PipelineMetrics {
+    pub fn stage(&self, stage: Stage) -> &StageMetrics {
+        match stage {
+            Stage::Fetch => &self.fetch,
+            Stage::Parse => &self.parse,
+            Stage::Store => &self.store,
+        }
+    }
+}
+
+impl fmt::Display for PipelineMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "fetch: {}; parse: {}; store: {}",
+            self.fetch, self.parse, self.store
+        )
+    }
+}
+
+/// Why `Pipeline::run` returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PipelineStop {
+    /// The frontier ran dry.
+    Finished,
+    Budget(BudgetExhausted),
+    /// Shutdown was requested or the run was cancelled; unfinished tasks are
+    /// left in flight for the checkpoint.
+    Interrupted,
+}
+
+#[derive(Debug)]
+pub struct PipelineOutcome {
+    pub stop: PipelineStop,
+    /// Links found on the first seed page that was parsed.
+    pub seed_links: Option<Vec<String>>,
+    /// Tasks whose handling panicked; their URLs are not retried.
+    pub panics: Vec<TaskPanic>,
+}
+
+/// Whether two URLs are the same once canonicalized.
+fn same_url(a: &str, b: &str) -> bool {
+    match (canonicalize(a), canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// The redirect a fetched page made, preferring the HTTP one.
+fn redirect(redirected_to: Option<String>, refresh: Option<String>) -> Option<Redirect> {
+    redirected_to
+        .map(|target| Redirect {
+            target,
+            kind: RedirectKind::Http,
+        })
+        .or_else(|| {
+            refresh.map(|target| Redirect {
+                target,
+                kind: RedirectKind::MetaRefresh,
+            })
+        })
+}
+
+/// For a fetched page that HTTP redirects moved, the URL it ended up at and
+/// the entry recording the redirect under the URL that was requested.
+fn http_redirect(task: &Task) -> Option<(String, Task)> {
+    let Task::Parse {
+        fetched,
+        status,
+        content_type,
+        redirected_to: Some(target),
+        ..
+    } = task
+    else {
+        return None;
+    };
+    if !matches!(**fetched, Task::Fetch { .. } | Task::Recrawl { .. }) {
+        return None;
+    }
+    let entry = DataEntry {
+        url: fetched.url().to_string(),
+        status_code: *status as i32,
+        content_type: content_type.clone(),
+        title: None,
+        external_links: Vec::new(),
+        links: Vec::new(),
+        redirect: redirect(Some(target.clone()), None),
+        robots: Default::default(),
+        relations: Default::default(),
+        canonical_conflict: false,
+        metadata: Default::default(),
+        fields: Default::default(),
+        content: None,
+        crawled_at: Utc::now(),
+    };
+    let store = Task::Store {
+        entry: Box::new(entry),
+        depth: fetched.depth(),
+    };
+    Some((target.clone(), store))
+}
+
+/// A task's place in the pipeline's pending count. It is settled exactly
+/// once: when the task leaves the pipeline or moves on to the next stage.
+struct Ticket {
+    url: String,
+    settled: bool,
+}
+
+impl Ticket {
+    fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            settled: false,
+        }
+    }
+}
+
+/// A panic caught while a worker handled a task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskPanic {
+    pub url: String,
+    pub stage: Stage,
+    pub message: String,
+}
+
+/// State the stage workers share with the dispatcher.
+struct Run {
+    state: Arc<SharedState>,
+    fetcher: Arc<Fetcher>,
+    parser: Arc<ParsePool>,
+    scope: Arc<CrawlScope>,
+    store: Arc<dyn PageStore>,
+    metrics: Arc<PipelineMetrics>,
+    cancel: CancellationToken,
+    /// Tasks dispatched that have not left the pipeline yet.
+    pending: AtomicUsize,
+    progress: Notify,
+    fetched_any: AtomicBool,
+    fatal: Mutex<Option<CrawlerError>>,
+    seed_links: Mutex<Option<Vec<String>>>,
+    panics: Mutex<Vec<TaskPanic>>,
+    max_panics: usize,
+    skip_noindex: bool,
+    collapse_canonicals: bool,
+    /// Pages left out in favour of their canonical URL.
+    collapsed: DashSet<String>,
+    parse_options: Arc<ParseOptions>,
+}
+
+impl Run {
+    fn interrupted(&self) -> bool {
+        get_shutdown().is_requested() || self.cancel.is_cancelled()
+    }
+
+    /// Marks a task as having left the pipeline, unless its ticket was
+    /// already settled. `finished` tasks are done with; the others stay in
+    /// flight so a checkpoint queues them again.
+    fn leave(&self, ticket: &mut Ticket, finished: bool) {
+        if std::mem::replace(&mut ticket.settled, true) {
+            return;
+        }
+        if finished {
+            self.state.finish_task(&ticket.url);
+        }
+        self.pending.fetch_sub(1, Ordering::SeqCst);
+        self.progress.notify_waiters();
+    }
+
+    /// Records a panic while handling `ticket`'s task as a failure for its
+    /// URL, and stops the crawl once there have been too many.
+    fn panicked(&self, stage: Stage, ticket: &mut Ticket, message: String) {
+        tracing::error!("Worker panicked on {}: {}", ticket.url, message);
+        self.metrics
+            .stage(stage)
+            .failed
+            .fetch_add(1, Ordering::Relaxed);
+        self.panics.lock().unwrap().push(TaskPanic {
+            url: ticket.url.clone(),
+            stage,
+            message,
+        });
+        self.leave(ticket, true);
+        let panics = self.panics.lock().unwrap().len();
+        if panics > self.max_panics {
+            self.fail(CrawlerError::TooManyPanics(panics));
+        }
+    }
+
+    async fn process(&self, task: Task, next: Option<&Sender<Task>>, ticket: &mut Ticket) {
+        match (task.stage(), next) {
+            (Stage::Fetch, Some(next)) => self.fetch(task, next, ticket).await,
+            (Stage::Parse, Some(next)) => self.parse(task, next, ticket).await,
+            (Stage::Store, _) => self.store(task, ticket).await,
+            _ => self.leave(ticket, true),
+        }
+    }
+
+    /// Takes tasks off `receiver` until it closes. A panic while handling a
+    /// task is recorded against that task and the loop carries on.
+    async fn work(&self, receiver: &Receiver<Task>, next: Option<&Sender<Task>>) {
+        while let Ok(task) = receiver.recv_async().await {
+            let stage = task.stage();
+            self.metrics.stage(stage).dequeued();
+            let mut ticket = Ticket::new(task.url());
+            let handled = AssertUnwindSafe(self.process(task, next, &mut ticket))
+                .catch_unwind()
+                .await;
+            if let Err(payload) = handled {
+                self.panicked(stage, &mut ticket, panic_message(payload));
+            }
+        }
+    }
+
+    fn fail(&self, error: CrawlerError) {
+        self.fatal.lock().unwrap().get_or_insert(error);
+        self.cancel.cancel();
+    }
+
+    /// Hands `task` to the next stage, which then owns its place in the
+    /// pending count.
+    async fn send(&self, sender: &Sender<Task>, task: Task, ticket: &mut Ticket) -> bool {
+        let metrics = self.metrics.stage(task.stage());
+        metrics.enqueued();
+        if sender.send_async(task).await.is_err() {
+            metrics.dequeued();
+            return false;
+        }
+        ticket.settled = true;
+        true
+    }
+
+    /// Gives back the budget page reserved for `url`, which was not fetched.
+    fn release(&self, url: &str) {
+        if let Err(e) = self.state.release_fetch(url) {
+            tracing::error!("Failed to release the budget for {}: {}", url, e);
+        }
+    }
+
+    async fn fetch(&self, task: Task, parse: &Sender<Task>, ticket: &mut Ticket) {
+        let url = task.url().to_string();
+        if self.interrupted() {
+            self.release(&url);
+            return self.leave(ticket, false);
+        }
+        let started = Instant::now();
+        match self.fetcher.fetch_page(&url, &self.cancel).await {
+            Ok(FetchedPage {
+                body,
+                status,
+                content_type,
+                url: final_url,
+                robots_tags,
+                link_headers,
+            }) => {
+                self.metrics.fetch.finished(started, true);
+                self.fetched_any.store(true, Ordering::SeqCst);
+                if let Err(e) = self.state.record_fetch(&url, body.len() as u64) {
+                    tracing::error!("Failed to record fetch of {}: {}", url, e);
+                }
+                let parsed = Task::Parse {
+                    fetched: Box::new(task),
+                    status: status.as_u16(),
+                    content_type,
+                    body,
+                    redirected_to: (final_url != url).then_some(final_url),
+                    robots_tags,
+                    link_headers,
+                };
+                if !self.send(parse, parsed, ticket).await {
+                    self.leave(ticket, false);
+                }
+            }
+            Err(CrawlerError::TaskCancelled) => {
+                self.metrics.fetch.finished(started, false);
+                self.release(&url);
+                self.leave(ticket, false);
+            }
+            Err(e)
+                if matches!(task, Task::Fetch { depth: 0, .. })
+                    && !self.fetched_any.load(Ordering::SeqCst) =>
+            {
+                self.metrics.fetch.finished(started, false);
+                self.release(&url);
+                self.leave(ticket, true);
+                self.fail(e);
+            }
+            // Failed fetches do not count towards the page budget.
+            Err(e) => {
+                self.metrics.fetch.finished(started, false);
+                tracing::warn!("Error fetching {}: {}", url, e);
+                self.release(&url);
+                self.leave(ticket, true);
+            }
+        }
+    }
+
+    async fn parse(&self, task: Task, store: &Sender<Task>, ticket: &mut Ticket) {
+        let url = task.url().to_string();
+        let started = Instant::now();
+        // The target of an HTTP redirect is the page that was fetched. It is
+        // claimed, so links to it are not fetched again, and the page stored
+        // under it; the URL it was reached through is stored as a redirect.
+        let moved = http_redirect(&task);
+        let claimed = match &moved {
+            Some((target, _)) => {
+                let target = Task::with_depth(target.clone(), task.depth());
+                self.state.claim_task(&target).unwrap_or_else(|e| {
+                    tracing::warn!("Failed to claim {}: {}", target.url(), e);
+                    false
+                })
+            }
+            None => true,
+        };
+        let parsed = if claimed {
+            self.parse_task(task).await
+        } else {
+            Ok(None)
+        };
+        let page = match parsed {
+            Ok(page) => {
+                self.metrics.parse.finished(started, true);
+                page
+            }
+            // The parse pool catches panics on its threads and hands them
+            // back as errors.
+            Err(CrawlerError::TaskPanicked(message)) => {
+                if let Some((target, _)) = moved.as_ref().filter(|_| claimed) {
+                    self.state.finish_task(target);
+                }
+                return self.panicked(Stage::Parse, ticket, message);
+            }
+            Err(e) => {
+                self.metrics.parse.finished(started, false);
+                tracing::warn!("Error parsing {}: {}", url, e);
+                None
+            }
+        };
+        match moved {
+            Some((target, redirect)) => {
+                if claimed {
+                    let mut moved_ticket = Ticket::new(&target);
+                    self.pending.fetch_add(1, Ordering::SeqCst);
+                    self.forward(store, page, &mut moved_ticket).await;
+                }
+                self.forward(store, Some(redirect), ticket).await;
+            }
+            None => self.forward(store, page, ticket).await,
+        }
+    }
+
+    /// Sends `page` on to be stored, or settles `ticket` if there is none.
+    /// A page handed to storage is no longer in flight, so a checkpoint
+    /// taken before it is saved does not fetch it again; if the crawl stops
+    /// first, the page is lost rather than fetched twice.
+    async fn forward(&self, store: &Sender<Task>, page: Option<Task>, ticket: &mut Ticket) {
+        match page {
+            Some(page) => {
+                if self.send(store, page, ticket).await {
+                    self.state.finish_task(&ticket.url);
+                } else {
+                    self.leave(ticket, false);
+                }
+            }
+            None => self.leave(ticket, true),
+        }
+    }
+
+    /// Follows a fetched body's links. Returns the page to store, if any.
+    async fn parse_task(&self, task: Task) -> Result<Option<Task>, CrawlerError> {
+        let Task::Parse {
+            fetched,
+            status,
+            content_type,
+            body,
+            redirected_to,
+            robots_tags,
+            link_headers,
+        } = task
+        else {
+            return Err(CrawlerError::Other(format!("not a parse task: {:?}", task)));
+        };
+        match *fetched {
+            Task::FetchRobots { depth, .. } => {
+                for sitemap in sitemaps_in_robots(&body) {
+                    let task = Task::FetchSitemap {
+                        url: sitemap,
+                        depth,
+                        priority: fetched.priority(),
+                    };
+                    self.state.add_task(task)?;
+                }
+                Ok(None)
+            }
+            Task::FetchSitemap { depth, .. } => {
+                let (sitemaps, pages) = self.parser.run(move || Ok(sitemap_locs(&body))).await?;
+                for sitemap in sitemaps {
+                    let task = Task::FetchSitemap {
+                        url: sitemap,
+                        depth,
+                        priority: fetched.priority(),
+                    };
+                    self.state.add_task(task)?;
+                }
+                let (internal, _) = self.scope.partition(pages);
+                self.state.add_links(&fetched, internal)?;
+                Ok(None)
+            }
+            Task::Fetch { .. } | Task::Recrawl { .. } => {
+                let url = fetched.url().to_string();
+                // Relative links resolve against where the fetch ended up.
+                let page_url = redirected_to.clone().unwrap_or_else(|| url.clone());
+                let ParsedPage {
+                    links,
+                    title,
+                    refresh,
+                    robots,
+                    metadata,
+                    fields,
+                    content,
+                } = self
+                    .parser
+                    .parse(body, &page_url, self.parse_options.clone())
+                    .await?;
+                let robots =
+                    robots.merge(header_directives(&robots_tags, &self.parse_options.robot));
+                let followed: Vec<_> = links
+                    .iter()
+                    .filter(|link| !robots.nofollow && self.scope.follows(link))
+                    .map(|link| link.url.clone())
+                    .collect();
+                let (internal, external) = self.scope.partition(followed.clone());
+                self.state.add_links(&fetched, internal)?;
+                if fetched.depth() == 0 {
+                    self.seed_links.lock().unwrap().get_or_insert(followed);
+                }
+                if robots.noindex && self.skip_noindex {
+                    return Ok(None);
+                }
+                let relations = PageRelations::collect(&links, &link_headers, &page_url.parse()?);
+                let canonical_conflict = match (
+                    &relations.canonical,
+                    &redirect(redirected_to, refresh.clone()),
+                ) {
+                    (Some(canonical), Some(redirect)) => !same_url(canonical, &redirect.target),
+                    _ => false,
+                };
+                if let Some(canonical) = relations.canonical.as_ref().filter(|canonical| {
+                    self.collapse_canonicals
+                        && !canonical_conflict
+                        && !same_url(canonical, &page_url)
+                }) {
+                    let (internal, _) = self.scope.partition(vec![canonical.clone()]);
+                    if let Some(canonical) = internal.into_iter().next() {
+                        if self.collapses_into(&fetched, &page_url, canonical)? {
+                            return Ok(None);
+                        }
+                    }
+                }
+                Ok(Some(Task::Store {
+                    entry: Box::new(DataEntry {
+                        url: page_url,
+                        status_code: status as i32,
+                        content_type,
+                        title,
+                        external_links: external,
+                        links,
+                        // An HTTP redirect is stored with the URL it came from.
+                        redirect: redirect(None, refresh),
+                        robots,
+                        relations,
+                        canonical_conflict,
+                        metadata,
+                        fields,
+                        // noarchive pages are recorded without their content.
+                        content: content.filter(|_| !robots.noarchive),
+                        crawled_at: Utc::now(),
+                    }),
+                    depth: fetched.depth(),
+                }))
+            }
+            other => Err(CrawlerError::Other(format!("cannot parse {:?}", other))),
+        }
+    }
+
+    /// Whether a page naming `canonical` is left out because the canonical
+    /// page is queued or was fetched and stands in for it. A page whose
+    /// canonical the crawl will not reach, e.g. one past the depth budget,
+    /// is kept under its own URL. When two pages name each other, the
+    /// second one parsed is kept so the pair is not lost.
+    fn collapses_into(
+        &self,
+        page: &Task,
+        page_url: &str,
+        canonical: String,
+    ) -> Result<bool, CrawlerError> {
+        let key = canonicalize(page_url)?;
+        // Recorded before looking at the canonical, so two pages naming each
+        // other at the same time are at worst both kept.
+        self.collapsed.insert(key.clone());
+        let collapses = if self.state.add_links(page, vec![canonical.clone()])? > 0
+            || self.state.is_queued(&canonical)?
+        {
+            true
+        } else if self.state.is_visited(&canonical)? {
+            !self.collapsed.contains(&canonicalize(&canonical)?)
+        } else {
+            false
+        };
+        if !collapses {
+            self.collapsed.remove(&key);
+        }
+        Ok(collapses)
+    }
+
+    async fn store(&self, task: Task, ticket: &mut Ticket) {
+        let url = task.url().to_string();
+        let started = Instant::now();
+        let Task::Store { entry, .. } = task else {
+            return self.leave(ticket, true);
+        };
+        let store = self.store.clone();
+        // A panicking store is handled like a panic in the worker itself.
+        let saved = match tokio::task::spawn_blocking(move || store.save(&entry)).await {
+            Ok(saved) => saved,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(CrawlerError::TaskPanicked(e.to_string())),
+        };
+        self.metrics.store.finished(started, saved.is_ok());
+        if let Err(e) = saved {
+            tracing::error!("Error storing {}: {}", url, e);
+        }
+        self.leave(ticket, true);
+    }
+}
+
+/// `Sitemap:` lines of a robots.txt file.
+fn sitemaps_in_robots(robots: &str) -> Vec<String> {
+    robots
+        .lines()
+        .filter_map(|line| {
+            let (field, value) = line.split_once(':')?;
+            field
+                .trim()
+                .eq_ignore_ascii_case("sitemap")
+                .then(|| value.trim().to_string())
+        })
+        .filter(|url| !url.is_empty())
+        .collect()
+}
+
+/// `<loc>` entries of a sitemap, split into nested sitemaps (from a sitemap
+/// index) and pages.
+fn sitemap_locs(xml: &str) -> (Vec<String>, Vec<String>) {
+    let document = Document::from(xml);
+    let mut sitemaps = Vec::new();
+    let mut pages = Vec::new();
+    for loc in document.find(Name("loc")) {
+        let url = loc.text().trim().to_string();
+        if url.is_empty() {
+            continue;
+        }
+        match loc.parent().and_then(|parent| parent.name()) {
+            Some("sitemap") => sitemaps.push(url),
+            _ => pages.push(url),
+        }
+    }
+    (sitemaps, pages)
+}
+
+/// Runs a crawl as fetch → parse → store stages, each with its own workers
+/// and a bounded channel in front. When a stage falls behind its channel
+/// fills up and the stage before it waits, so a slow store slows fetching
+/// instead of piling pages up in memory.
+pub struct Pipeline {
+    config: PipelineConfig,
+    state: Arc<SharedState>,
+    fetcher: Arc<Fetcher>,
+    parser: Arc<ParsePool>,
+    scope: Arc<CrawlScope>,
+    store: Arc<dyn PageStore>,
+    metrics: Arc<PipelineMetrics>,
+    parse_options: Arc<ParseOptions>,
+}
+
+impl Pipeline {
+    pub fn new(
+        config: PipelineConfig,
+        state: Arc<SharedState>,
+        fetcher: Arc<Fetcher>,
+        parser: Arc<ParsePool>,
+        scope: Arc<CrawlScope>,
+        store: Arc<dyn PageStore>,
+    ) -> Self {
+        Self {
+            config,
+            state,
+            fetcher,
+            parser,
+            scope,
+            store,
+            metrics: Arc::new(PipelineMetrics::default()),
+            parse_options: Arc::new(ParseOptions::default()),
+        }
+    }
+
+    /// Sets the user agent whose robots directives pages are checked for and
+    /// the rules extracting custom fields.
+    pub fn with_parse_options(mut self, options: ParseOptions) -> Self {
+        self.parse_options = Arc::new(options);
+        self
+    }
+
+    pub fn metrics(&self) -> &PipelineMetrics {
+        &self.metrics
+    }
+
+    /// Crawls until the frontier is empty, a budget runs out or the run is
+    /// interrupted, then lets the stages drain.
+    pub async fn run(
+        &self,
+        cancel: &CancellationToken,
+        checkpointer: Option<&mut Checkpointer>,
+    ) -> Result<PipelineOutcome, CrawlerError> {
+        let run = Arc::new(Run {
+            state: self.state.clone(),
+            fetcher: self.fetcher.clone(),
+            parser: self.parser.clone(),
+            scope: self.scope.clone(),
+            store: self.store.clone(),
+            metrics: self.metrics.clone(),
+            cancel: cancel.child_token(),
+            pending: AtomicUsize::new(0),
+            progress: Notify::new(),
+            fetched_any: AtomicBool::new(false),
+            fatal: Mutex::new(None),
+            seed_links: Mutex::new(None),
+            panics: Mutex::new(Vec::new()),
+            max_panics: self.config.max_panics,
+            skip_noindex: self.config.skip_noindex,
+            collapse_canonicals: self.config.collapse_canonicals,
+            collapsed: DashSet::new(),
+            parse_options: self.parse_options.clone(),
+        });
+        let queue_size = self.config.queue_size.max(1);
+        let (fetch_tx, fetch_rx) = bounded::<Task>(queue_size);
+        let (parse_tx, parse_rx) = bounded::<Task>(queue_size);
+        let (store_tx, store_rx) = bounded::<Task>(queue_size);
+
+        let mut workers = JoinSet::new();
+        let spawn_stage = |workers: &mut JoinSet<()>,
+                           count: usize,
+                           receiver: Receiver<Task>,
+                           next: Option<Sender<Task>>| {
+            for _ in 0..count.max(1) {
+                let (run, receiver, next) = (run.clone(), receiver.clone(), next.clone());
+                // Supervises the worker: a panic outside task handling
+                // restarts it instead of shrinking the stage.
+                workers.spawn(async move {
+                    loop {
+                        let worked = AssertUnwindSafe(run.work(&receiver, next.as_ref()))
+                            .catch_unwind()
+                            .await;
+                        match worked {
+                            Ok(()) => break,
+                            Err(payload) => {
+                                tracing::error!(
+                                    "Pipeline worker panicked, restarting it: {}",
+                                    panic_message(payload)
+                                );
+                            }
+                        }
+                    }
+                });
+            }
+        };
+        spawn_stage(&mut workers, self.config.fetchers, fetch_rx, Some(parse_tx));
+        spawn_stage(&mut workers, self.config.parsers, parse_rx, Some(store_tx));
+        spawn_stage(&mut workers, self.config.storers, store_rx, None);
+
+        let stop = self.dispatch(&run, &fetch_tx, checkpointer).await;
+        // Closing the first channel lets each stage drain and close the next.
+        drop(fetch_tx);
+        while workers.join_next().await.is_some() {}
+
+        if let Some(error) = run.fatal.lock().unwrap().take() {
+            return Err(error);
+        }
+        let stop = stop?;
+        let seed_links = run.seed_links.lock().unwrap().take();
+        let panics = std::mem::take(&mut *run.panics.lock().unwrap());
+        Ok(PipelineOutcome {
+            stop,
+            seed_links,
+            panics,
+        })
+    }
+
+    /// Feeds due tasks from the frontier into the fetch stage.
+    async fn dispatch(
+        &self,
+        run: &Arc<Run>,
+        fetch: &Sender<Task>,
+        mut checkpointer: Option<&mut Checkpointer>,
+    ) -> Result<PipelineStop, CrawlerError> {
+        let hosts = DashSet::new();
+        loop {
+            if run.interrupted() {
+                return Ok(PipelineStop::Interrupted);
+            }
+            // Registered before polling, so progress made meanwhile wakes us.
+            let progress = run.progress.notified();
+            tokio::pin!(progress);
+            progress.as_mut().enable();
+            let task = match self.state.poll_task() {
+                Ok(FrontierPoll::Ready(task)) => task,
+                Ok(FrontierPoll::Wait(due)) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(due.into()) => {}
+                        _ = progress => {}
+                        _ = get_shutdown().requested() => {}
+                        _ = run.cancel.cancelled() => {}
+                    }
+                    continue;
+                }
+                Ok(FrontierPoll::Empty) if run.pending.load(Ordering::SeqCst) == 0 => {
+                    return Ok(PipelineStop::Finished);
+                }
+                Ok(FrontierPoll::Empty) => {
+                    // Pages still in the pipeline may add links.
+                    tokio::select! {
+                        _ = progress => {}
+                        _ = tokio::time::sleep(IDLE_POLL) => {}
+                        _ = run.cancel.cancelled() => {}
+                    }
+                    continue;
+                }
+                Err(CrawlerError::BudgetExhausted(reason)) => {
+                    return Ok(PipelineStop::Budget(reason));
+                }
+                Err(e) => return Err(e),
+            };
+
+            if self.config.discover_sitemaps && hosts.insert(host_of(task.url())) {
+                if let Ok(mut robots) = url::Url::parse(task.url()) {
+                    robots.set_path("/robots.txt");
+                    robots.set_query(None);
+                    let robots = Task::FetchRobots {
+                        url: robots.to_string(),
+                        depth: task.depth(),
+                        priority: task.priority(),
+                    };
+                    if self.state.add_task(robots.with_priority(ROBOTS_PRIORITY))? {
+                        self.state.requeue_task(task)?;
+                        continue;
+                    }
+                }
+            }
+            if !self.state.claim_task(&task)? {
+                run.release(task.url());
+                continue;
+            }
+            let url = task.url().to_string();
+            let mut ticket = Ticket::new(&url);
+            run.pending.fetch_add(1, Ordering::SeqCst);
+            if !run.send(fetch, task, &mut ticket).await {
+                run.release(&url);
+                run.leave(&mut ticket, false);
+            }
+
+            if let Some(checkpointer) = checkpointer.as_deref_mut() {
+                if let Err(e) = checkpointer.maybe_save(&self.state) {
+                    tracing::error!("Error saving checkpoint: {}", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::{BudgetConfig, CrawlBudget, DomainBudget};
+    use crate::modules::fetcher::client::DEFAULT_USER_AGENT;
+    use crate::modules::parser::directives::RobotsDirectives;
+    use crate::modules::storage::disk::DiskFrontierConfig;
+    use crate::modules::storage::frontier::FrontierConfig;
+
+    #[test]
+    fn test_robots_priority_round_trips() {
+        let robots = Task::FetchRobots {
+            url: "https://example.com/robots.txt".to_string(),
+            depth: 0,
+            priority: Default::default(),
+        }
+        .with_priority(ROBOTS_PRIORITY);
+        let json = serde_json::to_string(&robots).unwrap();
+        assert_eq!(serde_json::from_str::<Task>(&json).unwrap(), robots);
+
+        // The disk frontier stores tasks as JSON too.
+        let path = std::env::temp_dir()
+            .join(format!("thamur-robots-{}", std::process::id()))
+            .join("frontier.redb");
+        let config = FrontierConfig {
+            default_delay_ms: 0,
+            disk: Some(DiskFrontierConfig {
+                path: path.to_string_lossy().to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let state = SharedState::new();
+        state.set_frontier_config(config.clone()).unwrap();
+        state.add_url("https://example.com/".to_string()).unwrap();
+        assert!(state.add_task(robots.clone()).unwrap());
+        state.flush().unwrap();
+        drop(state);
+
+        let resumed = SharedState::new();
+        resumed.set_frontier_config(config).unwrap();
+        assert_eq!(resumed.next_task().unwrap(), Some(robots));
+        drop(resumed);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_sitemap_discovery() {
+        let robots = "User-agent: *\nDisallow: /private\nSitemap: https://example.com/sitemap.xml\nsitemap:https://example.com/news.xml\n";
+        assert_eq!(
+            sitemaps_in_robots(robots),
+            vec![
+                "https://example.com/sitemap.xml".to_string(),
+                "https://example.com/news.xml".to_string()
+            ]
+        );
+
+        let index = r#"<?xml version="1.0"?>
+            <sitemapindex><sitemap><loc>https://example.com/a.xml</loc></sitemap></sitemapindex>"#;
+        assert_eq!(
+            sitemap_locs(index),
+            (vec!["https://example.com/a.xml".to_string()], vec![])
+        );
+
+        let urlset = r#"<?xml version="1.0"?>
+            <urlset><url><loc> https://example.com/page </loc></url></urlset>"#;
+        assert_eq!(
+            sitemap_locs(urlset),
+            (vec![], vec!["https://example.com/page".to_string()])
+        );
+    }
+
+    /// A pipeline crawling `server` from its root into `store`, without
+    /// politeness delays, and the state it crawls with.
+    async fn test_pipeline(
+        server: &httpmock::MockServer,
+        config: PipelineConfig,
+        store: Arc<dyn PageStore>,
+    ) -> (Pipeline, Arc<SharedState>) {
+        let state = SharedState::new();
+        state
+            .set_frontier_config(FrontierConfig {
+                default_delay_ms: 0,
+                ..Default::default()
+            })
+            .unwrap();
+        state.add_url(server.url("/")).unwrap();
+        let seed = url::Url::parse(&server.url("/")).unwrap();
+        let pipeline = Pipeline::new(
+            config,
+            state.clone(),
+            Arc::new(Fetcher::new(DEFAULT_USER_AGENT).await.unwrap()),
+            Arc::new(ParsePool::new(1, 1)),
+            Arc::new(CrawlScope::new(&Default::default(), &seed).unwrap()),
+            store,
+        );
+        (pipeline, state)
+    }
+
+    /// Records pages slowly, tracking how far fetching runs ahead and
+    /// whether pages are still in flight once handed over.
+    struct SlowStore {
+        metrics: Mutex<Option<Arc<PipelineMetrics>>>,
+        state: Mutex<Option<Arc<SharedState>>>,
+        stored: Mutex<Vec<String>>,
+        max_ahead: AtomicU64,
+        saved_in_flight: AtomicBool,
+    }
+
+    impl PageStore for SlowStore {
+        fn save(&self, entry: &DataEntry) -> Result<(), CrawlerError> {
+            if let Some(state) = self.state.lock().unwrap().as_ref() {
+                let in_flight = state.in_flight_tasks();
+                if in_flight.iter().any(|task| task.url() == entry.url) {
+                    self.saved_in_flight.store(true, Ordering::SeqCst);
+                }
+            }
+            std::thread::sleep(Duration::from_millis(20));
+            let mut stored = self.stored.lock().unwrap();
+            stored.push(entry.url.clone());
+            if let Some(metrics) = self.metrics.lock().unwrap().as_ref() {
+                let ahead = metrics.fetch.processed() - stored.len() as u64;
+                self.max_ahead.fetch_max(ahead, Ordering::SeqCst);
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_slow_store_holds_back_fetching() {
+        let server = httpmock::MockServer::start();
+        let links: String = (0..20)
+            .map(|i| format!("<a href=\"/page/{i}\">{i}</a>"))
+            .collect();
+        server.mock(|when, then| {
+            when.path("/");
+            then.status(200)
+                .body(format!("<html><body>{links}</body></html>"));
+        });
+        server.mock(|when, then| {
+            when.path_contains("/page/");
+            then.status(200)
+                .body("<html><head><title>Leaf</title></head></html>");
+        });
+
+        let store = Arc::new(SlowStore {
+            metrics: Mutex::new(None),
+            state: Mutex::new(None),
+            stored: Mutex::new(Vec::new()),
+            max_ahead: AtomicU64::new(0),
+            saved_in_flight: AtomicBool::new(false),
+        });
+        let config = PipelineConfig {
+            fetchers: 2,
+            parsers: 1,
+            storers: 1,
+            queue_size: 1,
+            ..Default::default()
+        };
+        let (pipeline, state) = test_pipeline(&server, config, store.clone()).await;
+        *store.metrics.lock().unwrap() = Some(pipeline.metrics.clone());
+        *store.state.lock().unwrap() = Some(state.clone());
+
+        let outcome = pipeline.run(&CancellationToken::new(), None).await.unwrap();
+        assert_eq!(outcome.stop, PipelineStop::Finished);
+        assert_eq!(outcome.seed_links.map(|links| links.len()), Some(20));
+        assert_eq!(store.stored.lock().unwrap().len(), 21);
+        assert_eq!(pipeline.metrics().store.processed(), 21);
+        // Two fetchers, one parser and one storer plus a slot in each
+        // channel: fetching never gets further ahead than that.
+        assert!(store.max_ahead.load(Ordering::SeqCst) <= 6);
+        // A checkpoint taken while a page waits to be saved does not fetch
+        // it again.
+        assert!(!store.saved_in_flight.load(Ordering::SeqCst));
+        assert!(state.in_flight_tasks().is_empty());
+    }
+
+    /// Panics while storing the even-numbered pages.
+    struct PanickyStore;
+
+    impl PageStore for PanickyStore {
+        fn save(&self, entry: &DataEntry) -> Result<(), CrawlerError> {
+            let page = entry.url.rsplit('/').next().unwrap_or_default();
+            if page.parse::<u32>().is_ok_and(|n| n % 2 == 0) {
+                panic!("cannot store {}", entry.url);
+            }
+            Ok(())
+        }
+    }
+
+    async fn crawl_with_panics(
+        server: &httpmock::MockServer,
+        max_panics: usize,
+        store: Arc<dyn PageStore>,
+    ) -> (Result<PipelineOutcome, CrawlerError>, Arc<SharedState>) {
+        let config = PipelineConfig {
+            fetchers: 2,
+            parsers: 1,
+            storers: 1,
+            max_panics,
+            ..Default::default()
+        };
+        let (mut pipeline, state) = test_pipeline(server, config, store).await;
+        pipeline.parser = Arc::new(ParsePool::new(1, 1).with_page_parser(panicky_parse));
+        let outcome = pipeline.run(&CancellationToken::new(), None).await;
+        (outcome, state)
+    }
+
+    /// Marks pages that make `panicky_parse` panic.
+    const PANIC_MARKER: &str = "<!-- parser panic -->";
+
+    /// `parse_page`, except that it panics on pages holding `PANIC_MARKER`
+    /// as a parser bug would.
+    fn panicky_parse(
+        html: &str,
+        base_url: &str,
+        options: &ParseOptions,
+    ) -> Result<ParsedPage, CrawlerError> {
+        if html.contains(PANIC_MARKER) {
+            panic!("cannot parse {base_url}");
+        }
+        crate::modules::parser::pool::parse_page(html, base_url, options)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_worker_panics_are_isolated() {
+        let server = httpmock::MockServer::start();
+        let links: String = (0..10)
+            .map(|i| format!("<a href=\"/page/{i}\">{i}</a>"))
+            .collect();
+        server.mock(|when, then| {
+            when.path("/");
+            then.status(200)
+                .body(format!("<html><body>{links}</body></html>"));
+        });
+        server.mock(|when, then| {
+            when.path_contains("/page/");
+            then.status(200).body("<html></html>");
+        });
+
+        let (outcome, state) = crawl_with_panics(&server, 10, Arc::new(PanickyStore)).await;
+        let outcome = outcome.unwrap();
+        assert_eq!(outcome.stop, PipelineStop::Finished);
+        let mut panicked: Vec<_> = outcome.panics.iter().map(|p| p.url.clone()).collect();
+        panicked.sort();
+        let expected: Vec<_> = [0, 2, 4, 6, 8]
+            .iter()
+            .map(|i| server.url(format!("/page/{i}")))
+            .collect();
+        assert_eq!(panicked, expected);
+        assert!(outcome.panics.iter().all(|p| p.stage == Stage::Store));
+        // Panicked pages are settled, not left in flight to be retried.
+        assert!(state.in_flight_tasks().is_empty());
+
+        let (outcome, _) = crawl_with_panics(&server, 2, Arc::new(PanickyStore)).await;
+        assert!(matches!(outcome, Err(CrawlerError::TooManyPanics(n)) if n > 2));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_parser_panics_are_counted() {
+        let server = httpmock::MockServer::start();
+        let links: String = (0..6)
+            .map(|i| format!("<a href=\"/page/{i}\">{i}</a>"))
+            .collect();
+        server.mock(|when, then| {
+            when.path("/");
+            then.status(200)
+                .body(format!("<html><body>{links}</body></html>"));
+        });
+        server.mock(|when, then| {
+            when.path_matches(regex::Regex::new("^/page/[024]$").unwrap());
+            then.status(200).body(PANIC_MARKER);
+        });
+        server.mock(|when, then| {
+            when.path_matches(regex::Regex::new("^/page/[135]$").unwrap());
+            then.status(200).body("<html></html>");
+        });
+
+        let store = Arc::new(MemoryStore::default());
+        let (outcome, state) = crawl_with_panics(&server, 10, store.clone()).await;
+        let outcome = outcome.unwrap();
+        let mut panicked: Vec<_> = outcome.panics.iter().map(|p| p.url.clone()).collect();
+        panicked.sort();
+        let expected: Vec<_> = [0, 2, 4]
+            .iter()
+            .map(|i| server.url(format!("/page/{i}")))
+            .collect();
+        assert_eq!(panicked, expected);
+        assert!(outcome
+            .panics
+            .iter()
+            .all(|p| p.stage == Stage::Parse && p.message == format!("cannot parse {}", p.url)));
+        // The seed and the odd pages are stored.
+        assert_eq!(store.entries.lock().unwrap().len(), 4);
+        assert!(state.in_flight_tasks().is_empty());
+
+        let (outcome, _) = crawl_with_panics(&server, 1, Arc::new(MemoryStore::default())).await;
+        assert!(matches!(outcome, Err(CrawlerError::TooManyPanics(n)) if n > 1));
+    }
+
+    /// Keeps every stored page.
+    #[derive(Default)]
+    struct MemoryStore {
+        entries: Mutex<Vec<DataEntry>>,
+    }
+
+    impl PageStore for MemoryStore {
+        fn save(&self, entry: &DataEntry) -> Result<(), CrawlerError> {
+            self.entries.lock().unwrap().push(entry.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_redirects_are_tracked() {
+        let server = httpmock::MockServer::start();
+        server.mock(|when, then| {
+            when.path("/");
+            then.status(200).body(
+                "<html><head><meta http-equiv=\"refresh\" content=\"0; url=/old\"></head></html>",
+            );
+        });
+        server.mock(|when, then| {
+            when.path("/old");
+            then.status(301).header("Location", "/moved/");
+        });
+        let moved = server.mock(|when, then| {
+            when.path("/moved/");
+            then.status(200)
+                .body("<html><body><a href=\"child\">child</a></body></html>");
+        });
+        let child = server.mock(|when, then| {
+            when.path("/moved/child");
+            then.status(200)
+                .body("<html><body><a href=\"/moved/\">back</a></body></html>");
+        });
+
+        let store = Arc::new(MemoryStore::default());
+        let (pipeline, _) = test_pipeline(&server, PipelineConfig::default(), store.clone()).await;
+        let outcome = pipeline.run(&CancellationToken::new(), None).await.unwrap();
+        assert_eq!(outcome.stop, PipelineStop::Finished);
+
+        let entries = store.entries.lock().unwrap();
+        let redirect_of = |path: &str| {
+            entries
+                .iter()
+                .find(|entry| entry.url == server.url(path))
+                .and_then(|entry| entry.redirect.clone())
+        };
+        assert_eq!(
+            redirect_of("/"),
+            Some(Redirect {
+                target: server.url("/old"),
+                kind: RedirectKind::MetaRefresh,
+            })
+        );
+        assert_eq!(
+            redirect_of("/old"),
+            Some(Redirect {
+                target: server.url("/moved/"),
+                kind: RedirectKind::Http,
+            })
+        );
+        // The page is stored under where it moved to, which counts as
+        // visited: the link back to it is not fetched again.
+        let target = entries
+            .iter()
+            .find(|entry| entry.url == server.url("/moved/"))
+            .unwrap();
+        assert_eq!(target.redirect, None);
+        assert_eq!(target.links[0].url, server.url("/moved/child"));
+        assert_eq!(entries.len(), 4);
+        moved.assert_hits(1);
+        // Links on a redirected page resolve against where it moved to.
+        child.assert();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_robots_txt_is_fetched_first() {
+        let server = httpmock::MockServer::start();
+        let robots = server.mock(|when, then| {
+            when.path("/robots.txt");
+            then.status(200)
+                .delay(Duration::from_millis(300))
+                .body("User-agent: *\nAllow: /\n");
+        });
+        server.mock(|when, then| {
+            when.path("/");
+            then.status(200).body("<html></html>");
+        });
+
+        let store = Arc::new(MemoryStore::default());
+        let config = PipelineConfig {
+            fetchers: 1,
+            discover_sitemaps: true,
+            ..Default::default()
+        };
+        let (pipeline, _) = test_pipeline(&server, config, store.clone()).await;
+        let started = Utc::now();
+        let outcome = pipeline.run(&CancellationToken::new(), None).await.unwrap();
+        assert_eq!(outcome.stop, PipelineStop::Finished);
+        robots.assert_hits(1);
+
+        // With a single fetcher, the seed is fetched only once the slow
+        // robots.txt is done.
+        let entries = store.entries.lock().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].crawled_at - started >= chrono::Duration::milliseconds(300));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_robots_directives_are_applied() {
+        let server = httpmock::MockServer::start();
+        server.mock(|when, then| {
+            when.path("/");
+            then.status(200).body(
+                "<html><head><meta name=\"robots\" content=\"noarchive\"></head>\
+                 <body><p>An archived page should not keep its text.</p>\
+                 <a href=\"/a\">a</a><a href=\"/b\">b</a></body></html>",
+            );
+        });
+        server.mock(|when, then| {
+            when.path("/a");
+            then.status(200)
+                .header("X-Robots-Tag", "crawlbot: nofollow")
+                .body(
+                    "<html><body><p>This page keeps its readable text.</p>\
+                     <a href=\"/a/deep\">deep</a></body></html>",
+                );
+        });
+        let deep = server.mock(|when, then| {
+            when.path("/a/deep");
+            then.status(200).body("<html></html>");
+        });
+        server.mock(|when, then| {
+            when.path("/b");
+            then.status(200)
+                .body("<html><head><meta name=\"CrawlBot\" content=\"noindex\"></head></html>");
+        });
+
+        let store = Arc::new(MemoryStore::default());
+        let config = PipelineConfig {
+            skip_noindex: true,
+            ..Default::default()
+        };
+        let (pipeline, _) = test_pipeline(&server, config, store.clone()).await;
+        let pipeline =
+            pipeline.with_parse_options(ParseOptions::new("CrawlBot/2.0", Default::default()));
+        let outcome = pipeline.run(&CancellationToken::new(), None).await.unwrap();
+        assert_eq!(outcome.stop, PipelineStop::Finished);
+
+        let entries = store.entries.lock().unwrap();
+        let robots: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.url.trim_start_matches(&server.url("")), entry.robots))
+            .collect();
+        assert!(robots.contains(&(
+            "/",
+            RobotsDirectives {
+                noarchive: true,
+                ..Default::default()
+            }
+        )));
+        assert!(robots.contains(&(
+            "/a",
+            RobotsDirectives {
+                nofollow: true,
+                ..Default::default()
+            }
+        )));
+        // `/b` is noindex and skipped; `/a` asks not to follow its links.
+        assert_eq!(robots.len(), 2);
+        for entry in entries.iter() {
+            assert_eq!(entry.content.is_some(), !entry.robots.noarchive);
+        }
+        deep.assert_hits(0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_page_budget_is_exact() {
+        let server = httpmock::MockServer::start();
+        let links: String = (0..20)
+            .map(|i| format!("<a href=\"/page/{i}\">{i}</a>"))
+            .collect();
+        server.mock(|when, then| {
+            when.path("/");
+            then.status(200)
+                .body(format!("<html><body>{links}</body></html>"));
+        });
+        // Slow pages keep every fetcher busy at once.
+        let pages = server.mock(|when, then| {
+            when.path_contains("/page/");
+            then.status(200)
+                .delay(Duration::from_millis(200))
+                .body("<html></html>");
+        });
+
+        let store = Arc::new(MemoryStore::default());
+        let config = PipelineConfig {
+            fetchers: 4,
+            ..Default::default()
+        };
+        let (pipeline, state) = test_pipeline(&server, config, store.clone()).await;
+        state
+            .set_budget(Arc::new(CrawlBudget::new(BudgetConfig {
+                max_pages: Some(5),
+                ..Default::default()
+            })))
+            .unwrap();
+        let outcome = pipeline.run(&CancellationToken::new(), None).await.unwrap();
+        assert_eq!(
+            outcome.stop,
+            PipelineStop::Budget(BudgetExhausted::Pages(5))
+        );
+        pages.assert_hits(4);
+        assert_eq!(store.entries.lock().unwrap().len(), 5);
+        assert_eq!(state.budget().unwrap().pages(), 5);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_canonicals_collapse_duplicates() {
+        let server = httpmock::MockServer::start();
+        let page = |path: &'static str, body: &'static str| {
+            server.mock(move |when, then| {
+                when.path(path);
+                then.status(200).body(body);
+            })
+        };
+        page(
+            "/",
+            "<a href=\"/a\">a</a><a href=\"/b\">b</a><a href=\"/c\">c</a>",
+        );
+        page("/a", "<link rel=\"canonical\" href=\"/article\">");
+        server.mock(|when, then| {
+            when.path("/b");
+            then.status(200)
+                .header("Link", "</article>; rel=\"canonical\"")
+                .body("<html></html>");
+        });
+        let article = page(
+            "/article",
+            "<link rel=\"alternate\" hreflang=\"de\" href=\"/de/article\">",
+        );
+        page("/de/article", "<html></html>");
+        page(
+            "/c",
+            "<meta http-equiv=\"refresh\" content=\"0;url=/d\">\
+             <link rel=\"canonical\" href=\"/e\">",
+        );
+        page("/d", "<html></html>");
+        page("/e", "<html></html>");
+
+        let store = Arc::new(MemoryStore::default());
+        let (pipeline, _) = test_pipeline(&server, PipelineConfig::default(), store.clone()).await;
+        let outcome = pipeline.run(&CancellationToken::new(), None).await.unwrap();
+        assert_eq!(outcome.stop, PipelineStop::Finished);
+
+        let entries = store.entries.lock().unwrap();
+        let mut stored: Vec<_> = entries
+            .iter()
+            .map(|entry| entry.url.trim_start_matches(&server.url("")))
+            .collect();
+        stored.sort();
+        // `/a` and `/b` are stored only as their canonical `/article`.
+        assert_eq!(
+            stored,
+            vec!["/", "/article", "/c", "/d", "/de/article", "/e"]
+        );
+        article.assert_hits(1);
+
+        let entry = |path: &str| {
+            entries
+                .iter()
+                .find(|entry| entry.url == server.url(path))
+                .unwrap()
+        };
+        assert_eq!(
+            entry("/article").relations.alternates[0].url,
+            server.url("/de/article")
+        );
+        assert!(entry("/c").canonical_conflict);
+        assert!(!entry("/article").canonical_conflict);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_unreachable_and_cyclic_canonicals() {
+        let server = httpmock::MockServer::start();
+        let page = |path: &'static str, body: &'static str| {
+            server.mock(move |when, then| {
+                when.path(path);
+                then.status(200).body(body);
+            })
+        };
+        page("/", "<a href=\"/a\">a</a><a href=\"/x\">x</a>");
+        page("/a", "<a href=\"/b\">b</a>");
+        page("/b", "<link rel=\"canonical\" href=\"/article\">");
+        let article = page("/article", "<html></html>");
+        page("/x", "<link rel=\"canonical\" href=\"/y\">");
+        page("/y", "<link rel=\"canonical\" href=\"/x\">");
+
+        let store = Arc::new(MemoryStore::default());
+        let (pipeline, state) =
+            test_pipeline(&server, PipelineConfig::default(), store.clone()).await;
+        // `/article` would be at depth 3, past the budget.
+        state
+            .set_budget(Arc::new(CrawlBudget::new(BudgetConfig {
+                per_domain: DomainBudget {
+                    max_depth: Some(2),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })))
+            .unwrap();
+        let outcome = pipeline.run(&CancellationToken::new(), None).await.unwrap();
+        assert_eq!(outcome.stop, PipelineStop::Finished);
+
+        let entries = store.entries.lock().unwrap();
+        let mut stored: Vec<_> = entries
+            .iter()
+            .map(|entry| entry.url.trim_start_matches(&server.url("")))
+            .collect();
+        stored.sort();
+        // `/b` keeps its own URL since its canonical is never fetched. `/x`
+        // gives way to `/y`, which names `/x` back and so is kept.
+        assert_eq!(stored, vec!["/", "/a", "/b", "/y"]);
+        article.assert_hits(0);
+        let b = entries
+            .iter()
+            .find(|entry| entry.url == server.url("/b"))
+            .unwrap();
+        assert_eq!(b.relations.canonical, Some(server.url("/article")));
+        assert!(!state.is_visited(&server.url("/article")).unwrap());
+    }
+}
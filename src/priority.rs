@@ -0,0 +1,305 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::task::Task;
+
+/// Crawl priority of a task; higher values are fetched first. Wraps an `f64`
+/// with a total order so it can live inside `Task` and the frontier heaps.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Priority(pub f64);
+
+impl PartialEq for Priority {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Priority {}
+
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Priority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Hash for Priority {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+/// Assigns priorities to newly discovered links.
+///
+/// `parent` is the page the links were found on, or `None` for seeds. The
+/// returned score for a URL replaces its queued priority if it is higher, so
+/// stateful scorers can raise a URL each time it is rediscovered.
+pub trait PriorityScorer: fmt::Debug + Send + Sync {
+    fn score_links(&self, parent: Option<&Task>, links: &[String]) -> Vec<f64>;
+
+    /// What the scorer has learned so far, for checkpoints. Stateless
+    /// scorers have nothing to save.
+    fn state(&self) -> Option<ScorerState> {
+        None
+    }
+
+    /// Continues from a state saved by `state`. States of another kind of
+    /// scorer are ignored.
+    fn restore(&self, _state: &ScorerState) {}
+}
+
+/// Saved state of a stateful scorer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScorerState {
+    Opic { cash: HashMap<String, f64> },
+    Backlinks { counts: HashMap<String, u64> },
+}
+
+/// Breadth-first order: shallower links first.
+#[derive(Debug, Default)]
+pub struct DepthScorer;
+
+impl PriorityScorer for DepthScorer {
+    fn score_links(&self, parent: Option<&Task>, links: &[String]) -> Vec<f64> {
+        let depth = parent.map_or(0, |parent| parent.depth() + 1);
+        vec![-(depth as f64); links.len()]
+    }
+}
+
+/// Online Page Importance Computation: every page starts with cash, and
+/// fetching a page splits its cash evenly between its out-links. A link's
+/// priority is the cash it has accumulated so far.
+#[derive(Debug, Default)]
+pub struct OpicScorer {
+    cash: Mutex<HashMap<String, f64>>,
+}
+
+const OPIC_SEED_CASH: f64 = 1.0;
+
+impl PriorityScorer for OpicScorer {
+    fn score_links(&self, parent: Option<&Task>, links: &[String]) -> Vec<f64> {
+        let Ok(mut cash) = self.cash.lock() else {
+            return vec![0.0; links.len()];
+        };
+        let share = match parent {
+            Some(parent) => {
+                let parent_cash = cash
+                    .insert(parent.url().to_string(), 0.0)
+                    .unwrap_or(OPIC_SEED_CASH);
+                parent_cash / links.len().max(1) as f64
+            }
+            None => OPIC_SEED_CASH,
+        };
+        links
+            .iter()
+            .map(|link| {
+                let total = cash.entry(link.clone()).or_insert(0.0);
+                *total += share;
+                *total
+            })
+            .collect()
+    }
+
+    fn state(&self) -> Option<ScorerState> {
+        let cash = self.cash.lock().ok()?.clone();
+        Some(ScorerState::Opic { cash })
+    }
+
+    fn restore(&self, state: &ScorerState) {
+        if let (ScorerState::Opic { cash }, Ok(mut current)) = (state, self.cash.lock()) {
+            *current = cash.clone();
+        }
+    }
+}
+
+/// Ranks URLs by how many inbound links to them the crawl has seen.
+#[derive(Debug, Default)]
+pub struct BacklinkScorer {
+    counts: Mutex<HashMap<String, u64>>,
+}
+
+impl PriorityScorer for BacklinkScorer {
+    fn score_links(&self, parent: Option<&Task>, links: &[String]) -> Vec<f64> {
+        let Ok(mut counts) = self.counts.lock() else {
+            return vec![0.0; links.len()];
+        };
+        links
+            .iter()
+            .map(|link| {
+                let count = counts.entry(link.clone()).or_insert(0);
+                if parent.is_some() {
+                    *count += 1;
+                }
+                *count as f64
+            })
+            .collect()
+    }
+
+    fn state(&self) -> Option<ScorerState> {
+        let counts = self.counts.lock().ok()?.clone();
+        Some(ScorerState::Backlinks { counts })
+    }
+
+    fn restore(&self, state: &ScorerState) {
+        if let (ScorerState::Backlinks { counts }, Ok(mut current)) = (state, self.counts.lock()) {
+            *current = counts.clone();
+        }
+    }
+}
+
+/// Scores each link with a user closure taking the URL and its depth.
+pub struct FnScorer<F>(pub F);
+
+impl<F> fmt::Debug for FnScorer<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FnScorer")
+    }
+}
+
+impl<F> PriorityScorer for FnScorer<F>
+where
+    F: Fn(&str, u32) -> f64 + Send + Sync,
+{
+    fn score_links(&self, parent: Option<&Task>, links: &[String]) -> Vec<f64> {
+        let depth = parent.map_or(0, |parent| parent.depth() + 1);
+        links.iter().map(|link| (self.0)(link, depth)).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScorerKind {
+    #[default]
+    BfsDepth,
+    Opic,
+    Backlinks,
+}
+
+impl ScorerKind {
+    pub fn build(self) -> Box<dyn PriorityScorer> {
+        match self {
+            ScorerKind::BfsDepth => Box::new(DepthScorer),
+            ScorerKind::Opic => Box::new(OpicScorer::default()),
+            ScorerKind::Backlinks => Box::new(BacklinkScorer::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(urls: &[&str]) -> Vec<String> {
+        urls.iter().map(|url| url.to_string()).collect()
+    }
+
+    #[test]
+    fn test_priority_ordering() {
+        assert!(Priority(2.0) > Priority(1.0));
+        assert!(Priority(-1.0) < Priority(0.0));
+        assert_eq!(Priority(0.5), Priority(0.5));
+    }
+
+    #[test]
+    fn test_depth_scorer() {
+        let seed = Task::new("https://example.com/".to_string());
+        let child = Task::with_depth("https://example.com/a".to_string(), 1);
+        let scorer = DepthScorer;
+        assert_eq!(scorer.score_links(None, &links(&["a"])), vec![0.0]);
+        assert_eq!(scorer.score_links(Some(&seed), &links(&["b"])), vec![-1.0]);
+        assert_eq!(scorer.score_links(Some(&child), &links(&["c"])), vec![-2.0]);
+    }
+
+    #[test]
+    fn test_opic_scorer() {
+        let scorer = OpicScorer::default();
+        let seed = Task::new("https://example.com/".to_string());
+        assert_eq!(
+            scorer.score_links(None, &links(&["https://example.com/"])),
+            vec![1.0]
+        );
+
+        let scores = scorer.score_links(
+            Some(&seed),
+            &links(&["https://example.com/a", "https://example.com/b"]),
+        );
+        assert_eq!(scores, vec![0.5, 0.5]);
+
+        // Cash flowing in from another page raises the shared target.
+        let a = Task::with_depth("https://example.com/a".to_string(), 1);
+        let scores = scorer.score_links(Some(&a), &links(&["https://example.com/b"]));
+        assert_eq!(scores, vec![1.0]);
+    }
+
+    #[test]
+    fn test_backlink_scorer() {
+        let scorer = BacklinkScorer::default();
+        let a = Task::new("https://example.com/a".to_string());
+        let b = Task::new("https://example.com/b".to_string());
+        assert_eq!(
+            scorer.score_links(Some(&a), &links(&["x", "y"])),
+            vec![1.0, 1.0]
+        );
+        assert_eq!(scorer.score_links(Some(&b), &links(&["x"])), vec![2.0]);
+    }
+
+    #[test]
+    fn test_scorer_state_round_trip() {
+        let scorer = OpicScorer::default();
+        let seed = Task::new("https://example.com/".to_string());
+        scorer.score_links(None, &links(&["https://example.com/"]));
+        scorer.score_links(Some(&seed), &links(&["https://example.com/a"]));
+        let state = scorer.state().unwrap();
+        let json = serde_json::to_string(&state).unwrap();
+        let state: ScorerState = serde_json::from_str(&json).unwrap();
+
+        let resumed = OpicScorer::default();
+        resumed.restore(&state);
+        assert_eq!(resumed.state(), scorer.state());
+        // The seed's cash was spent, so a second visit passes nothing on.
+        assert_eq!(
+            resumed.score_links(Some(&seed), &links(&["https://example.com/b"])),
+            vec![0.0]
+        );
+
+        // Another scorer's state is ignored.
+        let backlinks = BacklinkScorer::default();
+        backlinks.restore(&state);
+        assert_eq!(
+            backlinks.state(),
+            Some(ScorerState::Backlinks {
+                counts: HashMap::new()
+            })
+        );
+        assert_eq!(DepthScorer.state(), None);
+    }
+
+    #[test]
+    fn test_fn_scorer() {
+        let scorer = FnScorer(|url: &str, depth: u32| {
+            if url.contains("/products/") {
+                10.0 - depth as f64
+            } else {
+                0.0
+            }
+        });
+        assert_eq!(
+            scorer.score_links(
+                None,
+                &links(&["https://shop.com/products/1", "https://shop.com/about"])
+            ),
+            vec![10.0, 0.0]
+        );
+    }
+}
@@ -0,0 +1,437 @@
+use std::collections::HashSet;
+use std::fs;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::error::CrawlerError;
+use crate::modules::parser::parser::{Link, LinkKind};
+
+const PUBLIC_SUFFIX_LIST: &str = include_str!("data/public_suffix_list.dat");
+
+static SUFFIX_LIST: OnceLock<PublicSuffixList> = OnceLock::new();
+
+fn get_suffix_list() -> &'static PublicSuffixList {
+    SUFFIX_LIST.get_or_init(|| PublicSuffixList::parse(PUBLIC_SUFFIX_LIST))
+}
+
+/// Rules from the embedded Public Suffix List.
+#[derive(Debug, Default)]
+struct PublicSuffixList {
+    rules: HashSet<String>,
+    wildcards: HashSet<String>,
+    exceptions: HashSet<String>,
+}
+
+impl PublicSuffixList {
+    fn parse(content: &str) -> Self {
+        let mut list = Self::default();
+        for line in content.lines() {
+            let rule = line.split_whitespace().next().unwrap_or("");
+            if rule.is_empty() || rule.starts_with("//") {
+                continue;
+            }
+            if let Some(exception) = rule.strip_prefix('!') {
+                list.exceptions.insert(to_ascii(exception));
+            } else if let Some(parent) = rule.strip_prefix("*.") {
+                list.wildcards.insert(to_ascii(parent));
+            } else {
+                list.rules.insert(to_ascii(rule));
+            }
+        }
+        list
+    }
+
+    /// Number of labels in the public suffix of `labels`, following the PSL
+    /// algorithm: exceptions win, otherwise the longest matching rule, with
+    /// `*` as the implicit default.
+    fn suffix_len(&self, labels: &[&str]) -> usize {
+        let mut longest = 1;
+        for start in (0..labels.len()).rev() {
+            let candidate = labels[start..].join(".");
+            let len = labels.len() - start;
+            if self.exceptions.contains(&candidate) {
+                return len - 1;
+            }
+            if self.rules.contains(&candidate) {
+                longest = longest.max(len);
+            }
+            if start > 0 && self.wildcards.contains(&candidate) {
+                longest = longest.max(len + 1);
+            }
+        }
+        longest
+    }
+}
+
+/// Hosts reach us in punycode while the list spells IDN rules in Unicode.
+fn to_ascii(rule: &str) -> String {
+    idna::domain_to_ascii(rule).unwrap_or_else(|_| rule.to_lowercase())
+}
+
+/// Returns the registrable domain ("eTLD+1") of `host`, e.g. `example.co.uk`
+/// for `www.example.co.uk`. IP addresses and bare public suffixes have none.
+pub fn registrable_domain(host: &str) -> Option<String> {
+    let host = host.trim_end_matches('.').to_lowercase();
+    if host.is_empty() || host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[') {
+        return None;
+    }
+    let labels: Vec<&str> = host.split('.').collect();
+    let suffix_len = get_suffix_list().suffix_len(&labels);
+    if labels.len() <= suffix_len {
+        return None;
+    }
+    Some(labels[labels.len() - suffix_len - 1..].join("."))
+}
+
+pub(crate) fn is_same_or_subdomain(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScopeMode {
+    /// Follow every valid http(s) link.
+    #[default]
+    Any,
+    /// Only the seed's exact host.
+    ExactHost,
+    /// The seed's host and any of its subdomains.
+    HostAndSubdomains,
+    /// Any host sharing the seed's registrable domain.
+    RegistrableDomain,
+    /// The seed's host, restricted to URLs under `path_prefix`.
+    PathPrefix,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScopeConfig {
+    pub mode: ScopeMode,
+    /// Prefix for `ScopeMode::PathPrefix`; defaults to the seed's directory.
+    pub path_prefix: Option<String>,
+    /// If non-empty, URLs must match at least one of these regexes.
+    pub include: Vec<String>,
+    /// URLs matching any of these regexes are out of scope.
+    pub exclude: Vec<String>,
+    /// File with one domain per line that is always in scope.
+    pub allow_domains_file: Option<String>,
+    /// File with one domain per line that is never in scope.
+    pub deny_domains_file: Option<String>,
+    /// Kinds of links the crawler follows; the others are only recorded.
+    pub follow: Vec<LinkKind>,
+    /// Record `rel="nofollow"` links without following them.
+    pub respect_nofollow: bool,
+}
+
+impl Default for ScopeConfig {
+    fn default() -> Self {
+        Self {
+            mode: ScopeMode::default(),
+            path_prefix: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            allow_domains_file: None,
+            deny_domains_file: None,
+            follow: vec![LinkKind::Navigation, LinkKind::Redirect],
+            respect_nofollow: false,
+        }
+    }
+}
+
+/// Decides which discovered links the crawler may fetch. Links outside the
+/// scope are still reported as external links, they are just never queued.
+#[derive(Debug, Clone)]
+pub struct CrawlScope {
+    mode: ScopeMode,
+    seed_host: String,
+    seed_domain: Option<String>,
+    path_prefix: String,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    allow_domains: Vec<String>,
+    deny_domains: Vec<String>,
+    follow: Vec<LinkKind>,
+    respect_nofollow: bool,
+}
+
+impl CrawlScope {
+    pub fn new(config: &ScopeConfig, seed: &Url) -> Result<Self, CrawlerError> {
+        let seed_host = seed
+            .host_str()
+            .ok_or(CrawlerError::InvalidDomain)?
+            .to_lowercase();
+        let path_prefix = config.path_prefix.clone().unwrap_or_else(|| {
+            let path = seed.path();
+            path[..=path.rfind('/').unwrap_or(0)].to_string()
+        });
+        Ok(Self {
+            mode: config.mode,
+            seed_domain: registrable_domain(&seed_host),
+            seed_host,
+            path_prefix,
+            include: compile_patterns(&config.include)?,
+            exclude: compile_patterns(&config.exclude)?,
+            allow_domains: read_domains_file(config.allow_domains_file.as_deref())?,
+            deny_domains: read_domains_file(config.deny_domains_file.as_deref())?,
+            follow: config.follow.clone(),
+            respect_nofollow: config.respect_nofollow,
+        })
+    }
+
+    pub fn is_in_scope(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host.to_lowercase(),
+            None => return false,
+        };
+        if self
+            .deny_domains
+            .iter()
+            .any(|domain| is_same_or_subdomain(&host, domain))
+        {
+            return false;
+        }
+        if self.exclude.iter().any(|re| re.is_match(url.as_str())) {
+            return false;
+        }
+        if !self.include.is_empty() && !self.include.iter().any(|re| re.is_match(url.as_str())) {
+            return false;
+        }
+        let allowed = self
+            .allow_domains
+            .iter()
+            .any(|domain| is_same_or_subdomain(&host, domain));
+        allowed || self.matches_mode(&host, url)
+    }
+
+    fn matches_mode(&self, host: &str, url: &Url) -> bool {
+        match self.mode {
+            ScopeMode::Any => true,
+            ScopeMode::ExactHost => host == self.seed_host,
+            ScopeMode::HostAndSubdomains => is_same_or_subdomain(host, &self.seed_host),
+            ScopeMode::RegistrableDomain => match &self.seed_domain {
+                Some(domain) => registrable_domain(host).as_ref() == Some(domain),
+                None => host == self.seed_host,
+            },
+            ScopeMode::PathPrefix => host == self.seed_host && self.under_prefix(url.path()),
+        }
+    }
+
+    /// Whether `path` is `path_prefix` or below it, so `/docs` admits
+    /// `/docs/guide` but not `/docs-old`.
+    fn under_prefix(&self, path: &str) -> bool {
+        let prefix = &self.path_prefix;
+        match path.strip_prefix(prefix.as_str()) {
+            Some(rest) => prefix.ends_with('/') || rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        }
+    }
+
+    /// Whether the crawl policy follows `link` at all, wherever it points.
+    pub fn follows(&self, link: &Link) -> bool {
+        self.follow.contains(&link.kind) && !(self.respect_nofollow && link.is_nofollow())
+    }
+
+    /// Splits `links` into the ones to crawl and the external ones to only
+    /// record. Unparseable links count as external.
+    pub fn partition(&self, links: Vec<String>) -> (Vec<String>, Vec<String>) {
+        links.into_iter().partition(|link| {
+            Url::parse(link)
+                .map(|url| self.is_in_scope(&url))
+                .unwrap_or(false)
+        })
+    }
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Regex>, CrawlerError> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern)
+                .map_err(|e| CrawlerError::Other(format!("Invalid scope pattern {pattern}: {e}")))
+        })
+        .collect()
+}
+
+fn read_domains_file(path: Option<&str>) -> Result<Vec<String>, CrawlerError> {
+    let Some(path) = path else {
+        return Ok(Vec::new());
+    };
+    let content = fs::read_to_string(path).map_err(CrawlerError::IoError)?;
+    Ok(content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.trim_start_matches("*.")
+                .trim_start_matches('.')
+                .to_lowercase()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(mode: ScopeMode, seed: &str) -> CrawlScope {
+        let config = ScopeConfig {
+            mode,
+            ..Default::default()
+        };
+        CrawlScope::new(&config, &Url::parse(seed).unwrap()).unwrap()
+    }
+
+    fn in_scope(scope: &CrawlScope, url: &str) -> bool {
+        scope.is_in_scope(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_registrable_domain() {
+        assert_eq!(
+            registrable_domain("www.example.com").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(
+            registrable_domain("a.b.example.co.uk").as_deref(),
+            Some("example.co.uk")
+        );
+        assert_eq!(
+            registrable_domain("user.github.io").as_deref(),
+            Some("user.github.io")
+        );
+        assert_eq!(
+            registrable_domain("shop.foo.kawasaki.jp").as_deref(),
+            Some("shop.foo.kawasaki.jp")
+        );
+        assert_eq!(
+            registrable_domain("www.city.kawasaki.jp").as_deref(),
+            Some("city.kawasaki.jp")
+        );
+        assert_eq!(
+            registrable_domain("shop.nabari.mie.jp").as_deref(),
+            Some("shop.nabari.mie.jp")
+        );
+        assert_eq!(
+            registrable_domain("docs.project.pages.dev").as_deref(),
+            Some("project.pages.dev")
+        );
+        // 公司.cn, as hosts arrive in punycode.
+        assert_eq!(
+            registrable_domain("www.example.xn--55qx5d.cn").as_deref(),
+            Some("example.xn--55qx5d.cn")
+        );
+        assert_eq!(registrable_domain("co.uk"), None);
+        assert_eq!(registrable_domain("gov.kg"), None);
+        assert_eq!(registrable_domain("127.0.0.1"), None);
+    }
+
+    #[test]
+    fn test_host_modes() {
+        let exact = scope(ScopeMode::ExactHost, "https://blog.example.com/");
+        assert!(in_scope(&exact, "https://blog.example.com/post"));
+        assert!(!in_scope(&exact, "https://www.example.com/"));
+
+        let subdomains = scope(ScopeMode::HostAndSubdomains, "https://example.com/");
+        assert!(in_scope(&subdomains, "https://docs.example.com/"));
+        assert!(!in_scope(&subdomains, "https://notexample.com/"));
+
+        let domain = scope(ScopeMode::RegistrableDomain, "https://www.example.co.uk/");
+        assert!(in_scope(&domain, "https://shop.example.co.uk/cart"));
+        assert!(!in_scope(&domain, "https://other.co.uk/"));
+
+        let any = scope(ScopeMode::Any, "https://example.com/");
+        assert!(in_scope(&any, "https://anything.org/"));
+    }
+
+    #[test]
+    fn test_follow_policy() {
+        let html = r#"<a href="/a">a</a><a href="/b" rel="nofollow">b</a><img src="/c.png">"#;
+        let links = crate::modules::parser::parser::document_links(
+            &select::document::Document::from(html),
+            "https://example.com/",
+        )
+        .unwrap();
+        let seed = Url::parse("https://example.com/").unwrap();
+        let followed = |config: &ScopeConfig| -> Vec<usize> {
+            let scope = CrawlScope::new(config, &seed).unwrap();
+            links
+                .iter()
+                .filter(|link| scope.follows(link))
+                .map(|link| link.position)
+                .collect()
+        };
+
+        assert_eq!(followed(&ScopeConfig::default()), vec![0, 1]);
+        let polite = ScopeConfig {
+            respect_nofollow: true,
+            ..Default::default()
+        };
+        assert_eq!(followed(&polite), vec![0]);
+        let assets = ScopeConfig {
+            follow: vec![LinkKind::Asset],
+            ..Default::default()
+        };
+        assert_eq!(followed(&assets), vec![2]);
+    }
+
+    #[test]
+    fn test_path_prefix() {
+        let prefix = scope(ScopeMode::PathPrefix, "https://example.com/docs/index.html");
+        assert!(in_scope(&prefix, "https://example.com/docs/guide"));
+        assert!(!in_scope(&prefix, "https://example.com/blog/"));
+        assert!(!in_scope(&prefix, "https://docs.example.com/docs/guide"));
+
+        let config = ScopeConfig {
+            mode: ScopeMode::PathPrefix,
+            path_prefix: Some("/docs".to_string()),
+            ..Default::default()
+        };
+        let prefix =
+            CrawlScope::new(&config, &Url::parse("https://example.com/docs").unwrap()).unwrap();
+        assert!(in_scope(&prefix, "https://example.com/docs"));
+        assert!(in_scope(&prefix, "https://example.com/docs/guide"));
+        assert!(!in_scope(&prefix, "https://example.com/docs-old/guide"));
+        assert!(!in_scope(&prefix, "https://example.com/docsearch"));
+    }
+
+    #[test]
+    fn test_include_exclude_and_domain_lists() {
+        let dir = std::env::temp_dir().join(format!("thamur-scope-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let allow = dir.join("allow.txt");
+        let deny = dir.join("deny.txt");
+        fs::write(&allow, "# partners\ncdn.partner.net\n").unwrap();
+        fs::write(&deny, "private.example.com\n").unwrap();
+
+        let config = ScopeConfig {
+            mode: ScopeMode::HostAndSubdomains,
+            include: vec![r"^https://".to_string()],
+            exclude: vec![r"\.pdf$".to_string()],
+            allow_domains_file: Some(allow.to_string_lossy().to_string()),
+            deny_domains_file: Some(deny.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let scope = CrawlScope::new(&config, &Url::parse("https://example.com/").unwrap()).unwrap();
+
+        assert!(in_scope(&scope, "https://www.example.com/page"));
+        assert!(in_scope(&scope, "https://cdn.partner.net/lib.js"));
+        assert!(!in_scope(&scope, "http://www.example.com/page"));
+        assert!(!in_scope(&scope, "https://example.com/report.pdf"));
+        assert!(!in_scope(&scope, "https://a.private.example.com/"));
+
+        let (internal, external) = scope.partition(vec![
+            "https://example.com/a".to_string(),
+            "https://elsewhere.org/".to_string(),
+        ]);
+        assert_eq!(internal, vec!["https://example.com/a"]);
+        assert_eq!(external, vec!["https://elsewhere.org/"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,706 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use dashmap::DashSet;
+use serde::{Deserialize, Serialize};
+
+use crate::error::CrawlerError;
+
+const MAGIC: &[u8; 8] = b"THSEEN02";
+const KIND_FINGERPRINT: u8 = 1;
+const KIND_BLOOM: u8 = 2;
+const KIND_EXACT: u8 = 3;
+
+/// Independently locked Bloom filters; a URL always maps to the same one.
+const BLOOM_SHARDS: usize = 16;
+/// Each new Bloom slice holds this many times the previous one's URLs...
+const BLOOM_GROWTH: usize = 2;
+/// ...with this fraction of its false-positive rate, so the compound rate
+/// stays below the configured one however far the filter grows.
+const BLOOM_TIGHTENING: f64 = 0.5;
+
+/// Stable 128-bit FNV-1a hash of a URL. Unlike `DefaultHasher` it does not
+/// change between Rust releases, so persisted sets stay valid.
+pub fn fingerprint(url: &str) -> u128 {
+    const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+    url.bytes().fold(OFFSET, |hash, byte| {
+        (hash ^ u128::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeenKind {
+    /// Exact up to 128-bit fingerprint collisions, at 16 bytes per URL.
+    #[default]
+    Fingerprint,
+    /// Scalable Bloom filter: fixed bits per URL, tunable false positives.
+    Bloom,
+    /// Every URL in full: the most memory, but the URLs can be listed.
+    Exact,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeenConfig {
+    pub kind: SeenKind,
+    /// Target false-positive rate of the Bloom filter. A false positive means
+    /// a never-fetched URL is skipped.
+    pub false_positive_rate: f64,
+    /// URLs the first Bloom slice is sized for; later slices double.
+    pub initial_capacity: usize,
+    /// File the set is saved to on flush and loaded from at startup.
+    pub path: Option<String>,
+}
+
+impl Default for SeenConfig {
+    fn default() -> Self {
+        Self {
+            kind: SeenKind::Fingerprint,
+            false_positive_rate: 0.001,
+            initial_capacity: 1_000_000,
+            path: None,
+        }
+    }
+}
+
+impl SeenConfig {
+    /// Builds the configured set, resuming from `path` if it exists. A saved
+    /// set of another kind is refused: it cannot be converted, since only
+    /// the exact set keeps its URLs.
+    pub fn build(&self) -> Result<Box<dyn SeenSet>, CrawlerError> {
+        if let Some(path) = &self.path {
+            if Path::new(path).exists() {
+                let set = load(Path::new(path))?;
+                let saved = set.stats().kind;
+                if saved != self.kind {
+                    return Err(CrawlerError::Other(format!(
+                        "{path} holds a {saved:?} visited set but {:?} is configured; \
+                         remove the file or configure {saved:?}",
+                        self.kind
+                    )));
+                }
+                tracing::info!("Resumed {} visited URLs from {}", set.len(), path);
+                return Ok(set);
+            }
+        }
+        Ok(match self.kind {
+            SeenKind::Fingerprint => Box::new(FingerprintSet::default()),
+            SeenKind::Bloom => Box::new(BloomFilter::new(
+                self.initial_capacity,
+                self.false_positive_rate,
+            )),
+            SeenKind::Exact => Box::new(UrlSet::default()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SeenStats {
+    pub kind: SeenKind,
+    pub len: usize,
+    pub memory_bytes: usize,
+    /// Probability that an unseen URL is reported as seen.
+    pub false_positive_rate: f64,
+}
+
+impl fmt::Display for SeenStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} set: {} URLs in {:.1} MiB (false-positive rate {:.2e})",
+            self.kind,
+            self.len,
+            self.memory_bytes as f64 / (1024.0 * 1024.0),
+            self.false_positive_rate
+        )
+    }
+}
+
+/// The set of URLs the crawler has already fetched. Shared between workers,
+/// so every method takes `&self`.
+pub trait SeenSet: fmt::Debug + Send + Sync {
+    /// Adds `url`; returns whether it was not seen before. Test and insert
+    /// are one atomic step, so concurrent callers never both get `true`.
+    fn insert(&self, url: &str) -> bool;
+    fn contains(&self, url: &str) -> bool;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn stats(&self) -> SeenStats;
+    /// The URLs in the set, if it keeps them.
+    fn urls(&self) -> Option<Vec<String>> {
+        None
+    }
+    /// Writes the set in the format read by `load`.
+    fn write_to(&self, writer: &mut dyn Write) -> io::Result<()>;
+}
+
+/// Saves `set` to `path`, replacing the previous file atomically.
+pub fn save(set: &dyn SeenSet, path: &Path) -> Result<(), CrawlerError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(CrawlerError::IoError)?;
+    }
+    let tmp = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&tmp).map_err(CrawlerError::IoError)?);
+    set.write_to(&mut writer).map_err(CrawlerError::IoError)?;
+    writer.flush().map_err(CrawlerError::IoError)?;
+    drop(writer);
+    fs::rename(&tmp, path).map_err(CrawlerError::IoError)
+}
+
+/// Reads a set written by `save`, whatever its kind.
+pub fn load(path: &Path) -> Result<Box<dyn SeenSet>, CrawlerError> {
+    let mut reader = BufReader::new(File::open(path).map_err(CrawlerError::IoError)?);
+    let mut magic = [0; 8];
+    reader
+        .read_exact(&mut magic)
+        .map_err(CrawlerError::IoError)?;
+    if &magic != MAGIC {
+        return Err(CrawlerError::Other(format!(
+            "{} is not a visited-set file",
+            path.display()
+        )));
+    }
+    let set: Box<dyn SeenSet> = match read_u8(&mut reader)? {
+        KIND_FINGERPRINT => Box::new(FingerprintSet::read_from(&mut reader)?),
+        KIND_BLOOM => Box::new(BloomFilter::read_from(&mut reader)?),
+        KIND_EXACT => Box::new(UrlSet::read_from(&mut reader)?),
+        kind => {
+            return Err(CrawlerError::Other(format!(
+                "Unknown visited-set kind {kind} in {}",
+                path.display()
+            )))
+        }
+    };
+    Ok(set)
+}
+
+fn read_u8(reader: &mut impl Read) -> Result<u8, CrawlerError> {
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf).map_err(CrawlerError::IoError)?;
+    Ok(buf[0])
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64, CrawlerError> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf).map_err(CrawlerError::IoError)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_u128(reader: &mut impl Read) -> Result<u128, CrawlerError> {
+    let mut buf = [0; 16];
+    reader.read_exact(&mut buf).map_err(CrawlerError::IoError)?;
+    Ok(u128::from_le_bytes(buf))
+}
+
+/// Exact set of full URLs, sharded like `FingerprintSet`.
+#[derive(Debug, Default)]
+pub struct UrlSet {
+    urls: DashSet<String>,
+}
+
+impl UrlSet {
+    fn read_from(reader: &mut impl Read) -> Result<Self, CrawlerError> {
+        let len = read_u64(reader)? as usize;
+        let urls = DashSet::with_capacity(len);
+        for _ in 0..len {
+            let mut url = vec![0; read_u64(reader)? as usize];
+            reader.read_exact(&mut url).map_err(CrawlerError::IoError)?;
+            let url = String::from_utf8(url)
+                .map_err(|e| CrawlerError::Other(format!("Invalid visited URL: {e}")))?;
+            urls.insert(url);
+        }
+        Ok(Self { urls })
+    }
+}
+
+impl SeenSet for UrlSet {
+    fn insert(&self, url: &str) -> bool {
+        self.urls.insert(url.to_string())
+    }
+
+    fn contains(&self, url: &str) -> bool {
+        self.urls.contains(url)
+    }
+
+    fn len(&self) -> usize {
+        self.urls.len()
+    }
+
+    fn stats(&self) -> SeenStats {
+        let bucket = std::mem::size_of::<String>() + 1;
+        let text: usize = self.urls.iter().map(|url| url.capacity()).sum();
+        SeenStats {
+            kind: SeenKind::Exact,
+            len: self.len(),
+            memory_bytes: self.urls.capacity() * bucket + text,
+            false_positive_rate: 0.0,
+        }
+    }
+
+    fn urls(&self) -> Option<Vec<String>> {
+        Some(self.urls.iter().map(|url| url.clone()).collect())
+    }
+
+    fn write_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[KIND_EXACT])?;
+        writer.write_all(&(self.urls.len() as u64).to_le_bytes())?;
+        for url in self.urls.iter() {
+            writer.write_all(&(url.len() as u64).to_le_bytes())?;
+            writer.write_all(url.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Exact set of URL fingerprints, sharded so workers rarely contend.
+#[derive(Debug, Default)]
+pub struct FingerprintSet {
+    fingerprints: DashSet<u128>,
+}
+
+impl FingerprintSet {
+    fn read_from(reader: &mut impl Read) -> Result<Self, CrawlerError> {
+        let len = read_u64(reader)? as usize;
+        let fingerprints = DashSet::with_capacity(len);
+        for _ in 0..len {
+            fingerprints.insert(read_u128(reader)?);
+        }
+        Ok(Self { fingerprints })
+    }
+}
+
+impl SeenSet for FingerprintSet {
+    fn insert(&self, url: &str) -> bool {
+        self.fingerprints.insert(fingerprint(url))
+    }
+
+    fn contains(&self, url: &str) -> bool {
+        self.fingerprints.contains(&fingerprint(url))
+    }
+
+    fn len(&self) -> usize {
+        self.fingerprints.len()
+    }
+
+    fn stats(&self) -> SeenStats {
+        // One control byte per bucket on top of the fingerprint itself.
+        let bucket = std::mem::size_of::<u128>() + 1;
+        SeenStats {
+            kind: SeenKind::Fingerprint,
+            len: self.len(),
+            memory_bytes: self.fingerprints.capacity() * bucket,
+            false_positive_rate: 0.0,
+        }
+    }
+
+    fn write_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[KIND_FINGERPRINT])?;
+        writer.write_all(&(self.fingerprints.len() as u64).to_le_bytes())?;
+        for fingerprint in self.fingerprints.iter() {
+            writer.write_all(&fingerprint.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// SplitMix64 finalizer. FNV spreads short suffix differences poorly across
+/// the bits, which double hashing would turn into correlated positions.
+fn mix64(mut x: u64) -> u64 {
+    x ^= x >> 30;
+    x = x.wrapping_mul(0xbf58476d1ce4e5b9);
+    x ^= x >> 27;
+    x = x.wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// One fixed-size Bloom filter within a `ScalableBloom`.
+#[derive(Debug, Clone, PartialEq)]
+struct BloomSlice {
+    bits: Vec<u64>,
+    hashes: u32,
+    capacity: usize,
+    len: usize,
+    false_positive_rate: f64,
+}
+
+impl BloomSlice {
+    fn new(capacity: usize, false_positive_rate: f64) -> Self {
+        let capacity = capacity.max(1);
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-(capacity as f64) * false_positive_rate.ln() / (ln2 * ln2)).ceil() as usize;
+        let hashes = (-false_positive_rate.log2()).ceil().max(1.0) as u32;
+        Self {
+            bits: vec![0; bits.div_ceil(64).max(1)],
+            hashes,
+            capacity,
+            len: 0,
+            false_positive_rate,
+        }
+    }
+
+    /// Bit positions for a fingerprint, by double hashing its two halves.
+    fn positions(&self, fingerprint: u128) -> impl Iterator<Item = usize> {
+        let bits = (self.bits.len() * 64) as u64;
+        let h1 = mix64(fingerprint as u64);
+        let h2 = mix64((fingerprint >> 64) as u64 ^ h1) | 1;
+        (0..u64::from(self.hashes))
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bits) as usize)
+    }
+
+    fn contains(&self, fingerprint: u128) -> bool {
+        self.positions(fingerprint)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    fn insert(&mut self, fingerprint: u128) {
+        let positions: Vec<usize> = self.positions(fingerprint).collect();
+        for bit in positions {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+        self.len += 1;
+    }
+}
+
+/// Scalable Bloom filter: when the newest slice reaches its capacity, a
+/// larger slice with a tighter false-positive rate is added, so memory grows
+/// with the crawl while the compound error stays bounded.
+#[derive(Debug, Clone, PartialEq)]
+struct ScalableBloom {
+    slices: Vec<BloomSlice>,
+}
+
+impl ScalableBloom {
+    fn new(initial_capacity: usize, false_positive_rate: f64) -> Self {
+        Self {
+            slices: vec![BloomSlice::new(
+                initial_capacity,
+                false_positive_rate * (1.0 - BLOOM_TIGHTENING),
+            )],
+        }
+    }
+
+    fn contains(&self, fingerprint: u128) -> bool {
+        self.slices.iter().any(|slice| slice.contains(fingerprint))
+    }
+
+    fn insert(&mut self, fingerprint: u128) -> bool {
+        if self.contains(fingerprint) {
+            return false;
+        }
+        let full = self
+            .slices
+            .last()
+            .filter(|slice| slice.len >= slice.capacity)
+            .map(|slice| (slice.capacity, slice.false_positive_rate));
+        if let Some((capacity, false_positive_rate)) = full {
+            self.slices.push(BloomSlice::new(
+                capacity * BLOOM_GROWTH,
+                false_positive_rate * BLOOM_TIGHTENING,
+            ));
+        }
+        if let Some(slice) = self.slices.last_mut() {
+            slice.insert(fingerprint);
+        }
+        true
+    }
+
+    fn read_from(reader: &mut impl Read) -> Result<Self, CrawlerError> {
+        let count = read_u64(reader)? as usize;
+        let mut slices = Vec::with_capacity(count);
+        for _ in 0..count {
+            let capacity = read_u64(reader)? as usize;
+            let len = read_u64(reader)? as usize;
+            let hashes = read_u64(reader)? as u32;
+            let slice_rate = f64::from_bits(read_u64(reader)?);
+            let words = read_u64(reader)? as usize;
+            let bits = (0..words)
+                .map(|_| read_u64(reader))
+                .collect::<Result<Vec<_>, _>>()?;
+            slices.push(BloomSlice {
+                bits,
+                hashes,
+                capacity,
+                len,
+                false_positive_rate: slice_rate,
+            });
+        }
+        if slices.is_empty() {
+            return Err(CrawlerError::Other(
+                "Bloom filter has no slices".to_string(),
+            ));
+        }
+        Ok(Self { slices })
+    }
+
+    fn write_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&(self.slices.len() as u64).to_le_bytes())?;
+        for slice in &self.slices {
+            writer.write_all(&(slice.capacity as u64).to_le_bytes())?;
+            writer.write_all(&(slice.len as u64).to_le_bytes())?;
+            writer.write_all(&u64::from(slice.hashes).to_le_bytes())?;
+            writer.write_all(&slice.false_positive_rate.to_bits().to_le_bytes())?;
+            writer.write_all(&(slice.bits.len() as u64).to_le_bytes())?;
+            for word in &slice.bits {
+                writer.write_all(&word.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Scalable Bloom filter split into independently locked shards, so that
+/// concurrent workers only contend when their URLs land on the same shard.
+#[derive(Debug)]
+pub struct BloomFilter {
+    shards: Vec<Mutex<ScalableBloom>>,
+    len: AtomicUsize,
+    false_positive_rate: f64,
+}
+
+impl BloomFilter {
+    pub fn new(initial_capacity: usize, false_positive_rate: f64) -> Self {
+        let false_positive_rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+        let shard_capacity = initial_capacity.div_ceil(BLOOM_SHARDS);
+        Self {
+            shards: (0..BLOOM_SHARDS)
+                .map(|_| Mutex::new(ScalableBloom::new(shard_capacity, false_positive_rate)))
+                .collect(),
+            len: AtomicUsize::new(0),
+            false_positive_rate,
+        }
+    }
+
+    /// The shard for a fingerprint. A poisoned shard is still a valid
+    /// filter, so the poison is ignored.
+    fn shard(&self, fingerprint: u128) -> std::sync::MutexGuard<'_, ScalableBloom> {
+        let index = mix64((fingerprint >> 64) as u64) as usize % self.shards.len();
+        self.shards[index]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn read_from(reader: &mut impl Read) -> Result<Self, CrawlerError> {
+        let false_positive_rate = f64::from_bits(read_u64(reader)?);
+        let count = read_u64(reader)? as usize;
+        let shards = (0..count)
+            .map(|_| ScalableBloom::read_from(reader).map(Mutex::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        if shards.is_empty() {
+            return Err(CrawlerError::Other(
+                "Bloom filter has no shards".to_string(),
+            ));
+        }
+        let len = shards
+            .iter()
+            .map(|shard| {
+                let shard = shard.lock().unwrap_or_else(PoisonError::into_inner);
+                shard.slices.iter().map(|slice| slice.len).sum::<usize>()
+            })
+            .sum();
+        Ok(Self {
+            shards,
+            len: AtomicUsize::new(len),
+            false_positive_rate,
+        })
+    }
+
+    /// Runs `f` on every shard in turn.
+    fn each_shard<T>(&self, mut f: impl FnMut(&ScalableBloom) -> T) -> Vec<T> {
+        self.shards
+            .iter()
+            .map(|shard| f(&shard.lock().unwrap_or_else(PoisonError::into_inner)))
+            .collect()
+    }
+}
+
+impl SeenSet for BloomFilter {
+    fn insert(&self, url: &str) -> bool {
+        let fingerprint = fingerprint(url);
+        let inserted = self.shard(fingerprint).insert(fingerprint);
+        if inserted {
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+        inserted
+    }
+
+    fn contains(&self, url: &str) -> bool {
+        let fingerprint = fingerprint(url);
+        self.shard(fingerprint).contains(fingerprint)
+    }
+
+    fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    fn stats(&self) -> SeenStats {
+        // A URL is checked against one shard, so the worst shard bounds the
+        // error.
+        let false_positive_rate = self
+            .each_shard(|shard| {
+                1.0 - shard
+                    .slices
+                    .iter()
+                    .map(|slice| 1.0 - slice.false_positive_rate)
+                    .product::<f64>()
+            })
+            .into_iter()
+            .fold(0.0, f64::max);
+        let memory_bytes = self
+            .each_shard(|shard| {
+                shard
+                    .slices
+                    .iter()
+                    .map(|slice| slice.bits.len() * 8)
+                    .sum::<usize>()
+            })
+            .into_iter()
+            .sum();
+        SeenStats {
+            kind: SeenKind::Bloom,
+            len: self.len(),
+            memory_bytes,
+            false_positive_rate,
+        }
+    }
+
+    fn write_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[KIND_BLOOM])?;
+        writer.write_all(&self.false_positive_rate.to_bits().to_le_bytes())?;
+        writer.write_all(&(self.shards.len() as u64).to_le_bytes())?;
+        for result in self.each_shard(|shard| shard.write_to(writer)) {
+            result?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir()
+            .join(format!("thamur-seen-{}", std::process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn test_fingerprint_set() {
+        let set = FingerprintSet::default();
+        assert!(set.insert("https://example.com/"));
+        assert!(!set.insert("https://example.com/"));
+        assert!(set.contains("https://example.com/"));
+        assert!(!set.contains("https://example.com/other"));
+        assert_eq!(set.stats().len, 1);
+        assert_ne!(fingerprint("a"), fingerprint("b"));
+    }
+
+    #[test]
+    fn test_url_set() {
+        // Not under `temp_path`, which `test_save_and_load` removes.
+        let path =
+            std::env::temp_dir().join(format!("thamur-seen-urls-{}.bin", std::process::id()));
+        let set = UrlSet::default();
+        assert!(set.insert("https://example.com/"));
+        assert!(!set.insert("https://example.com/"));
+        assert_eq!(set.urls(), Some(vec!["https://example.com/".to_string()]));
+        assert_eq!(FingerprintSet::default().urls(), None);
+
+        save(&set, &path).unwrap();
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.stats().kind, SeenKind::Exact);
+        assert!(loaded.contains("https://example.com/"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_bloom_filter_grows_within_error_bound() {
+        let filter = BloomFilter::new(100, 0.01);
+        let inserted = (0..1_000)
+            .filter(|i| filter.insert(&format!("https://example.com/{i}")))
+            .count();
+        assert!(inserted > 980, "only {inserted} URLs were new");
+        assert!(filter
+            .each_shard(|shard| shard.slices.len())
+            .into_iter()
+            .all(|slices| slices > 1));
+        assert_eq!(filter.len(), inserted);
+        assert!((0..1_000).all(|i| filter.contains(&format!("https://example.com/{i}"))));
+
+        let false_positives = (0..10_000)
+            .filter(|i| filter.contains(&format!("https://example.org/{i}")))
+            .count();
+        assert!(false_positives < 100, "{false_positives} false positives");
+        assert!(filter.stats().false_positive_rate <= 0.01);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = temp_path("bloom.bin");
+        let filter = BloomFilter::new(10, 0.01);
+        for i in 0..50 {
+            filter.insert(&format!("https://example.com/{i}"));
+        }
+        save(&filter, &path).unwrap();
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.len(), filter.len());
+        assert!(loaded.contains("https://example.com/42"));
+
+        let config = SeenConfig {
+            kind: SeenKind::Fingerprint,
+            path: Some(temp_path("exact.bin").to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let set = config.build().unwrap();
+        set.insert("https://example.com/");
+        save(set.as_ref(), Path::new(config.path.as_deref().unwrap())).unwrap();
+        let resumed = config.build().unwrap();
+        assert_eq!(resumed.stats().kind, SeenKind::Fingerprint);
+        assert!(resumed.contains("https://example.com/"));
+
+        // Switching kinds would silently keep the old one.
+        let bloom = SeenConfig {
+            kind: SeenKind::Bloom,
+            ..config
+        };
+        assert!(matches!(bloom.build(), Err(CrawlerError::Other(_))));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_inserts_claim_once() {
+        let sets: Vec<Box<dyn SeenSet>> = vec![
+            Box::new(FingerprintSet::default()),
+            Box::new(BloomFilter::new(1_000, 0.001)),
+            Box::new(UrlSet::default()),
+        ];
+        for set in sets {
+            let claimed = AtomicUsize::new(0);
+            std::thread::scope(|scope| {
+                for _ in 0..8 {
+                    scope.spawn(|| {
+                        for i in 0..200 {
+                            if set.insert(&format!("https://example.com/{i}")) {
+                                claimed.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                    });
+                }
+            });
+            assert_eq!(claimed.load(Ordering::Relaxed), set.len());
+            assert!(set.len() <= 200);
+        }
+    }
+}
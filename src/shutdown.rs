@@ -0,0 +1,145 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+static SHUTDOWN: Shutdown = Shutdown::new();
+
+pub fn get_shutdown() -> &'static Shutdown {
+    &SHUTDOWN
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    /// How long an in-flight fetch may keep running after a shutdown signal.
+    pub drain_timeout_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            drain_timeout_secs: 30,
+        }
+    }
+}
+
+/// Process-wide shutdown request. The first request asks the crawl to drain:
+/// stop taking tasks, finish what is in flight and save its state.
+#[derive(Debug)]
+pub struct Shutdown {
+    requests: AtomicUsize,
+    notify: Notify,
+}
+
+impl Shutdown {
+    pub const fn new() -> Self {
+        Self {
+            requests: AtomicUsize::new(0),
+            notify: Notify::const_new(),
+        }
+    }
+
+    /// Records a shutdown request and wakes everyone waiting in `requested`.
+    /// Returns how many requests there have been, including this one.
+    pub fn request(&self) -> usize {
+        let requests = self.requests.fetch_add(1, Ordering::SeqCst) + 1;
+        self.notify.notify_waiters();
+        requests
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requests.load(Ordering::SeqCst) > 0
+    }
+
+    /// Completes once shutdown has been requested.
+    pub async fn requested(&self) {
+        let notified = self.notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if self.is_requested() {
+            return;
+        }
+        notified.await;
+    }
+
+    /// Completes `timeout` after shutdown has been requested, bounding how
+    /// long in-flight work may delay the exit.
+    pub async fn drain_expired(&self, timeout: Duration) {
+        self.requested().await;
+        tokio::time::sleep(timeout).await;
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn next_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => tracing::error!("Failed to listen for SIGTERM: {}", e),
+        }
+    }
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        tracing::error!("Failed to listen for Ctrl-C: {}", e);
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Turns SIGINT and SIGTERM into shutdown requests. The first signal starts a
+/// graceful drain; a second one exits immediately.
+pub fn install_signal_handlers() {
+    tokio::spawn(async {
+        loop {
+            next_signal().await;
+            if get_shutdown().request() == 1 {
+                eprintln!(
+                    "Shutting down: finishing in-flight fetches and saving state. \
+                     Signal again to exit immediately."
+                );
+            } else {
+                eprintln!("Exiting immediately");
+                std::process::exit(130);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_wakes_waiters() {
+        let shutdown = Shutdown::new();
+        assert!(!shutdown.is_requested());
+
+        let waiter = shutdown.requested();
+        tokio::pin!(waiter);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), waiter.as_mut())
+                .await
+                .is_err()
+        );
+
+        assert_eq!(shutdown.request(), 1);
+        waiter.await;
+        assert!(shutdown.is_requested());
+        // Later waiters complete straight away.
+        shutdown.requested().await;
+        assert_eq!(shutdown.request(), 2);
+    }
+}
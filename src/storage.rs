@@ -2,28 +2,110 @@ use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json;
-use std::fmt::format;
+use std::collections::BTreeMap;
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::Path;
 
+use crate::canonical::canonicalize;
+use crate::error::CrawlerError;
+use crate::modules::parser::directives::RobotsDirectives;
+use crate::modules::parser::metadata::PageMetadata;
+use crate::modules::parser::parser::Link;
+use crate::modules::parser::readability::MainContent;
+use crate::modules::parser::relations::PageRelations;
+use crate::modules::parser::rules::FieldValue;
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct StorageConfig {
     pub output_path: String,
     pub file_name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct DataEntry {
     pub url: String,
     pub status_code: i32,
     pub content_type: Option<String>,
     pub title: Option<String>,
+    /// Links found on the page that fall outside the crawl scope.
+    #[serde(default)]
+    pub external_links: Vec<String>,
+    /// Every link on the page, whether the crawl policy followed it or not.
+    #[serde(default)]
+    pub links: Vec<Link>,
+    /// Where the page sent the crawler instead of serving content itself.
+    #[serde(default)]
+    pub redirect: Option<Redirect>,
+    /// What the page asked of crawlers through meta tags and headers.
+    #[serde(default)]
+    pub robots: RobotsDirectives,
+    /// The page's canonical URL, language alternates and pagination links.
+    #[serde(default)]
+    pub relations: PageRelations,
+    /// The page redirects somewhere other than its declared canonical URL.
+    #[serde(default)]
+    pub canonical_conflict: bool,
+    /// Meta tags, OpenGraph, Twitter cards, JSON-LD and microdata.
+    #[serde(default)]
+    pub metadata: PageMetadata,
+    /// Custom fields extracted by the configured rules.
+    #[serde(default)]
+    pub fields: BTreeMap<String, FieldValue>,
+    /// Readable main content, left out for noarchive pages.
+    #[serde(default)]
+    pub content: Option<MainContent>,
     pub crawled_at: DateTime<Utc>,
 }
+/// How a page redirected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedirectKind {
+    /// A 3xx response.
+    Http,
+    /// A `<meta http-equiv="refresh">` in the page.
+    MetaRefresh,
+}
+
+/// A redirect edge from a stored page to `target`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Redirect {
+    pub target: String,
+    pub kind: RedirectKind,
+}
+
 pub struct Storage {
     config: StorageConfig,
 }
+
+/// Where the pipeline's store stage sends crawled pages.
+pub trait PageStore: Send + Sync {
+    fn save(&self, entry: &DataEntry) -> Result<(), CrawlerError>;
+}
+
+/// Appends each page to its host's JSON file, as set up by a storage config
+/// file.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    config_path: String,
+}
+
+impl FileStore {
+    pub fn new(config_path: &str) -> Self {
+        Self {
+            config_path: config_path.to_string(),
+        }
+    }
+}
+
+impl PageStore for FileStore {
+    fn save(&self, entry: &DataEntry) -> Result<(), CrawlerError> {
+        let host = url::Url::parse(&entry.url)?.host_str().map(str::to_string);
+        StorageConfig::from(&self.config_path, host.as_deref())
+            .and_then(|config| Storage::new(config).save_data(entry))
+            .map_err(|e| CrawlerError::Other(e.to_string()))
+    }
+}
 pub fn get_storage_config_path() -> String {
     "storage_config.json".to_string()
 }
@@ -53,14 +135,22 @@ impl Storage {
     pub fn save_data(&self, data: &DataEntry) -> Result<(), Box<dyn std::error::Error>> {
         let path = Path::new(&self.config.output_path);
         let file_path = path.join(&self.config.file_name);
+        std::fs::create_dir_all(path)?;
 
         let mut file = OpenOptions::new()
             .create(true)
             .append(true)
             .open(file_path.clone())?;
 
-        file.write_all(serde_json::to_string_pretty(data)?.as_bytes())?;
-        file.write_all(b"\n")?;
+        let data = DataEntry {
+            url: canonicalize(&data.url)?,
+            ..data.clone()
+        };
+        // One write per entry, so an interrupted crawl never leaves half of
+        // one behind.
+        let mut entry = serde_json::to_string_pretty(&data)?;
+        entry.push('\n');
+        file.write_all(entry.as_bytes())?;
 
         tracing::info!("Data successfully saved to: {}", file_path.display());
         Ok(())
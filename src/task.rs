@@ -1,35 +1,138 @@
-use flume::unbounded;
-use std::thread;
-use std::time::Duration;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+use crate::priority::Priority;
+use crate::storage::DataEntry;
+
+/// Pipeline stage that handles a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    Fetch,
+    Parse,
+    Store,
+}
+
+/// A unit of work moving through the crawl pipeline. The fetch variants are
+/// what the frontier queues; `Parse` and `Store` carry a fetched page on to
+/// the later stages.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Task {
-    CrawlUrl(String),
+    /// Fetch a host's robots.txt to discover its sitemaps.
+    FetchRobots {
+        url: String,
+        depth: u32,
+        priority: Priority,
+    },
+    /// Fetch a sitemap or sitemap index and queue the URLs it lists.
+    FetchSitemap {
+        url: String,
+        depth: u32,
+        priority: Priority,
+    },
+    /// Fetch a page. Older checkpoints and frontier stores call it `CrawlUrl`.
+    #[serde(alias = "CrawlUrl")]
+    Fetch {
+        url: String,
+        depth: u32,
+        priority: Priority,
+    },
+    /// Fetch a page again even though it was already visited.
+    Recrawl {
+        url: String,
+        depth: u32,
+        priority: Priority,
+    },
+    /// Extract what the crawler needs from a fetched body.
+    Parse {
+        fetched: Box<Task>,
+        status: u16,
+        content_type: Option<String>,
+        body: String,
+        /// Where HTTP redirects took the fetch, if it moved.
+        #[serde(default)]
+        redirected_to: Option<String>,
+        /// The response's `X-Robots-Tag` header values.
+        #[serde(default)]
+        robots_tags: Vec<String>,
+        /// The response's `Link` header values.
+        #[serde(default)]
+        link_headers: Vec<String>,
+    },
+    /// Persist a crawled page.
+    Store { entry: Box<DataEntry>, depth: u32 },
 }
 impl Task {
     pub fn new(url: String) -> Self {
-        Task::CrawlUrl(url)
+        Task::with_depth(url, 0)
+    }
+    pub fn with_depth(url: String, depth: u32) -> Self {
+        Task::Fetch {
+            url,
+            depth,
+            priority: Priority::default(),
+        }
     }
-    pub fn execute(&self) -> Result<(), anyhow::Error> {
+    /// Sets the priority of a fetch task; other tasks are left as they are.
+    pub fn with_priority(mut self, value: f64) -> Self {
+        match &mut self {
+            Task::FetchRobots { priority, .. }
+            | Task::FetchSitemap { priority, .. }
+            | Task::Fetch { priority, .. }
+            | Task::Recrawl { priority, .. } => *priority = Priority(value),
+            Task::Parse { .. } | Task::Store { .. } => {}
+        }
+        self
+    }
+    /// Replaces the URL of a fetch task; other tasks are left as they are.
+    pub fn with_url(mut self, new: String) -> Self {
+        match &mut self {
+            Task::FetchRobots { url, .. }
+            | Task::FetchSitemap { url, .. }
+            | Task::Fetch { url, .. }
+            | Task::Recrawl { url, .. } => *url = new,
+            Task::Parse { .. } | Task::Store { .. } => {}
+        }
+        self
+    }
+    pub fn url(&self) -> &str {
         match self {
-            Task::CrawlUrl(url) => {
-                // Simulate processing time
-                thread::sleep(Duration::from_millis(500));
-                println!("Processed: {}", url);
-                // Here you would implement the actual crawling logic
-            }
+            Task::FetchRobots { url, .. }
+            | Task::FetchSitemap { url, .. }
+            | Task::Fetch { url, .. }
+            | Task::Recrawl { url, .. } => url,
+            Task::Parse { fetched, .. } => fetched.url(),
+            Task::Store { entry, .. } => &entry.url,
         }
-        Ok(())
     }
-}
-
-async fn worker_thread(receiver: flume::Receiver<Task>) {
-    println!("Worker thread started");
-
-    while let Ok(task) = receiver.recv_async().await {
-        match task.execute() {
-            Ok(_) => println!("Task completed successfully"),
-            Err(e) => eprintln!("Error executing task: {}", e),
+    /// Number of links followed from the seed to reach this task.
+    pub fn depth(&self) -> u32 {
+        match self {
+            Task::FetchRobots { depth, .. }
+            | Task::FetchSitemap { depth, .. }
+            | Task::Fetch { depth, .. }
+            | Task::Recrawl { depth, .. }
+            | Task::Store { depth, .. } => *depth,
+            Task::Parse { fetched, .. } => fetched.depth(),
+        }
+    }
+    /// Higher-priority tasks are fetched first within a host.
+    pub fn priority(&self) -> Priority {
+        match self {
+            Task::FetchRobots { priority, .. }
+            | Task::FetchSitemap { priority, .. }
+            | Task::Fetch { priority, .. }
+            | Task::Recrawl { priority, .. } => *priority,
+            Task::Parse { fetched, .. } => fetched.priority(),
+            Task::Store { .. } => Priority::default(),
+        }
+    }
+    pub fn stage(&self) -> Stage {
+        match self {
+            Task::FetchRobots { .. }
+            | Task::FetchSitemap { .. }
+            | Task::Fetch { .. }
+            | Task::Recrawl { .. } => Stage::Fetch,
+            Task::Parse { .. } => Stage::Parse,
+            Task::Store { .. } => Stage::Store,
         }
     }
 }
@@ -1,93 +1,656 @@
-use flume::{unbounded, Receiver, SendError, Sender};
-use std::thread;
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll};
 use std::time::Duration;
+
+use flume::{bounded, Receiver, Sender};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tokio::sync::oneshot;
 use tokio::task;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::CrawlerError;
+
+type Job = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send + 'static>;
+
+/// How long a cancelled job may keep running to stop cleanly before it is
+/// dropped.
+const DEFAULT_CANCEL_GRACE: Duration = Duration::from_secs(5);
+
+/// Ended jobs the pool remembers for its report.
+const DEFAULT_HISTORY: usize = 1024;
+
+/// Where a job submitted to a `ThreadPool` ended up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskOutcome {
+    Queued,
+    Running,
+    Finished,
+    Panicked,
+    /// Cancelled through its token, whether or not it stopped by itself.
+    Cancelled,
+    /// Ran past its deadline.
+    TimedOut,
+}
+
+#[derive(Debug)]
+struct TaskRecord {
+    name: String,
+    outcome: TaskOutcome,
+}
+
+impl TaskOutcome {
+    fn is_pending(self) -> bool {
+        matches!(self, TaskOutcome::Queued | TaskOutcome::Running)
+    }
+}
+
+/// Every pending job, and the last `history` ones that ended, oldest first.
+#[derive(Debug)]
+struct Tasks {
+    records: HashMap<u64, TaskRecord>,
+    ended: VecDeque<u64>,
+    history: usize,
+}
+
+impl Tasks {
+    fn set_outcome(&mut self, id: u64, outcome: TaskOutcome) {
+        let Some(task) = self.records.get_mut(&id) else {
+            return;
+        };
+        let ends = task.outcome.is_pending() && !outcome.is_pending();
+        task.outcome = outcome;
+        if ends {
+            self.ended.push_back(id);
+            while self.ended.len() > self.history {
+                if let Some(oldest) = self.ended.pop_front() {
+                    self.records.remove(&oldest);
+                }
+            }
+        }
+    }
+}
+
+type Registry = Arc<Mutex<Tasks>>;
+
+/// Locks the registry even if a panic poisoned it: the records stay usable,
+/// and the pool must keep running jobs and reporting after a panic.
+fn lock(registry: &Registry) -> MutexGuard<'_, Tasks> {
+    registry.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn record(registry: &Registry, id: u64, outcome: TaskOutcome) {
+    lock(registry).set_outcome(id, outcome);
+}
+
+/// Names of the pool's jobs grouped by how they ended. Jobs still queued or
+/// running are listed as `pending`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolReport {
+    pub finished: Vec<String>,
+    pub cancelled: Vec<String>,
+    pub timed_out: Vec<String>,
+    pub panicked: Vec<String>,
+    pub pending: Vec<String>,
+}
+
+impl fmt::Display for PoolReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} finished", self.finished.len())?;
+        for (label, names) in [
+            ("cancelled", &self.cancelled),
+            ("timed out", &self.timed_out),
+            ("panicked", &self.panicked),
+            ("pending", &self.pending),
+        ] {
+            if !names.is_empty() {
+                write!(f, ", {} {} ({})", names.len(), label, names.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Options for `ThreadPool::execute_with`.
+#[derive(Debug, Clone, Default)]
+pub struct TaskOptions {
+    /// Name used in the pool's report; defaults to `task-<id>`.
+    pub name: Option<String>,
+    /// Longest the job may run once a worker picks it up. When it passes the
+    /// job's token is cancelled.
+    pub deadline: Option<Duration>,
+}
+
+/// A fixed number of workers, each running one job at a time, fed from a
+/// bounded queue. `execute` waits while the queue is full.
 pub struct ThreadPool {
     workers: Vec<task::JoinHandle<()>>,
-    task_sender: Sender<Box<dyn FnOnce() + Send + 'static>>,
+    /// `None` once the pool stopped accepting jobs.
+    task_sender: Option<Sender<Job>>,
+    /// Parent of every job's token.
+    cancel: CancellationToken,
+    cancel_grace: Duration,
+    next_id: AtomicU64,
+    tasks: Registry,
+}
+
+/// The eventual output of a job given to `ThreadPool::execute`. Dropping the
+/// handle detaches the job; it still runs.
+#[derive(Debug)]
+pub struct TaskHandle<T> {
+    id: u64,
+    token: CancellationToken,
+    result: oneshot::Receiver<Result<T, CrawlerError>>,
+}
+
+impl<T> TaskHandle<T> {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The job's cancellation token.
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Asks the job to stop. It gets the pool's grace period to return by
+    /// itself before it is dropped.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+}
+
+impl<T> Future for TaskHandle<T> {
+    type Output = Result<T, CrawlerError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.result)
+            .poll(cx)
+            .map(|result| result.unwrap_or(Err(CrawlerError::TaskCancelled)))
+    }
+}
+
+/// The message a panic was raised with, from its payload.
+pub fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+async fn sleep_or_forever(duration: Option<Duration>) {
+    match duration {
+        Some(duration) => tokio::time::sleep(duration).await,
+        None => std::future::pending().await,
+    }
 }
 
 impl ThreadPool {
+    /// A pool of `workers_size` workers whose queue holds twice as many jobs.
     pub fn new(workers_size: usize) -> Self {
-        let (task_sender, task_receiver) = unbounded();
+        Self::with_queue_size(workers_size, workers_size * 2)
+    }
+
+    pub fn with_queue_size(workers_size: usize, queue_size: usize) -> Self {
+        let (task_sender, task_receiver) = bounded(queue_size);
         let mut workers = Vec::with_capacity(workers_size);
-        for _ in 0..workers_size {
-            let task_receiver: Receiver<Box<dyn FnOnce() + Send + 'static>> = task_receiver.clone();
+        for _ in 0..workers_size.max(1) {
+            let task_receiver: Receiver<Job> = task_receiver.clone();
+            // Jobs catch their own panics; should the worker itself die, it
+            // is started again so the pool keeps its size.
             let worker = task::spawn(async move {
-                while let Ok(task) = task_receiver.recv_async().await {
-                    task();
+                loop {
+                    let worked = AssertUnwindSafe(async {
+                        while let Ok(task) = task_receiver.recv_async().await {
+                            task().await;
+                        }
+                    })
+                    .catch_unwind()
+                    .await;
+                    if worked.is_ok() {
+                        break;
+                    }
+                    tracing::error!("Worker panicked, restarting it");
                 }
-                println!("Worker exiting...");
+                tracing::debug!("Worker exiting");
             });
             workers.push(worker);
         }
         Self {
             workers,
-            task_sender,
+            task_sender: Some(task_sender),
+            cancel: CancellationToken::new(),
+            cancel_grace: DEFAULT_CANCEL_GRACE,
+            next_id: AtomicU64::new(0),
+            tasks: Arc::new(Mutex::new(Tasks {
+                records: HashMap::new(),
+                ended: VecDeque::new(),
+                history: DEFAULT_HISTORY,
+            })),
         }
     }
-    pub async fn execute<F, Fut>(&self, f: F) -> Result<(), SendError<Box<dyn FnOnce() + Send>>>
+
+    /// Sets how many ended jobs are kept for `report`; older ones are
+    /// forgotten so a long-lived pool does not grow without bound.
+    pub fn with_history(self, history: usize) -> Self {
+        lock(&self.tasks).history = history;
+        self
+    }
+
+    /// Sets how long a cancelled job may keep running to stop cleanly.
+    pub fn with_cancel_grace(mut self, grace: Duration) -> Self {
+        self.cancel_grace = grace;
+        self
+    }
+
+    /// Queues `f` to be called and awaited on a worker, waiting for room in
+    /// the queue first. A panic in the job is reported through the handle
+    /// and leaves the worker running.
+    pub async fn execute<F, Fut, T>(&self, f: F) -> Result<TaskHandle<T>, CrawlerError>
     where
         F: FnOnce() -> Fut + Send + 'static,
-        Fut: std::future::Future<Output = ()> + Send + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
     {
-        let future = f();
+        self.execute_with(TaskOptions::default(), move |_| f())
+            .await
+    }
 
-        if self.task_sender.is_disconnected() {
-            // ✅ PREVENT SENDING TO DROPPED CHANNEL
-            return Err(SendError(Box::new(|| println!("Channel Disconnected"))));
-            // Return a dummy function to indicate failure
+    /// Like `execute`, but hands the job a cancellation token to watch. The
+    /// token is cancelled by `TaskHandle::cancel`, `cancel_all` or the
+    /// deadline in `options`; a job still running `cancel_grace` later is
+    /// dropped and its handle reports `TaskCancelled` or `TaskTimedOut`.
+    pub async fn execute_with<F, Fut, T>(
+        &self,
+        options: TaskOptions,
+        f: F,
+    ) -> Result<TaskHandle<T>, CrawlerError>
+    where
+        F: FnOnce(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let task_sender = self.task_sender.as_ref().ok_or(CrawlerError::PoolClosed)?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let token = self.cancel.child_token();
+        let (sender, result) = oneshot::channel();
+        let (job_token, tasks, grace) = (token.clone(), self.tasks.clone(), self.cancel_grace);
+        let job: Job = Box::new(move || {
+            async move {
+                record(&tasks, id, TaskOutcome::Running);
+                let future = AssertUnwindSafe(f(job_token.clone())).catch_unwind();
+                tokio::pin!(future);
+                let mut timed_out = false;
+                let output = tokio::select! {
+                    output = &mut future => Some(output),
+                    _ = job_token.cancelled() => None,
+                    _ = sleep_or_forever(options.deadline) => {
+                        timed_out = true;
+                        job_token.cancel();
+                        None
+                    }
+                };
+                let output = match output {
+                    Some(output) => Some(output),
+                    None => tokio::time::timeout(grace, &mut future).await.ok(),
+                };
+                let (outcome, output) = match output {
+                    Some(Ok(output)) if timed_out => (TaskOutcome::TimedOut, Ok(output)),
+                    Some(Ok(output)) if job_token.is_cancelled() => {
+                        (TaskOutcome::Cancelled, Ok(output))
+                    }
+                    Some(Ok(output)) => (TaskOutcome::Finished, Ok(output)),
+                    Some(Err(payload)) => (
+                        TaskOutcome::Panicked,
+                        Err(CrawlerError::TaskPanicked(panic_message(payload))),
+                    ),
+                    None if timed_out => (TaskOutcome::TimedOut, Err(CrawlerError::TaskTimedOut)),
+                    None => (TaskOutcome::Cancelled, Err(CrawlerError::TaskCancelled)),
+                };
+                record(&tasks, id, outcome);
+                let _ = sender.send(output);
+            }
+            .boxed()
+        });
+        let name = options.name.clone().unwrap_or_else(|| format!("task-{id}"));
+        lock(&self.tasks).records.insert(
+            id,
+            TaskRecord {
+                name,
+                outcome: TaskOutcome::Queued,
+            },
+        );
+        if task_sender.send_async(job).await.is_err() {
+            lock(&self.tasks).records.remove(&id);
+            return Err(CrawlerError::PoolClosed);
         }
+        Ok(TaskHandle { id, token, result })
+    }
 
-        self.task_sender
-            .send_async(Box::new(move || {
-                tokio::spawn(future);
-            }))
-            .await?;
-
-        Ok(())
+    /// Cancels the token of every job, queued or running, and of any job
+    /// submitted later.
+    pub fn cancel_all(&self) {
+        self.cancel.cancel();
     }
 
+    /// Stops accepting jobs and waits until every queued and running job has
+    /// finished.
     pub async fn shutdown(&mut self) {
+        self.task_sender.take();
         for handle in self.workers.drain(..) {
             if let Err(e) = handle.await {
                 tracing::error!("Error waiting for thread to complete: {}", e);
             }
         }
     }
-}
-impl Drop for ThreadPool {
-    fn drop(&mut self) {
-        let workers = std::mem::take(&mut self.workers);
 
-        if let Ok(handle) = tokio::runtime::Handle::try_current() {
-            handle.spawn_blocking(move || {
-                for worker in workers {
-                    let _ = futures::executor::block_on(worker);
-                }
-            });
-        } else {
-            let rt = tokio::runtime::Runtime::new().unwrap();
-            rt.block_on(async {
-                for worker in workers {
-                    if let Err(e) = worker.await {
-                        tracing::error!("Error waiting for thread to complete: {}", e);
-                    }
+    /// Like `shutdown`, but once `timeout` passes the remaining jobs are
+    /// cancelled, and after the grace period the workers are stopped.
+    pub async fn shutdown_with_timeout(&mut self, timeout: Duration) -> PoolReport {
+        self.task_sender.take();
+        let workers = std::mem::take(&mut self.workers);
+        let aborts: Vec<_> = workers.iter().map(|w| w.abort_handle()).collect();
+        let drain = futures::future::join_all(workers);
+        tokio::pin!(drain);
+        if tokio::time::timeout(timeout, &mut drain).await.is_err() {
+            self.cancel_all();
+            // Queued jobs still go through a worker, which only gives each
+            // the grace period since their tokens are already cancelled.
+            if tokio::time::timeout(self.cancel_grace, &mut drain)
+                .await
+                .is_err()
+            {
+                aborts.iter().for_each(|abort| abort.abort());
+                let mut tasks = lock(&self.tasks);
+                let mut pending: Vec<u64> = tasks
+                    .records
+                    .iter()
+                    .filter(|(_, task)| task.outcome.is_pending())
+                    .map(|(id, _)| *id)
+                    .collect();
+                pending.sort_unstable();
+                for id in pending {
+                    tasks.set_outcome(id, TaskOutcome::Cancelled);
                 }
-            });
+            }
+        }
+        self.report()
+    }
+
+    /// Cancels the running jobs and stops the workers; queued jobs are
+    /// dropped and their handles report `TaskCancelled`.
+    pub fn abort(&self) {
+        for worker in &self.workers {
+            worker.abort();
         }
     }
+
+    /// How the pending jobs and the most recently ended ones stand; see
+    /// `with_history`.
+    pub fn report(&self) -> PoolReport {
+        let tasks = lock(&self.tasks);
+        let mut ids: Vec<_> = tasks.records.keys().copied().collect();
+        ids.sort_unstable();
+        let mut report = PoolReport::default();
+        for id in ids {
+            let task = &tasks.records[&id];
+            let names = match task.outcome {
+                TaskOutcome::Queued | TaskOutcome::Running => &mut report.pending,
+                TaskOutcome::Finished => &mut report.finished,
+                TaskOutcome::Panicked => &mut report.panicked,
+                TaskOutcome::Cancelled => &mut report.cancelled,
+                TaskOutcome::TimedOut => &mut report.timed_out,
+            };
+            names.push(task.name.clone());
+        }
+        report
+    }
+}
+
+/// Dropping the pool cannot wait for its jobs, as `drop` cannot await. It
+/// stops accepting jobs and leaves the workers to finish the running and
+/// queued ones on the runtime, so no job is lost and every handle still
+/// resolves. Call `shutdown` or `shutdown_with_timeout` to wait for them.
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.task_sender.take();
+    }
 }
 
+#[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
     use super::*;
 
     #[tokio::test]
     async fn test_thread_pool() {
-        let mut pool = ThreadPool::new(4);
+        let pool = ThreadPool::new(4);
         pool.execute(|| async move { println!("Hello from thread!") })
-            .await;
+            .await
+            .unwrap();
         drop(pool);
         dbg!("pool dropped");
     }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_running_jobs() {
+        let mut pool = ThreadPool::new(2);
+        let done = Arc::new(AtomicUsize::new(0));
+        for _ in 0..4 {
+            let done = done.clone();
+            pool.execute(|| async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                done.fetch_add(1, Ordering::SeqCst);
+            })
+            .await
+            .unwrap();
+        }
+        pool.shutdown().await;
+        assert_eq!(done.load(Ordering::SeqCst), 4);
+        assert!(pool.execute(|| async {}).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drop_keeps_in_flight_jobs() {
+        let pool = ThreadPool::new(1);
+        let done = Arc::new(AtomicUsize::new(0));
+        let mut handles = Vec::new();
+        for i in 0..3 {
+            let done = done.clone();
+            let handle = pool
+                .execute(move || async move {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    done.fetch_add(1, Ordering::SeqCst);
+                    i
+                })
+                .await
+                .unwrap();
+            handles.push(handle);
+        }
+        // Let the first job start, so one is running and two are queued.
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        drop(pool);
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.await.unwrap(), i);
+        }
+        assert_eq!(done.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_task_handle_returns_output_and_panics() {
+        let pool = ThreadPool::new(1);
+        let sum = pool.execute(|| async { 2 + 2 }).await.unwrap();
+        let panicked = pool.execute(|| async { panic!("boom") }).await.unwrap();
+        let after = pool.execute(|| async { "still running" }).await.unwrap();
+
+        assert_eq!(sum.await.unwrap(), 4);
+        assert!(matches!(
+            panicked.await,
+            Err(CrawlerError::TaskPanicked(message)) if message == "boom"
+        ));
+        assert_eq!(after.await.unwrap(), "still running");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_worker_count_caps_concurrency() {
+        let pool = ThreadPool::with_queue_size(2, 8);
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let mut handles = Vec::new();
+        for _ in 0..8 {
+            let (active, peak) = (active.clone(), peak.clone());
+            let handle = pool
+                .execute(|| async move {
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    active.fetch_sub(1, Ordering::SeqCst);
+                })
+                .await
+                .unwrap();
+            handles.push(handle);
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_execute_waits_for_queue_capacity() {
+        let pool = ThreadPool::with_queue_size(1, 1);
+        let (release, blocked) = oneshot::channel::<()>();
+        let first = pool
+            .execute(|| async move {
+                let _ = blocked.await;
+            })
+            .await
+            .unwrap();
+        // Let the worker take the first job, then fill the queue.
+        tokio::task::yield_now().await;
+        pool.execute(|| async {}).await.unwrap();
+
+        let third = pool.execute(|| async {});
+        tokio::pin!(third);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), third.as_mut())
+                .await
+                .is_err()
+        );
+
+        release.send(()).unwrap();
+        third.await.unwrap().await.unwrap();
+        first.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancel_and_deadline() {
+        let pool = ThreadPool::new(2).with_cancel_grace(Duration::from_millis(50));
+        // Watches its token and returns early.
+        let cooperative = pool
+            .execute_with(TaskOptions::default(), |cancel| async move {
+                cancel.cancelled().await;
+                "stopped"
+            })
+            .await
+            .unwrap();
+        // Ignores its token and has to be dropped.
+        let stubborn = pool
+            .execute_with(
+                TaskOptions {
+                    name: Some("stubborn".to_string()),
+                    deadline: Some(Duration::from_millis(10)),
+                },
+                |_| std::future::pending::<()>(),
+            )
+            .await
+            .unwrap();
+
+        cooperative.cancel();
+        assert_eq!(cooperative.await.unwrap(), "stopped");
+        assert!(matches!(stubborn.await, Err(CrawlerError::TaskTimedOut)));
+
+        let report = pool.report();
+        assert_eq!(report.cancelled, vec!["task-0".to_string()]);
+        assert_eq!(report.timed_out, vec!["stubborn".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_report_keeps_limited_history() {
+        let pool = ThreadPool::new(2).with_history(2);
+        let blocked = pool.execute(std::future::pending::<()>).await.unwrap();
+        for _ in 0..4 {
+            pool.execute(|| async {}).await.unwrap().await.unwrap();
+        }
+        let report = pool.report();
+        assert_eq!(
+            report.finished,
+            vec!["task-3".to_string(), "task-4".to_string()]
+        );
+        // Pending jobs are never forgotten.
+        assert_eq!(report.pending, vec!["task-0".to_string()]);
+        assert_eq!(pool.tasks.lock().unwrap().records.len(), 3);
+        blocked.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_poisoned_registry_keeps_working() {
+        let pool = ThreadPool::new(2);
+        let tasks = pool.tasks.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = tasks.lock().unwrap();
+            panic!("poison the registry");
+        })
+        .join();
+        assert!(pool.tasks.is_poisoned());
+
+        let handle = pool
+            .execute_with(
+                TaskOptions {
+                    name: Some("after".to_string()),
+                    ..Default::default()
+                },
+                |_| async { 1 },
+            )
+            .await
+            .unwrap();
+        assert_eq!(handle.await.unwrap(), 1);
+        assert_eq!(pool.report().finished, vec!["after".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_with_timeout_cancels_the_rest() {
+        let mut pool =
+            ThreadPool::with_queue_size(1, 4).with_cancel_grace(Duration::from_millis(20));
+        pool.execute(|| async {}).await.unwrap();
+        let slow = pool
+            .execute(|| tokio::time::sleep(Duration::from_secs(60)))
+            .await
+            .unwrap();
+        pool.execute(|| async {}).await.unwrap();
+
+        let report = pool.shutdown_with_timeout(Duration::from_millis(20)).await;
+        assert_eq!(report.finished, vec!["task-0".to_string()]);
+        assert_eq!(
+            report.cancelled,
+            vec!["task-1".to_string(), "task-2".to_string()]
+        );
+        assert!(report.pending.is_empty());
+        assert!(matches!(slow.await, Err(CrawlerError::TaskCancelled)));
+        assert_eq!(
+            report.to_string(),
+            "1 finished, 2 cancelled (task-1, task-2)"
+        );
+    }
 }
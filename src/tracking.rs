@@ -0,0 +1,257 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::error::CrawlerError;
+
+/// Parameter-name patterns for the analytics and session identifiers that
+/// most commonly fan a single page out into many URLs.
+const DEFAULT_PATTERNS: &[&str] = &[
+    r"^utm_",
+    r"^(fbclid|gclid|dclid|gbraid|wbraid|msclkid|yclid|igshid|twclid)$",
+    r"^mc_(cid|eid)$",
+    r"^_(ga|gl|hsenc|hsmi)$",
+    r"^(jsessionid|phpsessid|aspsessionid[a-z]*|sessionid|cfid|cftoken)$",
+];
+
+static TRACKING_STATS: TrackingStats = TrackingStats::new();
+
+pub fn get_tracking_stats() -> &'static TrackingStats {
+    &TRACKING_STATS
+}
+
+/// Counters for how often tracking rules rewrote a URL and how many of those
+/// rewrites collapsed the URL onto one the crawler had already seen.
+#[derive(Debug)]
+pub struct TrackingStats {
+    urls_rewritten: AtomicUsize,
+    duplicates_avoided: AtomicUsize,
+}
+
+impl TrackingStats {
+    pub const fn new() -> Self {
+        Self {
+            urls_rewritten: AtomicUsize::new(0),
+            duplicates_avoided: AtomicUsize::new(0),
+        }
+    }
+    pub fn record_rewrite(&self) {
+        self.urls_rewritten.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn record_duplicate_avoided(&self) {
+        self.duplicates_avoided.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn urls_rewritten(&self) -> usize {
+        self.urls_rewritten.load(Ordering::Relaxed)
+    }
+    pub fn duplicates_avoided(&self) -> usize {
+        self.duplicates_avoided.load(Ordering::Relaxed)
+    }
+    /// Sets both counters, e.g. from a checkpoint.
+    pub fn restore(&self, urls_rewritten: usize, duplicates_avoided: usize) {
+        self.urls_rewritten.store(urls_rewritten, Ordering::Relaxed);
+        self.duplicates_avoided
+            .store(duplicates_avoided, Ordering::Relaxed);
+    }
+}
+
+impl Default for TrackingStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackingRuleSet {
+    /// Exact parameter names, matched case-insensitively.
+    pub params: Vec<String>,
+    /// Regexes matched case-insensitively against parameter names.
+    pub patterns: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackingConfig {
+    pub use_defaults: bool,
+    #[serde(flatten)]
+    pub rules: TrackingRuleSet,
+    /// Extra rules for a host and all of its subdomains.
+    pub domains: HashMap<String, TrackingRuleSet>,
+}
+
+impl Default for TrackingConfig {
+    fn default() -> Self {
+        Self {
+            use_defaults: true,
+            rules: TrackingRuleSet::default(),
+            domains: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct CompiledRuleSet {
+    params: HashSet<String>,
+    patterns: Vec<Regex>,
+}
+
+impl CompiledRuleSet {
+    fn compile(rules: &TrackingRuleSet, defaults: &[&str]) -> Result<Self, CrawlerError> {
+        let patterns = defaults
+            .iter()
+            .copied()
+            .chain(rules.patterns.iter().map(String::as_str))
+            .map(|pattern| {
+                RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| {
+                        CrawlerError::Other(format!("Invalid tracking pattern {pattern}: {e}"))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            params: rules.params.iter().map(|p| p.to_lowercase()).collect(),
+            patterns,
+        })
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.params.contains(&name.to_lowercase())
+            || self.patterns.iter().any(|re| re.is_match(name))
+    }
+}
+
+/// Decides which query and path parameters carry no page identity and should
+/// be dropped during canonicalization.
+#[derive(Debug, Clone, Default)]
+pub struct TrackingRules {
+    global: CompiledRuleSet,
+    domains: Vec<(String, CompiledRuleSet)>,
+}
+
+impl TrackingRules {
+    pub fn new(config: &TrackingConfig) -> Result<Self, CrawlerError> {
+        let defaults = if config.use_defaults {
+            DEFAULT_PATTERNS
+        } else {
+            &[]
+        };
+        let global = CompiledRuleSet::compile(&config.rules, defaults)?;
+        let domains = config
+            .domains
+            .iter()
+            .map(|(domain, rules)| {
+                Ok((
+                    domain.trim_start_matches('.').to_lowercase(),
+                    CompiledRuleSet::compile(rules, &[])?,
+                ))
+            })
+            .collect::<Result<Vec<_>, CrawlerError>>()?;
+        Ok(Self { global, domains })
+    }
+
+    /// Returns true if the parameter `name` should be stripped from URLs on `host`.
+    pub fn is_tracking_param(&self, host: &str, name: &str) -> bool {
+        if self.global.matches(name) {
+            return true;
+        }
+        self.domains.iter().any(|(domain, rules)| {
+            let in_domain = host == domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'));
+            in_domain && rules.matches(name)
+        })
+    }
+
+    /// Removes matching `key=value` pairs from a raw query string.
+    pub fn strip_query(&self, host: &str, query: &str) -> String {
+        query
+            .split('&')
+            .filter(|param| {
+                let name = param.split_once('=').map_or(*param, |(name, _)| name);
+                !self.is_tracking_param(host, name)
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// Removes matching `;name=value` parameters from every path segment, as
+    /// in `/cart;jsessionid=ABC123`.
+    pub fn strip_path(&self, host: &str, path: &str) -> String {
+        path.split('/')
+            .map(|segment| {
+                let mut parts = segment.split(';');
+                let mut kept = parts.next().unwrap_or("").to_string();
+                for param in parts {
+                    let name = param.split_once('=').map_or(param, |(name, _)| name);
+                    if !self.is_tracking_param(host, name) {
+                        kept.push(';');
+                        kept.push_str(param);
+                    }
+                }
+                kept
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_rules() {
+        let rules = TrackingRules::new(&TrackingConfig::default()).unwrap();
+        assert!(rules.is_tracking_param("example.com", "utm_source"));
+        assert!(rules.is_tracking_param("example.com", "fbclid"));
+        assert!(rules.is_tracking_param("example.com", "PHPSESSID"));
+        assert!(!rules.is_tracking_param("example.com", "page"));
+        assert_eq!(
+            rules.strip_query("example.com", "page=2&utm_medium=email&gclid=abc"),
+            "page=2"
+        );
+        assert_eq!(
+            rules.strip_path("example.com", "/shop/cart;jsessionid=A1B2;v=1/item"),
+            "/shop/cart;v=1/item"
+        );
+    }
+
+    #[test]
+    fn test_custom_and_domain_rules() {
+        let config: TrackingConfig = serde_json::from_str(
+            r#"{
+                "use_defaults": false,
+                "params": ["ref"],
+                "patterns": ["^pk_"],
+                "domains": { "shop.example.com": { "params": ["variant_session"] } }
+            }"#,
+        )
+        .unwrap();
+        let rules = TrackingRules::new(&config).unwrap();
+
+        assert!(!rules.is_tracking_param("example.com", "utm_source"));
+        assert!(rules.is_tracking_param("example.com", "REF"));
+        assert!(rules.is_tracking_param("example.com", "pk_campaign"));
+        assert!(rules.is_tracking_param("eu.shop.example.com", "variant_session"));
+        assert!(!rules.is_tracking_param("example.com", "variant_session"));
+        assert!(!rules.is_tracking_param("notshop.example.com", "variant_session"));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let config = TrackingConfig {
+            rules: TrackingRuleSet {
+                patterns: vec!["(".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(TrackingRules::new(&config).is_err());
+    }
+}
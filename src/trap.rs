@@ -0,0 +1,367 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::seen::fingerprint;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrapConfig {
+    pub enabled: bool,
+    pub max_url_length: usize,
+    pub max_path_depth: usize,
+    /// How many back-to-back repetitions of a segment sequence are tolerated.
+    pub max_segment_repeats: usize,
+    pub max_query_variants_per_path: usize,
+    /// Cap on URLs sharing one host and path pattern (digits collapsed).
+    pub max_urls_per_pattern: usize,
+    /// Example URLs kept per suspected trap for the report.
+    pub sample_size: usize,
+}
+
+impl Default for TrapConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_url_length: 1024,
+            max_path_depth: 15,
+            max_segment_repeats: 2,
+            max_query_variants_per_path: 100,
+            max_urls_per_pattern: 1000,
+            sample_size: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TrapKind {
+    LongUrl,
+    DeepPath,
+    RepeatingSegments,
+    QueryVariants,
+    PatternCap,
+}
+
+impl fmt::Display for TrapKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TrapKind::LongUrl => "very long URL",
+            TrapKind::DeepPath => "excessive path depth",
+            TrapKind::RepeatingSegments => "repeating path segments",
+            TrapKind::QueryVariants => "too many query variants",
+            TrapKind::PatternCap => "too many URLs for path pattern",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A group of rejected URLs sharing a heuristic and a host/path pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SuspectedTrap {
+    pub kind: TrapKind,
+    pub pattern: String,
+    pub rejected: usize,
+    pub samples: Vec<String>,
+}
+
+/// What a `TrapDetector` has counted so far, saved with checkpoints so a
+/// resumed crawl keeps its limits.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrapSnapshot {
+    pub query_variants: HashMap<String, HashSet<u64>>,
+    pub pattern_counts: HashMap<String, usize>,
+    pub suspects: Vec<SuspectedTrap>,
+}
+
+#[derive(Debug, Default)]
+struct TrapState {
+    query_variants: HashMap<String, HashSet<u64>>,
+    pattern_counts: HashMap<String, usize>,
+    suspects: HashMap<(TrapKind, String), SuspectedTrap>,
+}
+
+/// Heuristics for infinite URL spaces such as calendars, faceted search and
+/// relative-link loops, checked before a URL is queued.
+#[derive(Debug, Default)]
+pub struct TrapDetector {
+    config: TrapConfig,
+    state: Mutex<TrapState>,
+}
+
+/// Collapses digit runs in the path so `/2024/05/01` and `/2023/12/31` share
+/// a pattern.
+fn path_pattern(url: &Url) -> String {
+    let mut pattern = String::new();
+    let mut in_digits = false;
+    for ch in url.path().chars() {
+        if ch.is_ascii_digit() {
+            if !in_digits {
+                pattern.push_str("{n}");
+            }
+            in_digits = true;
+        } else {
+            pattern.push(ch);
+            in_digits = false;
+        }
+    }
+    format!("{}{}", url.host_str().unwrap_or(""), pattern)
+}
+
+/// Longest run of back-to-back repetitions of any segment sequence, e.g. 3
+/// for `/a/b/a/b/a/b`.
+fn max_repeats(segments: &[&str]) -> usize {
+    let mut best = 1;
+    for len in 1..=segments.len() / 2 {
+        for start in 0..segments.len() {
+            let mut repeats = 1;
+            let mut next = start + len;
+            while next + len <= segments.len()
+                && segments[start..start + len] == segments[next..next + len]
+            {
+                repeats += 1;
+                next += len;
+            }
+            best = best.max(repeats);
+        }
+    }
+    best
+}
+
+impl TrapDetector {
+    pub fn new(config: TrapConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(TrapState::default()),
+        }
+    }
+
+    /// Checks `url` against every heuristic and, if it passes, counts it
+    /// towards the per-path limits. Rejected URLs are logged and recorded in
+    /// the report.
+    pub fn check(&self, url: &Url) -> Result<(), TrapKind> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let Ok(mut state) = self.state.lock() else {
+            return Ok(());
+        };
+        let pattern = path_pattern(url);
+        let segments: Vec<&str> = url
+            .path_segments()
+            .map(|segments| segments.filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+
+        let path_key = format!("{}{}", url.host_str().unwrap_or(""), url.path());
+        // A stable hash, so checkpointed variants still match after an
+        // upgrade.
+        let query_hash = url.query().map(|query| fingerprint(query) as u64);
+
+        let verdict = if url.as_str().len() > self.config.max_url_length {
+            Err(TrapKind::LongUrl)
+        } else if segments.len() > self.config.max_path_depth {
+            Err(TrapKind::DeepPath)
+        } else if max_repeats(&segments) > self.config.max_segment_repeats {
+            Err(TrapKind::RepeatingSegments)
+        } else if query_hash.is_some_and(|hash| {
+            state.query_variants.get(&path_key).is_some_and(|variants| {
+                !variants.contains(&hash)
+                    && variants.len() >= self.config.max_query_variants_per_path
+            })
+        }) {
+            Err(TrapKind::QueryVariants)
+        } else if state.pattern_counts.get(&pattern).copied().unwrap_or(0)
+            >= self.config.max_urls_per_pattern
+        {
+            Err(TrapKind::PatternCap)
+        } else {
+            Ok(())
+        };
+
+        match verdict {
+            Ok(()) => {
+                if let Some(hash) = query_hash {
+                    state
+                        .query_variants
+                        .entry(path_key)
+                        .or_default()
+                        .insert(hash);
+                }
+                *state.pattern_counts.entry(pattern).or_default() += 1;
+            }
+            Err(kind) => {
+                let sample_size = self.config.sample_size;
+                let suspect = state
+                    .suspects
+                    .entry((kind, pattern.clone()))
+                    .or_insert_with(|| {
+                        tracing::warn!("Suspected crawler trap ({}) at {}", kind, url);
+                        SuspectedTrap {
+                            kind,
+                            pattern,
+                            rejected: 0,
+                            samples: Vec::new(),
+                        }
+                    });
+                suspect.rejected += 1;
+                if suspect.samples.len() < sample_size {
+                    suspect.samples.push(url.to_string());
+                }
+            }
+        }
+        verdict
+    }
+
+    pub fn snapshot(&self) -> TrapSnapshot {
+        let Ok(state) = self.state.lock() else {
+            return TrapSnapshot::default();
+        };
+        TrapSnapshot {
+            query_variants: state.query_variants.clone(),
+            pattern_counts: state.pattern_counts.clone(),
+            suspects: state.suspects.values().cloned().collect(),
+        }
+    }
+
+    /// Continues counting from `snapshot`, replacing anything seen so far.
+    pub fn restore(&self, snapshot: TrapSnapshot) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        *state = TrapState {
+            query_variants: snapshot.query_variants,
+            pattern_counts: snapshot.pattern_counts,
+            suspects: snapshot
+                .suspects
+                .into_iter()
+                .map(|suspect| ((suspect.kind, suspect.pattern.clone()), suspect))
+                .collect(),
+        };
+    }
+
+    /// Suspected traps, most-rejected first.
+    pub fn report(&self) -> Vec<SuspectedTrap> {
+        let mut suspects: Vec<SuspectedTrap> = self
+            .state
+            .lock()
+            .map(|state| state.suspects.values().cloned().collect())
+            .unwrap_or_default();
+        suspects.sort_by(|a, b| {
+            b.rejected
+                .cmp(&a.rejected)
+                .then(a.kind.cmp(&b.kind))
+                .then(a.pattern.cmp(&b.pattern))
+        });
+        suspects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(detector: &TrapDetector, url: &str) -> Result<(), TrapKind> {
+        detector.check(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_repeating_segments() {
+        assert_eq!(max_repeats(&["a", "b", "a", "b", "a", "b"]), 3);
+        assert_eq!(max_repeats(&["docs", "api", "v1"]), 1);
+
+        let detector = TrapDetector::new(TrapConfig::default());
+        assert_eq!(check(&detector, "https://example.com/a/b/a/b"), Ok(()));
+        assert_eq!(
+            check(&detector, "https://example.com/a/b/a/b/a/b"),
+            Err(TrapKind::RepeatingSegments)
+        );
+    }
+
+    #[test]
+    fn test_length_and_depth() {
+        let detector = TrapDetector::new(TrapConfig {
+            max_url_length: 40,
+            max_path_depth: 3,
+            ..Default::default()
+        });
+        assert_eq!(
+            check(&detector, "https://example.com/a/very/long/path/name"),
+            Err(TrapKind::LongUrl)
+        );
+        assert_eq!(
+            check(&detector, "https://example.com/a/b/c/d"),
+            Err(TrapKind::DeepPath)
+        );
+        assert_eq!(check(&detector, "https://example.com/a/b/c"), Ok(()));
+    }
+
+    #[test]
+    fn test_query_variants_and_pattern_cap() {
+        let detector = TrapDetector::new(TrapConfig {
+            max_query_variants_per_path: 2,
+            max_urls_per_pattern: 3,
+            sample_size: 1,
+            ..Default::default()
+        });
+        assert_eq!(check(&detector, "https://example.com/search?q=a"), Ok(()));
+        assert_eq!(check(&detector, "https://example.com/search?q=b"), Ok(()));
+        assert_eq!(
+            check(&detector, "https://example.com/search?q=c"),
+            Err(TrapKind::QueryVariants)
+        );
+
+        assert_eq!(check(&detector, "https://example.com/cal/2024/01"), Ok(()));
+        assert_eq!(check(&detector, "https://example.com/cal/2024/02"), Ok(()));
+        assert_eq!(check(&detector, "https://example.com/cal/2024/03"), Ok(()));
+        assert_eq!(
+            check(&detector, "https://example.com/cal/2024/04"),
+            Err(TrapKind::PatternCap)
+        );
+        assert_eq!(
+            check(&detector, "https://example.com/cal/2024/05"),
+            Err(TrapKind::PatternCap)
+        );
+
+        let report = detector.report();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].kind, TrapKind::PatternCap);
+        assert_eq!(report[0].pattern, "example.com/cal/{n}/{n}");
+        assert_eq!(report[0].rejected, 2);
+        assert_eq!(report[0].samples, vec!["https://example.com/cal/2024/04"]);
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let config = TrapConfig {
+            max_query_variants_per_path: 1,
+            ..Default::default()
+        };
+        let detector = TrapDetector::new(config.clone());
+        assert_eq!(check(&detector, "https://example.com/search?q=a"), Ok(()));
+        assert_eq!(
+            check(&detector, "https://example.com/search?q=b"),
+            Err(TrapKind::QueryVariants)
+        );
+        let json = serde_json::to_string(&detector.snapshot()).unwrap();
+
+        let resumed = TrapDetector::new(config);
+        resumed.restore(serde_json::from_str(&json).unwrap());
+        assert_eq!(check(&resumed, "https://example.com/search?q=a"), Ok(()));
+        assert_eq!(
+            check(&resumed, "https://example.com/search?q=c"),
+            Err(TrapKind::QueryVariants)
+        );
+        assert_eq!(resumed.report()[0].rejected, 2);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let detector = TrapDetector::new(TrapConfig {
+            enabled: false,
+            ..Default::default()
+        });
+        assert_eq!(check(&detector, "https://example.com/a/a/a/a/a"), Ok(()));
+    }
+}
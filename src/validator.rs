@@ -1,6 +1,6 @@
 use idna::domain_to_ascii;
 use regex::Regex;
-use url::{ParseError, Url};
+use url::{Host, Url};
 
 use crate::error::CrawlerError;
 const MAX_URL_LENGTH: usize = 2048;
@@ -13,6 +13,12 @@ pub struct UrlValidator {
     fragment_re: Regex,
 }
 
+impl Default for UrlValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl UrlValidator {
     pub fn new() -> Self {
         let scheme_re = Regex::new(r"^https?$").unwrap();
@@ -41,21 +47,23 @@ impl UrlValidator {
             return false;
         }
 
-        // Convert IDN to ASCII
-        let ascii_domain = match domain_to_ascii(url.host_str().unwrap_or("")) {
-            Ok(d) => d,
-            Err(_) => return false,
-        };
-
-        // Validate domain
-        if !self.domain_re.is_match(&ascii_domain) || ascii_domain.contains("..") {
-            return false;
-        }
+        match url.host() {
+            Some(Host::Domain(domain)) => {
+                // Convert IDN to ASCII
+                let ascii_domain = match domain_to_ascii(domain) {
+                    Ok(d) => d,
+                    Err(_) => return false,
+                };
 
-        if let Some(port) = url.port() {
-            if (url.scheme() == "http" && port == 80) || (url.scheme() == "https" && port == 443) {
-                return false; // Should not explicitly specify default ports
+                // Validate domain
+                if !self.domain_re.is_match(&ascii_domain) || ascii_domain.contains("..") {
+                    return false;
+                }
             }
+            // Not asked for with canonicalization, but servers reached by
+            // address, such as local test servers, have no domain to check.
+            Some(Host::Ipv4(_)) | Some(Host::Ipv6(_)) => {}
+            None => return false,
         }
 
         // Validate path
@@ -86,7 +94,7 @@ impl UrlValidator {
         true
     }
     pub fn remove_url_fragment(&self, url: &str) -> Result<Url, CrawlerError> {
-        let mut url = Url::parse(url).map_err(|e| CrawlerError::from(e))?;
+        let mut url = Url::parse(url).map_err(CrawlerError::from)?;
         url.set_fragment(None);
         Ok(url)
     }
@@ -111,6 +119,7 @@ impl UrlValidator {
     }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -143,6 +152,20 @@ mod tests {
         assert!(!validator.is_valid(&Url::parse("http://.com").unwrap()));
         assert!(validator.is_valid(&Url::parse("https://example.com/path?a=1&b=2").unwrap()));
     }
+    #[test]
+    fn test_explicit_default_ports() {
+        let validator = UrlValidator::new();
+        assert!(validator.is_valid(&Url::parse("http://example.com:80/path").unwrap()));
+        assert!(validator.is_valid(&Url::parse("https://example.com:443/").unwrap()));
+    }
+
+    #[test]
+    fn test_ip_hosts() {
+        let validator = UrlValidator::new();
+        assert!(validator.is_valid(&Url::parse("http://127.0.0.1:8080/").unwrap()));
+        assert!(validator.is_valid(&Url::parse("http://[::1]/").unwrap()));
+    }
+
     #[test]
     fn test_remove_url_fragment() {
         let validator = UrlValidator::new();